@@ -1,26 +1,644 @@
-#[macro_use]
 extern crate imser;
-use imser::{Document, TokenizeType};
+use imser::ingest::{
+    self, CrawlConfig, CsvConfig, CsvLoader, ErrorPolicy, JsonLinesConfig, JsonLinesLoader,
+    LoadError,
+};
+use imser::server::{Server, ServerConfig};
+use imser::shell::{Shell, ShellConfig};
+use imser::{
+    json, DocAndScore, Document, Facet, FacetCollector, FacetResult, FieldType, Highlighter,
+    HighlighterConfig, Index, IndexWriter, IndexWriterConfig, Schema, Sort, SyncPolicy,
+    TokenizeType, TopDocs, TopFieldDocs,
+};
 
+use std::collections::HashMap;
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+// exit codes
+const EXIT_OK: i32 = 0;
+const EXIT_NOT_FOUND: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+const USAGE: &str = "usage:
+    imser index <path> --out <index> [--tokenizer whitespace|japanese]
+                [--input text|lines|jsonl|csv|tsv] [--field <name>:text|stored|numeric|date|keyword[^boost]]...
+                [--map <field>=<key>]... [--on-error skip|abort]
+                [--include <glob>]... [--exclude <glob>]... [--threads <n>]
+                [--ram-budget <MiB>]
+    imser search <index> <query> [--top-k <n>] [--offset <n>] [--search-after <score>,<id>]
+                 [--sort <field>[:asc|desc][:first|last],...]
+                 [--facet <keyword field>[:<n>] | <field>:<range>,...]...
+                 [--format text|full|json|jsonl|tsv]
+    imser explain <index> <id> <query> [--format text|json]
+    imser stats <index>
+    imser shell <index> [--top-k <n>]
+    imser serve <index> [--port <n>] [--host <addr>] [--key-field <keyword field>]
+                [--wal <path>] [--wal-sync always|never|<ms>] [--commit-interval <ms>]
+
+exit status:
+    0  success
+    1  no document matched
+    2  invalid arguments or I/O error";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    // one document per file with path and title
+    Text,
+    // one document per line
+    Lines,
+    // one JSON object per line
+    JsonLines,
+    // comma or tab separated values with a header row
+    Csv,
+    Tsv,
+}
+
+impl Input {
+    // guesses from the file extension
+    fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") | Some("ndjson") => Input::JsonLines,
+            Some("csv") => Input::Csv,
+            Some("tsv") => Input::Tsv,
+            Some("txt") | Some("md") => Input::Text,
+            _ => Input::Lines,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    // highlighted snippet
+    Text,
+    // whole body
+    Full,
+    // an object with total hit count and hits
+    Json,
+    // an object per hit
+    JsonLines,
+    // a header row and a row per hit
+    Tsv,
+}
 
 fn main() {
-    let argv: Vec<String> = env::args().collect();
-    if argv.len() < 3 {
-        eprintln!("invalid arguments");
-        process::exit(1);
+    let argv: Vec<String> = env::args().skip(1).collect();
+    let result = match argv.first().map(String::as_str) {
+        Some("index") => index(&argv[1..]),
+        Some("search") => search(&argv[1..]),
+        Some("explain") => explain(&argv[1..]),
+        Some("stats") => stats(&argv[1..]),
+        Some("shell") => shell(&argv[1..]),
+        Some("serve") => serve(&argv[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+        _ => Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(EXIT_ERROR);
+        }
     }
+}
 
-    let term = argv[1].clone();
-    let sentences = &argv[2..];
+// (positional arguments, (option name, value))
+type Args<'a> = (Vec<&'a str>, Vec<(&'a str, &'a str)>);
 
-    let docs = sentences.iter().map(|s| doc!(s.as_str())).collect();
-    let docs = imser::search_main(TokenizeType::Whitespace, docs, &term);
-    if docs.is_empty() {
-        eprintln!("term not found: {}", &term);
+// splits arguments into positional ones and `--name value` options
+fn parse_args<'a>(args: &'a [String], options: &[&str]) -> Result<Args<'a>, String> {
+    let mut positional = Vec::new();
+    let mut values = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.strip_prefix("--") {
+            Some(name) if options.contains(&name) => match iter.next() {
+                Some(value) => values.push((name, value.as_str())),
+                None => return Err(format!("missing value for --{}", name)),
+            },
+            Some(name) => return Err(format!("unknown option: --{}", name)),
+            None => positional.push(arg.as_str()),
+        }
     }
-    for doc in docs {
-        println!("{}", doc.body);
+    Ok((positional, values))
+}
+
+fn options<'a>(values: &'a [(&str, &'a str)], name: &'a str) -> impl Iterator<Item = &'a str> {
+    values
+        .iter()
+        .filter(move |(n, _)| *n == name)
+        .map(|(_, v)| *v)
+}
+
+fn option<'a>(values: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    values
+        .iter()
+        .rev()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| *v)
+}
+
+// always, never, or an interval in milliseconds
+fn parse_wal_sync(values: &[(&str, &str)]) -> Result<SyncPolicy, String> {
+    match option(values, "wal-sync") {
+        None | Some("always") => Ok(SyncPolicy::Always),
+        Some("never") => Ok(SyncPolicy::Never),
+        Some(ms) => ms
+            .parse::<u64>()
+            .map(|ms| SyncPolicy::Interval(Duration::from_millis(ms)))
+            .map_err(|e| format!("invalid --wal-sync: {}", e)),
+    }
+}
+
+fn index(args: &[String]) -> Result<i32, String> {
+    let (positional, values) = parse_args(
+        args,
+        &[
+            "out",
+            "tokenizer",
+            "input",
+            "field",
+            "map",
+            "on-error",
+            "include",
+            "exclude",
+            "threads",
+            "ram-budget",
+        ],
+    )?;
+    let path = match positional.as_slice() {
+        [path] => Path::new(path),
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    let out = option(&values, "out").ok_or("--out is required")?;
+    let tokenize_type = option(&values, "tokenizer")
+        .map_or(Ok(TokenizeType::default()), str::parse::<TokenizeType>)?;
+    let input = match option(&values, "input") {
+        None => None,
+        Some("text") => Some(Input::Text),
+        Some("lines") => Some(Input::Lines),
+        Some("jsonl") => Some(Input::JsonLines),
+        Some("csv") => Some(Input::Csv),
+        Some("tsv") => Some(Input::Tsv),
+        Some(input) => return Err(format!("unknown input: {}", input)),
+    };
+    let policy = match option(&values, "on-error").unwrap_or("skip") {
+        "skip" => ErrorPolicy::Skip,
+        "abort" => ErrorPolicy::Abort,
+        policy => return Err(format!("unknown error policy: {}", policy)),
+    };
+    let threads = option(&values, "threads")
+        .map_or(Ok(1), str::parse::<usize>)
+        .map_err(|e| format!("invalid --threads: {}", e))?;
+    let ram_budget = option(&values, "ram-budget")
+        .map_or(Ok(0), str::parse::<usize>)
+        .map_err(|e| format!("invalid --ram-budget: {}", e))?;
+
+    let mut schema = Schema::new();
+    for spec in options(&values, "field") {
+        let field = Schema::parse_field(spec)?;
+        schema
+            .add_field(&field.name, field.field_type)
+            .set_boost(&field.name, field.boost);
+    }
+    // fields of text files, unless specified otherwise
+    for (name, field_type) in [("title", FieldType::Text), ("path", FieldType::Stored)] {
+        if schema.field_type(name).is_none() {
+            schema.add_field(name, field_type);
+        }
+    }
+    let crawl_config = CrawlConfig {
+        include: options(&values, "include").map(String::from).collect(),
+        exclude: options(&values, "exclude").map(String::from).collect(),
+        extensions: Vec::new(),
+    };
+    // field => JSON key or CSV column
+    let mut mapping = HashMap::new();
+    for spec in options(&values, "map") {
+        match spec.split_once('=') {
+            Some((field, key)) => mapping.insert(field.to_string(), key.to_string()),
+            None => return Err(format!("invalid mapping: {}", spec)),
+        };
     }
+
+    // an interrupted run is rerun from its input, so no write-ahead log is kept
+    let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+        tokenize_type,
+        schema: schema.clone(),
+        threads,
+        ram_budget: ram_budget.saturating_mul(1024 * 1024),
+        ..Default::default()
+    });
+    let mut count = 0;
+    let files = ingest::list_files(path, &crawl_config)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    for file in files {
+        let report = match input.unwrap_or_else(|| Input::detect(&file)) {
+            Input::Text => match ingest::read_document(&file) {
+                Ok(Some(doc)) => ingest::load([Ok(doc)], &mut index_writer, policy),
+                Ok(None) => {
+                    eprintln!("{}: skipped: binary file", file.display());
+                    continue;
+                }
+                Err(e) => Err(LoadError::Io(e)),
+            },
+            Input::Lines => {
+                let content =
+                    fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+                let docs = content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| Ok(Document::new(line.to_string())));
+                ingest::load(docs, &mut index_writer, policy)
+            }
+            Input::JsonLines => {
+                let reader = File::open(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+                let config = JsonLinesConfig {
+                    mapping: mapping.clone(),
+                };
+                let loader = JsonLinesLoader::new(BufReader::new(reader), &schema, config);
+                ingest::load(loader, &mut index_writer, policy)
+            }
+            input @ (Input::Csv | Input::Tsv) => {
+                let reader = File::open(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+                let config = CsvConfig {
+                    delimiter: if input == Input::Csv { b',' } else { b'\t' },
+                    mapping: mapping.clone(),
+                };
+                CsvLoader::new(BufReader::new(reader), &schema, config)
+                    .and_then(|loader| ingest::load(loader, &mut index_writer, policy))
+            }
+        };
+        let report = report.map_err(|e| match e {
+            LoadError::Line(e) => format!("{}:{}: {}", file.display(), e.line, e.reason),
+            LoadError::Io(e) => format!("{}: {}", file.display(), e),
+            LoadError::Index(e) => format!("{}: {}", file.display(), e),
+        })?;
+        for e in report.errors {
+            eprintln!("{}:{}: skipped: {}", file.display(), e.line, e.reason);
+        }
+        count += report.documents;
+    }
+    // flushed segments are merged into the index files without loading them
+    index_writer
+        .save(out)
+        .map_err(|e| format!("{}: {}", out, e))?;
+
+    eprintln!("indexed {} documents into {}", count, out);
+    Ok(EXIT_OK)
+}
+
+fn search(args: &[String]) -> Result<i32, String> {
+    let (positional, values) = parse_args(
+        args,
+        &["top-k", "offset", "search-after", "sort", "facet", "format"],
+    )?;
+    let (dir, query) = match positional.as_slice() {
+        [dir, query] => (*dir, *query),
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    let top_k = option(&values, "top-k")
+        .map_or(Ok(10), str::parse::<usize>)
+        .map_err(|e| format!("invalid --top-k: {}", e))?;
+    let offset = option(&values, "offset")
+        .map_or(Ok(0), str::parse::<usize>)
+        .map_err(|e| format!("invalid --offset: {}", e))?;
+    let mut collector = TopDocs::new(top_k).with_offset(offset);
+    if let Some(after) = option(&values, "search-after") {
+        // score and id of the last hit of the previous page
+        let cursor = after
+            .split_once(',')
+            .and_then(|(score, id)| Some((score.parse::<f32>().ok()?, id.parse::<usize>().ok()?)));
+        match cursor {
+            Some((score, id)) => {
+                collector = collector.search_after(DocAndScore::new_with_score(id, score))
+            }
+            None => return Err(format!("invalid --search-after: {}", after)),
+        }
+    }
+    let sort = match option(&values, "sort") {
+        None => None,
+        Some(_) if option(&values, "search-after").is_some() => {
+            return Err("--search-after can't be used with --sort".to_string())
+        }
+        Some(sort) => Some(sort.parse::<Sort>().map_err(|e| e.to_string())?),
+    };
+    let format = match option(&values, "format").unwrap_or("text") {
+        "text" => Format::Text,
+        "full" => Format::Full,
+        "json" => Format::Json,
+        "jsonl" => Format::JsonLines,
+        "tsv" => Format::Tsv,
+        format => return Err(format!("unknown format: {}", format)),
+    };
+
+    let index = Index::open(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let facets = options(&values, "facet")
+        .map(|spec| {
+            Facet::parse(index.schema(), spec).and_then(|facet| FacetCollector::new(&index, facet))
+        })
+        .collect::<imser::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    let searcher = index.searcher();
+    let (result, facets) = searcher
+        .parse_query(query)
+        .and_then(|query| match sort {
+            None => searcher.search(&query, (collector, facets)),
+            Some(sort) => searcher.search(
+                &query,
+                (
+                    TopFieldDocs::new(&index, sort, top_k)?.with_offset(offset),
+                    facets,
+                ),
+            ),
+        })
+        .map_err(|e| e.to_string())?;
+    let code = if result.total_hits == 0 {
+        EXIT_NOT_FOUND
+    } else {
+        EXIT_OK
+    };
+    let mut hits = Vec::with_capacity(result.docs.len());
+    for doc in result.docs.iter() {
+        match index
+            .doc(doc.doc_id)
+            .map_err(|e| format!("{}: {}", dir, e))?
+        {
+            Some(stored) => hits.push((doc, stored)),
+            None => return Err(format!("document {} is not stored", doc.doc_id)),
+        }
+    }
+
+    match format {
+        Format::Text | Format::Full => {
+            if result.total_hits == 0 {
+                eprintln!("term not found: {}", query);
+            }
+
+            // colorize matches only when writing to a terminal
+            let (pre_tag, post_tag) = if io::stdout().is_terminal() {
+                ("\x1b[1;31m", "\x1b[0m")
+            } else {
+                ("", "")
+            };
+            let highlighter = Highlighter::with_config(
+                query,
+                HighlighterConfig {
+                    tokenize_type: index.tokenize_type(),
+                    pre_tag: pre_tag.to_string(),
+                    post_tag: post_tag.to_string(),
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| e.to_string())?;
+            for (_, stored) in hits {
+                match format {
+                    Format::Text => println!(
+                        "{}",
+                        highlighter
+                            .snippet(&stored.body)
+                            .map_err(|e| e.to_string())?
+                    ),
+                    _ => println!("{}", stored.body),
+                }
+            }
+            print_facets(&facets);
+        }
+        Format::Json => {
+            let mut body =
+                json::search_result_to_json(&index, query, &result).map_err(|e| e.to_string())?;
+            if !facets.is_empty() {
+                body["facets"] = json::facets_to_json(&facets);
+            }
+            println!("{}", body);
+        }
+        Format::JsonLines => {
+            for doc in result.docs.iter() {
+                let hit = json::hit_to_json(&index, query, doc.doc_id, doc.score)
+                    .map_err(|e| e.to_string())?;
+                if let Some(mut hit) = hit {
+                    hit["total_hits"] = result.total_hits.into();
+                    println!("{}", hit);
+                }
+            }
+            if !facets.is_empty() {
+                println!("{{\"facets\":{}}}", json::facets_to_json(&facets));
+            }
+        }
+        Format::Tsv => {
+            let fields = index
+                .schema()
+                .fields()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>();
+            println!("id\tscore\t{}\thighlight", fields.join("\t"));
+            let highlighter = Highlighter::with_config(
+                query,
+                HighlighterConfig {
+                    tokenize_type: index.tokenize_type(),
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| e.to_string())?;
+            for (doc, stored) in hits {
+                let values = fields
+                    .iter()
+                    .map(|name| tsv_escape(stored.field(name).unwrap_or("")))
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    doc.doc_id,
+                    doc.score,
+                    values.join("\t"),
+                    tsv_escape(
+                        &highlighter
+                            .snippet(&stored.body)
+                            .map_err(|e| e.to_string())?
+                    )
+                );
+            }
+            print_facets(&facets);
+            eprintln!("{} hits", result.total_hits);
+        }
+    }
+    Ok(code)
+}
+
+// "<field>\t<value>\t<count>" lines after an empty line
+fn print_facets(facets: &[FacetResult]) {
+    if facets.is_empty() {
+        return;
+    }
+    println!();
+    for facet in facets {
+        for count in facet.counts.iter() {
+            println!("{}\t{}\t{}", facet.field, count.value, count.count);
+        }
+    }
+}
+
+fn tsv_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn explain(args: &[String]) -> Result<i32, String> {
+    let (positional, values) = parse_args(args, &["format"])?;
+    let (dir, id, query) = match positional.as_slice() {
+        [dir, id, query] => (*dir, *id, *query),
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    let id = id
+        .parse::<usize>()
+        .map_err(|e| format!("invalid id: {}", e))?;
+    let format = match option(&values, "format").unwrap_or("text") {
+        format @ ("text" | "json") => format,
+        format => return Err(format!("unknown format: {}", format)),
+    };
+
+    let index = Index::open(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let explanation = imser::explain(&index, query, id)
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("document {} is not stored", id))?;
+    match format {
+        "json" => println!("{}", json::explanation_to_json(&explanation)),
+        _ => print!("{}", explanation),
+    }
+    // the document doesn't match the query
+    if explanation.description.starts_with("no match") {
+        return Ok(EXIT_NOT_FOUND);
+    }
+    Ok(EXIT_OK)
+}
+
+fn stats(args: &[String]) -> Result<i32, String> {
+    let (positional, _) = parse_args(args, &[])?;
+    let dir = match positional.as_slice() {
+        [dir] => *dir,
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+
+    let index = Index::open(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let stats = index.stats();
+    println!("tokenizer\t{}", index.tokenize_type().as_str());
+    println!("documents\t{}", stats.doc_count);
+    println!("terms\t{}", stats.term_count);
+    println!("postings\t{}", stats.posting_count);
+    println!("tokens\t{}", stats.token_count);
+    Ok(EXIT_OK)
+}
+
+fn shell(args: &[String]) -> Result<i32, String> {
+    let (positional, values) = parse_args(args, &["top-k"])?;
+    let dir = match positional.as_slice() {
+        [dir] => *dir,
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    let top_k = option(&values, "top-k")
+        .map_or(Ok(10), str::parse::<usize>)
+        .map_err(|e| format!("invalid --top-k: {}", e))?;
+
+    let index = Index::open(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let mut shell = Shell::new(
+        index,
+        ShellConfig {
+            top_k,
+            color: io::stdout().is_terminal(),
+        },
+    );
+    let mut editor = DefaultEditor::new().map_err(|e| e.to_string())?;
+    // history is kept across sessions when HOME is known
+    let history = env::var_os("HOME").map(|home| Path::new(&home).join(".imser_history"));
+    if let Some(ref history) = history {
+        let _ = editor.load_history(history);
+    }
+
+    eprintln!("type :help for commands, :quit or Ctrl-D to exit");
+    loop {
+        let line = match editor.readline("imser> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+        match shell.execute(&line) {
+            Some(output) if output.is_empty() => {}
+            Some(output) => println!("{}", output),
+            None => break,
+        }
+    }
+    if let Some(ref history) = history {
+        let _ = editor.save_history(history);
+    }
+    Ok(EXIT_OK)
+}
+
+fn serve(args: &[String]) -> Result<i32, String> {
+    let (positional, values) = parse_args(
+        args,
+        &[
+            "port",
+            "host",
+            "key-field",
+            "wal",
+            "wal-sync",
+            "commit-interval",
+        ],
+    )?;
+    let dir = match positional.as_slice() {
+        [dir] => *dir,
+        _ => return Err(format!("invalid arguments\n{}", USAGE)),
+    };
+    let port = option(&values, "port")
+        .map_or(Ok(8080), str::parse::<u16>)
+        .map_err(|e| format!("invalid --port: {}", e))?;
+    let host = option(&values, "host").unwrap_or("127.0.0.1");
+
+    let wal_sync = parse_wal_sync(&values)?;
+    // updates are saved together once in the interval
+    let commit_interval = option(&values, "commit-interval")
+        .map_or(Ok(1000), str::parse::<u64>)
+        .map_err(|e| format!("invalid --commit-interval: {}", e))?;
+
+    let index = Index::open(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let server = Server::open(
+        index,
+        ServerConfig {
+            key_field: option(&values, "key-field").unwrap_or("id").to_string(),
+            path: Some(dir.into()),
+            wal: option(&values, "wal").map(PathBuf::from),
+            wal_sync,
+            commit_interval: Duration::from_millis(commit_interval),
+            ..Default::default()
+        },
+    )
+    .map_err(|e| format!("{}: {}", dir, e))?;
+    let listener =
+        TcpListener::bind((host, port)).map_err(|e| format!("{}:{}: {}", host, port, e))?;
+    eprintln!("listening on http://{}:{}", host, port);
+    server.serve(listener).map_err(|e| e.to_string())?;
+    Ok(EXIT_OK)
 }
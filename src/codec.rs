@@ -0,0 +1,110 @@
+use std::io::{self, Read, Write};
+
+// variable length encoding of unsigned integers (LEB128)
+pub fn write_vint<W: Write>(w: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+// bytes of the value written by `write_vint`
+pub fn vint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).max(1).div_ceil(7)
+}
+
+pub fn read_vint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte)?;
+        if shift >= 64 {
+            return Err(invalid_data("vint is too long"));
+        }
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+pub fn write_usize<W: Write>(w: &mut W, value: usize) -> io::Result<()> {
+    write_vint(w, value as u64)
+}
+
+pub fn read_usize<R: Read>(r: &mut R) -> io::Result<usize> {
+    let value = read_vint(r)?;
+    usize::try_from(value).map_err(|_| invalid_data("value overflows usize"))
+}
+
+pub fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_usize(w, s.len())?;
+    w.write_all(s.as_bytes())
+}
+
+pub fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_usize(r)?;
+    let mut buf = Vec::new();
+    r.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    String::from_utf8(buf).map_err(|_| invalid_data("string is not valid UTF-8"))
+}
+
+// a string of `read_string` borrowed from the bytes, which aren't checked to be UTF-8
+pub fn read_bytes<'a>(r: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = read_usize(r)?;
+    if r.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    let (bytes, rest) = r.split_at(len);
+    *r = rest;
+    Ok(bytes)
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codec::{read_string, read_vint, vint_len, write_str, write_vint};
+    use std::io::Cursor;
+
+    #[test]
+    fn vint_test() {
+        for value in [0, 1, 127, 128, 300, 16384, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            write_vint(&mut buf, value).unwrap();
+            assert_eq!(vint_len(value), buf.len());
+            assert_eq!(read_vint(&mut Cursor::new(buf)).unwrap(), value);
+        }
+
+        let mut buf = Vec::new();
+        write_vint(&mut buf, 127).unwrap();
+        assert_eq!(buf, vec![0x7f]);
+        let mut buf = Vec::new();
+        write_vint(&mut buf, 300).unwrap();
+        assert_eq!(buf, vec![0xac, 0x02]);
+
+        assert!(read_vint(&mut Cursor::new(vec![0x80])).is_err());
+    }
+
+    #[test]
+    fn string_test() {
+        let mut buf = Vec::new();
+        write_str(&mut buf, "関西国際空港").unwrap();
+        write_str(&mut buf, "").unwrap();
+        let mut r = Cursor::new(buf);
+        assert_eq!(read_string(&mut r).unwrap(), "関西国際空港");
+        assert_eq!(read_string(&mut r).unwrap(), "");
+        assert!(read_string(&mut r).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use crate::doc_values::SortKey;
+use crate::{DocAndScore, Index, Result, SearchResult, Sort};
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap};
+
+// receives matched documents of a search and builds its output
+pub trait Collector {
+    type Output;
+
+    // called with every matched document in ascending id order. an error stops
+    // the search, which returns it.
+    fn collect(&mut self, doc_id: usize, score: f32) -> Result<()>;
+
+    fn finish(self) -> Self::Output;
+}
+
+// passes every document to both collectors, e.g. to collect the top documents
+// and facets in one search
+impl<A: Collector, B: Collector> Collector for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    fn collect(&mut self, doc_id: usize, score: f32) -> Result<()> {
+        self.0.collect(doc_id, score)?;
+        self.1.collect(doc_id, score)
+    }
+
+    fn finish(self) -> Self::Output {
+        (self.0.finish(), self.1.finish())
+    }
+}
+
+// passes every document to all of the collectors
+impl<C: Collector> Collector for Vec<C> {
+    type Output = Vec<C::Output>;
+
+    fn collect(&mut self, doc_id: usize, score: f32) -> Result<()> {
+        for collector in self.iter_mut() {
+            collector.collect(doc_id, score)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Self::Output {
+        self.into_iter().map(Collector::finish).collect()
+    }
+}
+
+// keeps `limit` documents with the highest scores after skipping the first
+// `offset` ones. only offset + limit documents are held during the search.
+//
+// for deep paging, `search_after` with the last document of the previous page
+// skips every document ordered before it without holding them.
+#[derive(Debug)]
+pub struct TopDocs {
+    offset: usize,
+    limit: usize,
+    after: Option<DocAndScore>,
+    total_hits: usize,
+
+    // the worst of the kept documents is at the top
+    docs: BinaryHeap<DocAndScore>,
+}
+
+impl TopDocs {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            offset: 0,
+            limit,
+            after: None,
+            total_hits: 0,
+            docs: BinaryHeap::new(),
+        }
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // collects documents ordered after `after` by (score desc, doc id asc).
+    // `offset` is applied to the documents after it.
+    pub fn search_after(mut self, after: DocAndScore) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    fn capacity(&self) -> usize {
+        self.offset.saturating_add(self.limit)
+    }
+}
+
+impl Collector for TopDocs {
+    type Output = SearchResult;
+
+    fn collect(&mut self, doc_id: usize, score: f32) -> Result<()> {
+        self.total_hits += 1;
+        let doc = DocAndScore::new_with_score(doc_id, score);
+        if matches!(self.after, Some(after) if doc <= after) {
+            return Ok(());
+        }
+        if self.docs.len() < self.capacity() {
+            self.docs.push(doc);
+        } else if let Some(mut worst) = self.docs.peek_mut() {
+            if doc < *worst {
+                *worst = doc;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> SearchResult {
+        SearchResult {
+            total_hits: self.total_hits,
+            docs: self
+                .docs
+                .into_sorted_vec()
+                .into_iter()
+                .skip(self.offset)
+                .collect(),
+        }
+    }
+}
+
+// keeps `limit` documents ordered first by `sort` after skipping the first
+// `offset` ones, like `TopDocs` with the order of the sort
+#[derive(Debug)]
+pub struct TopFieldDocs<'a> {
+    index: &'a Index,
+    sort: Sort,
+    offset: usize,
+    limit: usize,
+    total_hits: usize,
+
+    // the last of the kept documents in the sort is at the top
+    docs: BinaryHeap<SortedDoc>,
+}
+
+impl<'a> TopFieldDocs<'a> {
+    // fails if a field of the sort isn't a numeric, date or keyword field of the index
+    pub fn new(index: &'a Index, sort: Sort, limit: usize) -> Result<Self> {
+        sort.check(index.schema())?;
+        Ok(Self {
+            index,
+            sort,
+            offset: 0,
+            limit,
+            total_hits: 0,
+            docs: BinaryHeap::new(),
+        })
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl Collector for TopFieldDocs<'_> {
+    type Output = SearchResult;
+
+    fn collect(&mut self, doc_id: usize, score: f32) -> Result<()> {
+        self.total_hits += 1;
+        let doc = SortedDoc {
+            keys: self.sort.keys(self.index, doc_id, score)?,
+            doc: DocAndScore::new_with_score(doc_id, score),
+        };
+        if self.docs.len() < self.offset.saturating_add(self.limit) {
+            self.docs.push(doc);
+        } else if let Some(mut last) = self.docs.peek_mut() {
+            if doc < *last {
+                *last = doc;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> SearchResult {
+        SearchResult {
+            total_hits: self.total_hits,
+            docs: self
+                .docs
+                .into_sorted_vec()
+                .into_iter()
+                .skip(self.offset)
+                .map(|d| d.doc)
+                .collect(),
+        }
+    }
+}
+
+// a document ordered by its sort keys, then by doc id
+#[derive(Debug)]
+struct SortedDoc {
+    keys: Vec<SortKey>,
+    doc: DocAndScore,
+}
+
+impl PartialEq for SortedDoc {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortedDoc {}
+
+impl PartialOrd for SortedDoc {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortedDoc {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.keys
+            .cmp(&other.keys)
+            .then_with(|| self.doc.doc_id.cmp(&other.doc.doc_id))
+    }
+}
+
+// counts matched documents
+#[derive(Debug, Default)]
+pub struct Count {
+    count: usize,
+}
+
+impl Count {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Collector for Count {
+    type Output = usize;
+
+    fn collect(&mut self, _doc_id: usize, _score: f32) -> Result<()> {
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> usize {
+        self.count
+    }
+}
+
+// ids of all matched documents
+#[derive(Debug, Default)]
+pub struct DocSet {
+    ids: BTreeSet<usize>,
+}
+
+impl DocSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Collector for DocSet {
+    type Output = BTreeSet<usize>;
+
+    fn collect(&mut self, doc_id: usize, _score: f32) -> Result<()> {
+        self.ids.insert(doc_id);
+        Ok(())
+    }
+
+    fn finish(self) -> BTreeSet<usize> {
+        self.ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collector::{Collector, Count, DocSet, TopDocs, TopFieldDocs};
+    use crate::{
+        doc, DocAndScore, FieldType, IndexWriter, IndexWriterConfig, Missing, Order, Result,
+        Schema, Sort, SortField, TermQuery,
+    };
+
+    fn ids(collector: TopDocs) -> Vec<usize> {
+        collector.finish().docs.iter().map(|d| d.doc_id).collect()
+    }
+
+    fn collect_all(collector: &mut TopDocs) {
+        collector.collect(0, 0.1).unwrap();
+        collector.collect(1, 0.3).unwrap();
+        collector.collect(2, 0.2).unwrap();
+        collector.collect(3, 0.3).unwrap();
+        collector.collect(4, 0.4).unwrap();
+    }
+
+    #[test]
+    fn top_docs_test() {
+        let mut collector = TopDocs::new(2);
+        collector.collect(0, 0.1).unwrap();
+        collector.collect(1, 0.3).unwrap();
+        collector.collect(2, 0.2).unwrap();
+        collector.collect(3, 0.3).unwrap();
+        let result = collector.finish();
+        assert_eq!(result.total_hits, 4);
+        assert_eq!(
+            result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn top_docs_offset_test() {
+        let mut collector = TopDocs::new(2).with_offset(1);
+        collect_all(&mut collector);
+        assert_eq!(collector.total_hits, 5);
+        assert_eq!(ids(collector), vec![1, 3]);
+
+        let mut collector = TopDocs::new(10).with_offset(3);
+        collect_all(&mut collector);
+        assert_eq!(ids(collector), vec![2, 0]);
+
+        let mut collector = TopDocs::new(2).with_offset(5);
+        collect_all(&mut collector);
+        assert_eq!(ids(collector), Vec::<usize>::new());
+
+        let mut collector = TopDocs::new(0);
+        collect_all(&mut collector);
+        assert_eq!(collector.finish().total_hits, 5);
+
+        let mut collector = TopDocs::new(usize::MAX).with_offset(1);
+        collect_all(&mut collector);
+        assert_eq!(ids(collector), vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn search_after_test() {
+        let mut collector = TopDocs::new(2).search_after(DocAndScore::new_with_score(1, 0.3));
+        collect_all(&mut collector);
+        assert_eq!(collector.total_hits, 5);
+        assert_eq!(ids(collector), vec![3, 2]);
+
+        // paging with the last document of each page visits every document once
+        let mut pages = Vec::new();
+        let mut after = None;
+        loop {
+            let mut collector = TopDocs::new(2);
+            if let Some(after) = after {
+                collector = collector.search_after(after);
+            }
+            collect_all(&mut collector);
+            let page = collector.finish().docs;
+            match page.last() {
+                None => break,
+                Some(last) => after = Some(*last),
+            }
+            pages.push(page.iter().map(|d| d.doc_id).collect::<Vec<_>>());
+        }
+        assert_eq!(pages, vec![vec![4, 1], vec![3, 2], vec![0]]);
+
+        let mut collector = TopDocs::new(2)
+            .with_offset(1)
+            .search_after(DocAndScore::new_with_score(4, 0.4));
+        collect_all(&mut collector);
+        assert_eq!(ids(collector), vec![3, 2]);
+    }
+
+    #[test]
+    fn top_field_docs_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("date", FieldType::Date)
+            .add_field("tag", FieldType::Keyword);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        for (body, date, tag) in [
+            ("dog", Some("2025-01-01"), Some("b")),
+            ("dog dog cat", None, Some("a")),
+            ("dog cat", Some("2025-03-01"), None),
+            ("dog cat cat", Some("2025-01-01"), Some("a")),
+            ("bird", Some("2025-02-01"), Some("c")),
+            ("fox", None, None),
+            ("raccoon", None, None),
+        ] {
+            let mut doc = doc!(body);
+            if let Some(date) = date {
+                doc.set_field("date", date);
+            }
+            if let Some(tag) = tag {
+                doc.set_field("tag", tag);
+            }
+            index_writer.write(doc).unwrap();
+        }
+        let index = index_writer.build();
+        let searcher = index.searcher();
+        let query = TermQuery::new("body", "dog");
+        let ids = |sort: Sort, limit: usize, offset: usize| {
+            let collector = TopFieldDocs::new(&index, sort, limit)
+                .unwrap()
+                .with_offset(offset);
+            let result = searcher.search(&query, collector).unwrap();
+            assert_eq!(result.total_hits, 4);
+            result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>()
+        };
+
+        // date desc, then score. doc 0 has a higher score than doc 3.
+        let sort = Sort::by_field("date", Order::Desc).then(SortField::score());
+        assert_eq!(ids(sort.clone(), 10, 0), vec![2, 0, 3, 1]);
+        assert_eq!(ids(sort, 2, 1), vec![0, 3]);
+
+        let sort = Sort::new()
+            .then(SortField::field("date", Order::Asc).with_missing(Missing::First))
+            .then(SortField::score().with_order(Order::Asc));
+        assert_eq!(ids(sort, 10, 0), vec![1, 3, 0, 2]);
+
+        // ties of every field are ordered by doc id
+        assert_eq!(
+            ids(Sort::by_field("tag", Order::Asc), 10, 0),
+            vec![1, 3, 0, 2]
+        );
+        assert_eq!(ids(Sort::by_field("tag", Order::Desc), 2, 0), vec![0, 1]);
+        assert_eq!(ids(Sort::new(), 10, 0), vec![0, 1, 2, 3]);
+
+        assert!(TopFieldDocs::new(&index, Sort::by_field("body", Order::Asc), 10).is_err());
+        assert!(TopFieldDocs::new(&index, Sort::by_field("x", Order::Asc), 10).is_err());
+    }
+
+    // sum of the scores
+    struct ScoreSum(f32);
+
+    impl Collector for ScoreSum {
+        type Output = f32;
+
+        fn collect(&mut self, _doc_id: usize, score: f32) -> Result<()> {
+            self.0 += score;
+            Ok(())
+        }
+
+        fn finish(self) -> f32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn collectors_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.write(doc!("bird")).unwrap();
+        index_writer.write(doc!("dog fox")).unwrap();
+        index_writer.write(doc!("raccoon")).unwrap();
+        let index = index_writer.build();
+        let searcher = index.searcher();
+        let query = TermQuery::new("body", "dog");
+
+        assert_eq!(searcher.search(&query, Count::new()).unwrap(), 2);
+        assert_eq!(
+            searcher
+                .search(&query, DocSet::new())
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+
+        let top_docs = searcher.search(&query, TopDocs::new(10)).unwrap();
+        let sum = searcher.search(&query, ScoreSum(0f32)).unwrap();
+        assert_eq!(sum, top_docs.docs.iter().map(|d| d.score).sum::<f32>());
+    }
+}
@@ -1,24 +1,47 @@
+use crate::schema::DEFAULT_FIELD;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     pub body: String,
+
+    // field name => value, except for the body
+    pub fields: BTreeMap<String, String>,
 }
 
 impl Document {
     pub fn new(body: String) -> Self {
-        Self { body }
+        Self {
+            body,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        if name == DEFAULT_FIELD {
+            return Some(self.body.as_str());
+        }
+        self.fields.get(name).map(String::as_str)
+    }
+
+    pub fn set_field<T: Into<String>>(&mut self, name: &str, value: T) {
+        if name == DEFAULT_FIELD {
+            self.body = value.into();
+        } else {
+            self.fields.insert(name.to_string(), value.into());
+        }
     }
 }
 
 #[macro_export]
 macro_rules! doc {
     ($x:expr) => {
-        crate::Document::new($x.to_string())
+        $crate::Document::new($x.to_string())
     };
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct DocAndScore {
     pub score: f32,
     pub doc_id: usize,
@@ -61,9 +84,23 @@ impl Ord for DocAndScore {
 
 #[cfg(test)]
 mod tests {
-    use crate::DocAndScore;
+    use crate::{DocAndScore, Document};
     use std::cmp::Ordering;
 
+    #[test]
+    fn document_field_test() {
+        let mut doc = Document::new("I am Taisuke".to_string());
+        assert_eq!(doc.field("body"), Some("I am Taisuke"));
+        assert_eq!(doc.field("title"), None);
+
+        doc.set_field("title", "introduction");
+        doc.set_field("body", "What is this");
+        assert_eq!(doc.field("title"), Some("introduction"));
+        assert_eq!(doc.field("body"), Some("What is this"));
+        assert_eq!(doc.body, "What is this");
+        assert_eq!(doc.fields.len(), 1);
+    }
+
     #[test]
     fn doc_and_score_compare_test() {
         let doc1 = DocAndScore::new_with_score(1, 1.0);
@@ -90,7 +127,7 @@ mod tests {
 
     #[test]
     fn doc_and_score_sort_test() {
-        let mut docs = vec![
+        let mut docs = [
             DocAndScore::new_with_score(1, 0.0),
             DocAndScore::new_with_score(0, 0.0),
             DocAndScore::new_with_score(2, 0.0),
@@ -101,7 +138,7 @@ mod tests {
             vec![0, 1, 2]
         );
 
-        let mut docs = vec![
+        let mut docs = [
             DocAndScore::new_with_score(1, 0.0),
             DocAndScore::new_with_score(0, 0.5),
             DocAndScore::new_with_score(2, 0.5),
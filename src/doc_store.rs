@@ -0,0 +1,328 @@
+use crate::codec::*;
+use crate::Document;
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// uncompressed bytes of documents after which a block is compressed. a block
+// has at least one document, however large it is.
+pub(crate) const BLOCK_SIZE: usize = 16 * 1024;
+
+// decompressed blocks kept by a reader
+pub(crate) const CACHE_BLOCKS: usize = 64;
+
+// writes documents in ascending order of ids into blocks compressed by LZ4.
+//
+// stored: block*, (first doc_id, offset)* of each block, block_count
+// block:  LZ4 compressed (doc_id, body, field_count, (name, value)*)*
+//
+// the u64s of the block index are little endian, so that a reader finds the
+// block of a document without decoding the others.
+pub(crate) struct StoreWriter<W: Write> {
+    w: W,
+
+    // bytes written to `w`
+    offset: u64,
+
+    block: Vec<u8>,
+    first_doc_id: Option<usize>,
+
+    // (first doc_id, offset) of each written block
+    blocks: Vec<(u64, u64)>,
+}
+
+impl<W: Write> StoreWriter<W> {
+    pub(crate) fn new(w: W) -> Self {
+        Self {
+            w,
+            offset: 0,
+            block: Vec::new(),
+            first_doc_id: None,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, id: usize, doc: &Document) -> io::Result<()> {
+        self.first_doc_id.get_or_insert(id);
+        write_usize(&mut self.block, id)?;
+        write_str(&mut self.block, &doc.body)?;
+        write_usize(&mut self.block, doc.fields.len())?;
+        for (name, value) in doc.fields.iter() {
+            write_str(&mut self.block, name)?;
+            write_str(&mut self.block, value)?;
+        }
+        if self.block.len() >= BLOCK_SIZE {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        let first_doc_id = match self.first_doc_id.take() {
+            None => return Ok(()),
+            Some(id) => id,
+        };
+        let compressed = lz4_flex::compress_prepend_size(&self.block);
+        self.w.write_all(&compressed)?;
+        self.blocks.push((first_doc_id as u64, self.offset));
+        self.offset += compressed.len() as u64;
+        self.block.clear();
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.flush_block()?;
+        for (first_doc_id, offset) in self.blocks.iter() {
+            self.w.write_all(&first_doc_id.to_le_bytes())?;
+            self.w.write_all(&offset.to_le_bytes())?;
+        }
+        self.w
+            .write_all(&(self.blocks.len() as u64).to_le_bytes())?;
+        Ok(self.w)
+    }
+}
+
+// reads documents written by `StoreWriter`, keeping recently used blocks
+// decompressed
+#[derive(Debug)]
+pub(crate) struct StoreReader {
+    // (first doc_id, offset) of each block
+    blocks: Vec<(usize, usize)>,
+
+    // where the block index starts, which is the end of the last block
+    blocks_end: usize,
+
+    cache: Mutex<BlockCache>,
+}
+
+impl StoreReader {
+    // reads the block index of `data`, the whole stored file
+    pub(crate) fn open(data: &[u8]) -> io::Result<Self> {
+        let u64_at = |start: usize| -> io::Result<usize> {
+            let bytes = data
+                .get(start..start + 8)
+                .ok_or_else(|| invalid_data("stored file is too short"))?;
+            usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap()))
+                .map_err(|_| invalid_data("value overflows usize"))
+        };
+        let tail = data
+            .len()
+            .checked_sub(8)
+            .ok_or_else(|| invalid_data("stored file is too short"))?;
+        let block_count = u64_at(tail)?;
+        let blocks_end = block_count
+            .checked_mul(16)
+            .and_then(|len| tail.checked_sub(len))
+            .ok_or_else(|| invalid_data("stored file is too short"))?;
+
+        let mut blocks = Vec::with_capacity(block_count);
+        for i in 0..block_count {
+            let block = (
+                u64_at(blocks_end + i * 16)?,
+                u64_at(blocks_end + i * 16 + 8)?,
+            );
+            let out_of_order = blocks
+                .last()
+                .is_some_and(|last: &(usize, usize)| last.0 >= block.0 || last.1 >= block.1);
+            if out_of_order || block.1 >= blocks_end {
+                return Err(invalid_data("block index is out of order"));
+            }
+            blocks.push(block);
+        }
+        Ok(Self {
+            blocks,
+            blocks_end,
+            cache: Mutex::new(BlockCache::new(CACHE_BLOCKS)),
+        })
+    }
+
+    pub(crate) fn doc(&self, data: &[u8], id: usize) -> io::Result<Option<Document>> {
+        // the last block starting at or before the document
+        let i = match self.blocks.partition_point(|(first, _)| *first <= id) {
+            0 => return Ok(None),
+            i => i - 1,
+        };
+        let block = self.cache_block(data, i)?;
+        let mut r = &block[..];
+        while !r.is_empty() {
+            let (doc_id, doc) = read_doc(&mut r)?;
+            if doc_id == id {
+                return Ok(Some(doc));
+            }
+            if doc_id > id {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    // passes every document in ascending order of ids to `f`. blocks are
+    // decompressed without the cache, so that a scan doesn't evict the others.
+    pub(crate) fn for_each(
+        &self,
+        data: &[u8],
+        mut f: impl FnMut(usize, Document) -> io::Result<()>,
+    ) -> io::Result<()> {
+        for i in 0..self.blocks.len() {
+            let block = self.decompress(data, i)?;
+            let mut r = &block[..];
+            while !r.is_empty() {
+                let (id, doc) = read_doc(&mut r)?;
+                f(id, doc)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cache_block(&self, data: &[u8], i: usize) -> io::Result<Arc<Vec<u8>>> {
+        if let Some(block) = self.lock_cache().get(i) {
+            return Ok(block);
+        }
+        // decompressed without the lock. threads missing the same block may
+        // decompress it more than once.
+        let block = Arc::new(self.decompress(data, i)?);
+        self.lock_cache().insert(i, block.clone());
+        Ok(block)
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, BlockCache> {
+        // the cache is consistent even if a thread panicked holding the lock
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn decompress(&self, data: &[u8], i: usize) -> io::Result<Vec<u8>> {
+        let start = self.blocks[i].1;
+        let end = self.blocks.get(i + 1).map_or(self.blocks_end, |b| b.1);
+        let compressed = &data[start..end];
+        // LZ4 expands data at most 255 times, which bounds the allocation by a
+        // broken size
+        let size = compressed
+            .get(..4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            .ok_or_else(|| invalid_data("block is too short"))?;
+        if size > compressed.len().saturating_mul(255) {
+            return Err(invalid_data("block size is too large"));
+        }
+        lz4_flex::decompress_size_prepended(compressed)
+            .map_err(|e| invalid_data(&format!("broken block: {}", e)))
+    }
+
+    #[cfg(test)]
+    fn cached_blocks(&self) -> Vec<usize> {
+        self.lock_cache().blocks.iter().map(|(i, _)| *i).collect()
+    }
+}
+
+fn read_doc(r: &mut &[u8]) -> io::Result<(usize, Document)> {
+    let id = read_usize(r)?;
+    let mut doc = Document::new(read_string(r)?);
+    for _ in 0..read_usize(r)? {
+        let name = read_string(r)?;
+        let value = read_string(r)?;
+        doc.set_field(&name, value);
+    }
+    Ok((id, doc))
+}
+
+// decompressed blocks by index, of which the least recently used one is
+// evicted when it's full
+#[derive(Debug)]
+struct BlockCache {
+    capacity: usize,
+
+    // the most recently used first
+    blocks: VecDeque<(usize, Arc<Vec<u8>>)>,
+}
+
+impl BlockCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn get(&mut self, i: usize) -> Option<Arc<Vec<u8>>> {
+        let pos = self.blocks.iter().position(|(block, _)| *block == i)?;
+        let entry = self.blocks.remove(pos)?;
+        let block = entry.1.clone();
+        self.blocks.push_front(entry);
+        Some(block)
+    }
+
+    fn insert(&mut self, i: usize, block: Arc<Vec<u8>>) {
+        if self.blocks.iter().any(|(cached, _)| *cached == i) {
+            return;
+        }
+        if self.blocks.len() >= self.capacity {
+            self.blocks.pop_back();
+        }
+        self.blocks.push_front((i, block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc;
+    use crate::doc_store::{BlockCache, StoreReader, StoreWriter, BLOCK_SIZE, CACHE_BLOCKS};
+    use std::sync::Arc;
+
+    #[test]
+    fn store_test() {
+        let body = "dog cat fox ".repeat(100);
+        let ids = (0..500).filter(|id| id % 3 != 0).collect::<Vec<_>>();
+        let mut writer = StoreWriter::new(Vec::new());
+        for id in ids.iter() {
+            let mut doc = doc!(format!("{} {}", id, body));
+            doc.set_field("id", id.to_string());
+            writer.add(*id, &doc).unwrap();
+        }
+        let data = writer.finish().unwrap();
+        // repeated text is compressed
+        assert!(data.len() < ids.len() * body.len() / 10);
+
+        let reader = StoreReader::open(&data).unwrap();
+        assert!(reader.blocks.len() > 1);
+        assert!(reader.blocks.len() <= ids.len() * body.len() / BLOCK_SIZE + 1);
+        for id in [1, 2, 250, 499] {
+            let doc = reader.doc(&data, id).unwrap().unwrap();
+            assert_eq!(doc.field("id"), Some(id.to_string().as_str()));
+            assert!(doc.body.starts_with(&format!("{} dog", id)));
+        }
+        for id in [0, 3, 498, 500, 10000] {
+            assert_eq!(reader.doc(&data, id).unwrap(), None);
+        }
+        assert!(reader.cached_blocks().len() <= CACHE_BLOCKS);
+
+        let mut scanned = Vec::new();
+        reader
+            .for_each(&data, |id, _| {
+                scanned.push(id);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(scanned, ids);
+
+        let data = StoreWriter::new(Vec::new()).finish().unwrap();
+        let reader = StoreReader::open(&data).unwrap();
+        assert_eq!(reader.doc(&data, 0).unwrap(), None);
+
+        assert!(StoreReader::open(&[1, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(StoreReader::open(&[]).is_err());
+    }
+
+    #[test]
+    fn block_cache_test() {
+        let block = |n: u8| Arc::new(vec![n]);
+        let mut cache = BlockCache::new(2);
+        cache.insert(0, block(0));
+        cache.insert(1, block(1));
+        assert_eq!(cache.get(0), Some(block(0)));
+        // 1 is the least recently used
+        cache.insert(2, block(2));
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(0), Some(block(0)));
+        assert_eq!(cache.get(2), Some(block(2)));
+    }
+}
@@ -0,0 +1,532 @@
+use crate::codec::{invalid_data, read_bytes, vint_len, write_str};
+use crate::numeric::{parse_value, sortable_bits};
+use crate::store::index_error;
+use crate::{Document, Error, FieldType, Index, Result, Schema};
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+// value of a document without one in a column file
+const NO_VALUE: u64 = u64::MAX;
+
+// keyword values of a column which are written once. values which appear again
+// after this many others are written again, so that memory doesn't grow with
+// the number of distinct values.
+const SHARED_VALUES: usize = 4096;
+
+// value of a numeric, date or keyword field of a document
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DocValue {
+    Number(f64),
+    Keyword(String),
+}
+
+impl DocValue {
+    pub(crate) fn as_ref(&self) -> ValueRef<'_> {
+        match self {
+            DocValue::Number(value) => ValueRef::Number(*value),
+            DocValue::Keyword(value) => ValueRef::Keyword(value),
+        }
+    }
+}
+
+// a value of a column, which is borrowed from memory or a column file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ValueRef<'a> {
+    Number(f64),
+    Keyword(&'a str),
+}
+
+// values of a field indexed by doc id. None is a document without the field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Column {
+    values: Vec<Option<DocValue>>,
+}
+
+impl Column {
+    pub(crate) fn set(&mut self, doc_id: usize, value: DocValue) {
+        if self.values.len() <= doc_id {
+            self.values.resize(doc_id + 1, None);
+        }
+        self.values[doc_id] = Some(value);
+    }
+
+    pub(crate) fn remove(&mut self, doc_id: usize) {
+        if let Some(value) = self.values.get_mut(doc_id) {
+            *value = None;
+        }
+    }
+
+    pub(crate) fn get(&self, doc_id: usize) -> Option<&DocValue> {
+        self.values.get(doc_id)?.as_ref()
+    }
+
+    // writes the values of the first `doc_slots` doc ids in the layout which
+    // `ColumnReader` reads
+    pub(crate) fn write_to(
+        &self,
+        w: &mut impl Write,
+        doc_slots: usize,
+        keyword: bool,
+    ) -> io::Result<()> {
+        write_column(w, doc_slots, keyword, |f| {
+            for (doc_id, value) in self.values.iter().enumerate().take(doc_slots) {
+                if let Some(value) = value {
+                    f(doc_id, value.as_ref())?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// keyword values of a column which are written once, and their offsets
+struct SharedValues {
+    offsets: HashMap<String, u64>,
+    end: u64,
+}
+
+impl SharedValues {
+    fn new(doc_slots: usize) -> Self {
+        SharedValues {
+            offsets: HashMap::new(),
+            end: doc_slots as u64 * 8,
+        }
+    }
+
+    // returns the offset of a value and whether it is written there first
+    fn offset(&mut self, value: &str) -> (u64, bool) {
+        if let Some(offset) = self.offsets.get(value) {
+            return (*offset, false);
+        }
+        if self.offsets.len() >= SHARED_VALUES {
+            self.offsets.clear();
+        }
+        let offset = self.end;
+        self.end += (vint_len(value.len() as u64) + value.len()) as u64;
+        self.offsets.insert(value.to_string(), offset);
+        (offset, true)
+    }
+}
+
+// writes a column file of `doc_slots` doc ids in the layout which
+// `ColumnReader` reads. `for_each` passes (doc id, value) of the documents with
+// a value in ascending order of ids, and is called again for the values of a
+// keyword field after their offsets are written.
+pub(crate) fn write_column(
+    w: &mut impl Write,
+    doc_slots: usize,
+    keyword: bool,
+    mut for_each: impl FnMut(&mut dyn FnMut(usize, ValueRef) -> io::Result<()>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut shared = SharedValues::new(doc_slots);
+    let mut next_doc = 0;
+    for_each(&mut |doc_id, value| {
+        for _ in next_doc..doc_id {
+            w.write_all(&NO_VALUE.to_le_bytes())?;
+        }
+        next_doc = doc_id + 1;
+        let entry = match value {
+            ValueRef::Number(value) => value.to_bits(),
+            ValueRef::Keyword(value) => shared.offset(value).0,
+        };
+        w.write_all(&entry.to_le_bytes())
+    })?;
+    for _ in next_doc..doc_slots {
+        w.write_all(&NO_VALUE.to_le_bytes())?;
+    }
+    if !keyword {
+        return Ok(());
+    }
+
+    // the values in the order of their offsets
+    let mut shared = SharedValues::new(doc_slots);
+    for_each(&mut |_, value| match value {
+        ValueRef::Keyword(value) if shared.offset(value).1 => write_str(w, value),
+        _ => Ok(()),
+    })
+}
+
+// values of a field written by `write_column`, which are read without
+// reading the others.
+//
+// numeric and date fields: u64 bits of the f64 value of each doc id
+// keyword fields:          u64 offset of the value of each doc id, (value)* in
+//                          the order the values appear
+//
+// u64s are little endian, and NO_VALUE is a document without the field.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ColumnReader<'a> {
+    data: &'a [u8],
+    doc_slots: usize,
+    keyword: bool,
+}
+
+impl<'a> ColumnReader<'a> {
+    pub(crate) fn new(data: &'a [u8], doc_slots: usize, keyword: bool) -> io::Result<Self> {
+        let table_len = doc_slots
+            .checked_mul(8)
+            .ok_or_else(|| invalid_data("column file is too short"))?;
+        if data.len() < table_len || (!keyword && data.len() != table_len) {
+            return Err(invalid_data("file size doesn't match the meta"));
+        }
+        Ok(Self {
+            data,
+            doc_slots,
+            keyword,
+        })
+    }
+
+    pub(crate) fn get(&self, doc_id: usize) -> io::Result<Option<ValueRef<'a>>> {
+        if doc_id >= self.doc_slots {
+            return Ok(None);
+        }
+        let bytes = &self.data[doc_id * 8..doc_id * 8 + 8];
+        let value = u64::from_le_bytes(bytes.try_into().unwrap());
+        if value == NO_VALUE {
+            return Ok(None);
+        }
+        if !self.keyword {
+            return Ok(Some(ValueRef::Number(f64::from_bits(value))));
+        }
+        let mut r = usize::try_from(value)
+            .ok()
+            .and_then(|offset| self.data.get(offset..))
+            .ok_or_else(|| invalid_data("offset is out of range"))?;
+        let value = std::str::from_utf8(read_bytes(&mut r)?)
+            .map_err(|_| invalid_data("string is not valid UTF-8"))?;
+        Ok(Some(ValueRef::Keyword(value)))
+    }
+}
+
+// values of a field in memory or in a column file
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ColumnRef<'a> {
+    Memory(&'a Column),
+    Mapped(ColumnReader<'a>),
+}
+
+impl<'a> ColumnRef<'a> {
+    pub(crate) fn get(&self, doc_id: usize) -> io::Result<Option<ValueRef<'a>>> {
+        match self {
+            ColumnRef::Memory(column) => Ok(column.get(doc_id).map(DocValue::as_ref)),
+            ColumnRef::Mapped(reader) => reader.get(doc_id),
+        }
+    }
+}
+
+// (field, value) of every numeric, date and keyword field of the document
+// with a valid value
+pub(crate) fn doc_values(schema: &Schema, doc: &Document) -> Vec<(String, DocValue)> {
+    schema
+        .fields()
+        .filter_map(|field| {
+            let text = doc.field(&field.name)?;
+            let value = match field.field_type {
+                FieldType::Keyword if !text.is_empty() => DocValue::Keyword(text.to_string()),
+                FieldType::Numeric | FieldType::Date => {
+                    DocValue::Number(parse_value(field.field_type, text)?)
+                }
+                _ => return None,
+            };
+            Some((field.name.clone(), value))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+// where documents without a value of the field are placed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Missing {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortField {
+    // a numeric, date or keyword field, or None to sort by score
+    pub field: Option<String>,
+    pub order: Order,
+
+    // placement of missing values, which doesn't depend on the order
+    pub missing: Missing,
+}
+
+impl SortField {
+    pub fn field(name: &str, order: Order) -> Self {
+        Self {
+            field: Some(name.to_string()),
+            order,
+            missing: Missing::Last,
+        }
+    }
+
+    // highest scores first
+    pub fn score() -> Self {
+        Self {
+            field: None,
+            order: Order::Desc,
+            missing: Missing::Last,
+        }
+    }
+
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn with_missing(mut self, missing: Missing) -> Self {
+        self.missing = missing;
+        self
+    }
+}
+
+// order of search results by fields and score, such as "date desc, then score".
+// documents which tie on every sort field are ordered by doc id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sort {
+    fields: Vec<SortField>,
+}
+
+impl Sort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn by_field(name: &str, order: Order) -> Self {
+        Self::new().then(SortField::field(name, order))
+    }
+
+    pub fn by_score() -> Self {
+        Self::new().then(SortField::score())
+    }
+
+    // breaks ties of the previous fields by `field`
+    pub fn then(mut self, field: SortField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub fn fields(&self) -> &[SortField] {
+        &self.fields
+    }
+
+    // fails if a sort field isn't a numeric, date or keyword field of the schema
+    pub(crate) fn check(&self, schema: &Schema) -> Result<()> {
+        for name in self.fields.iter().filter_map(|f| f.field.as_deref()) {
+            match schema.field_type(name) {
+                Some(FieldType::Numeric | FieldType::Date | FieldType::Keyword) => {}
+                _ => {
+                    return Err(Error::QueryParse(format!(
+                        "field is not sortable: {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // keys of a document, which compare in the order of the sort
+    pub(crate) fn keys(&self, index: &Index, doc_id: usize, score: f32) -> Result<Vec<SortKey>> {
+        let mut keys = Vec::with_capacity(self.fields.len());
+        for sort_field in self.fields.iter() {
+            let value = match &sort_field.field {
+                None => Some(SortValue::Number(sortable_bits(score as f64))),
+                Some(name) => match index.doc_value(name, doc_id).map_err(index_error)? {
+                    None => None,
+                    Some(ValueRef::Number(v)) => Some(SortValue::Number(sortable_bits(v))),
+                    Some(ValueRef::Keyword(v)) => Some(SortValue::Keyword(v.to_string())),
+                },
+            };
+            keys.push(match (value, sort_field.order, sort_field.missing) {
+                (None, _, Missing::First) => SortKey::MissingFirst,
+                (None, _, Missing::Last) => SortKey::MissingLast,
+                (Some(value), Order::Asc, _) => SortKey::Asc(value),
+                (Some(value), Order::Desc, _) => SortKey::Desc(Reverse(value)),
+            });
+        }
+        Ok(keys)
+    }
+}
+
+// parses "date:desc,score" as a comma separated list of
+// `field[:asc|desc][:first|last]`, where `score` sorts by score.
+// fields are ascending and scores are descending unless specified otherwise,
+// and missing values are last.
+impl FromStr for Sort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut sort = Sort::new();
+        for spec in s.split(',') {
+            let mut parts = spec.trim().split(':');
+            let mut field = match parts.next() {
+                Some("score") => SortField::score(),
+                Some(name) if !name.is_empty() => SortField::field(name, Order::Asc),
+                _ => return Err(Error::QueryParse(format!("invalid sort: {}", s))),
+            };
+            for part in parts {
+                field = match part {
+                    "asc" => field.with_order(Order::Asc),
+                    "desc" => field.with_order(Order::Desc),
+                    "first" => field.with_missing(Missing::First),
+                    "last" => field.with_missing(Missing::Last),
+                    _ => return Err(Error::QueryParse(format!("invalid sort: {}", s))),
+                };
+            }
+            sort = sort.then(field);
+        }
+        Ok(sort)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SortValue {
+    Number(u64),
+    Keyword(String),
+}
+
+// a field of a document in a sort. the variants are ordered so that
+// missing values come first or last regardless of the order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SortKey {
+    MissingFirst,
+    Asc(SortValue),
+    Desc(Reverse<SortValue>),
+    MissingLast,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc_values::{
+        doc_values, Column, ColumnReader, DocValue, Missing, Order, Sort, SortField, ValueRef,
+        SHARED_VALUES,
+    };
+    use crate::{doc, FieldType, Schema};
+
+    #[test]
+    fn column_test() {
+        let mut column = Column::default();
+        column.set(2, DocValue::Number(1f64));
+        column.set(0, DocValue::Keyword("rust".to_string()));
+        assert_eq!(column.get(0), Some(&DocValue::Keyword("rust".to_string())));
+        assert_eq!(column.get(1), None);
+        assert_eq!(column.get(2), Some(&DocValue::Number(1f64)));
+        assert_eq!(column.get(3), None);
+
+        column.remove(2);
+        column.remove(10);
+        assert_eq!(column.get(2), None);
+    }
+
+    #[test]
+    fn column_reader_test() {
+        let mut column = Column::default();
+        column.set(0, DocValue::Number(-1.5));
+        column.set(2, DocValue::Number(1e10));
+        let mut data = Vec::new();
+        column.write_to(&mut data, 4, false).unwrap();
+        let reader = ColumnReader::new(&data, 4, false).unwrap();
+        assert_eq!(reader.get(0).unwrap(), Some(ValueRef::Number(-1.5)));
+        assert_eq!(reader.get(1).unwrap(), None);
+        assert_eq!(reader.get(2).unwrap(), Some(ValueRef::Number(1e10)));
+        assert_eq!(reader.get(3).unwrap(), None);
+        assert_eq!(reader.get(4).unwrap(), None);
+        assert!(ColumnReader::new(&data, 3, false).is_err());
+
+        // keyword values are written once
+        let mut column = Column::default();
+        for (doc_id, value) in [(0, "rust"), (1, "go"), (3, "rust")] {
+            column.set(doc_id, DocValue::Keyword(value.to_string()));
+        }
+        let mut data = Vec::new();
+        column.write_to(&mut data, 4, true).unwrap();
+        assert_eq!(data.len(), 4 * 8 + 3 + 5);
+        let reader = ColumnReader::new(&data, 4, true).unwrap();
+        assert_eq!(reader.get(0).unwrap(), Some(ValueRef::Keyword("rust")));
+        assert_eq!(reader.get(1).unwrap(), Some(ValueRef::Keyword("go")));
+        assert_eq!(reader.get(2).unwrap(), None);
+        assert_eq!(reader.get(3).unwrap(), Some(ValueRef::Keyword("rust")));
+
+        // a broken offset is found when it's read
+        data[8..16].copy_from_slice(&1000u64.to_le_bytes());
+        let reader = ColumnReader::new(&data, 4, true).unwrap();
+        assert!(reader.get(1).is_err());
+        assert!(ColumnReader::new(&data[..31], 4, true).is_err());
+
+        // values are written again once more distinct values than are kept appear
+        let mut column = Column::default();
+        let doc_slots = SHARED_VALUES + 3;
+        for doc_id in 0..doc_slots {
+            let value = match doc_id {
+                0 | 1 => "first".to_string(),
+                _ => doc_id.to_string(),
+            };
+            column.set(doc_id, DocValue::Keyword(value));
+        }
+        column.set(doc_slots - 1, DocValue::Keyword("first".to_string()));
+        let mut data = Vec::new();
+        column.write_to(&mut data, doc_slots, true).unwrap();
+        let reader = ColumnReader::new(&data, doc_slots, true).unwrap();
+        let offset = |doc_id: usize| &data[doc_id * 8..doc_id * 8 + 8];
+        assert_eq!(offset(0), offset(1));
+        assert_ne!(offset(0), offset(doc_slots - 1));
+        for doc_id in 0..doc_slots {
+            let value = reader.get(doc_id).unwrap().unwrap();
+            assert_eq!(value, column.get(doc_id).unwrap().as_ref());
+        }
+    }
+
+    #[test]
+    fn doc_values_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("price", FieldType::Numeric)
+            .add_field("date", FieldType::Date)
+            .add_field("tag", FieldType::Keyword)
+            .add_field("lang", FieldType::Keyword);
+        let mut doc = doc!("I am Taisuke");
+        doc.set_field("title", "introduction");
+        doc.set_field("price", "12.5");
+        doc.set_field("date", "1970-01-02");
+        doc.set_field("tag", "Rust Lang");
+        doc.set_field("lang", "");
+        assert_eq!(
+            doc_values(&schema, &doc),
+            vec![
+                ("price".to_string(), DocValue::Number(12.5)),
+                ("date".to_string(), DocValue::Number(86400f64)),
+                (
+                    "tag".to_string(),
+                    DocValue::Keyword("Rust Lang".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_sort_test() {
+        assert_eq!(
+            "date:desc,score".parse::<Sort>().unwrap(),
+            Sort::by_field("date", Order::Desc).then(SortField::score())
+        );
+        assert_eq!(
+            " price:asc:first , score:asc".parse::<Sort>().unwrap(),
+            Sort::new()
+                .then(SortField::field("price", Order::Asc).with_missing(Missing::First))
+                .then(SortField::score().with_order(Order::Asc))
+        );
+        for sort in ["", "date:up", "date,", ":desc"] {
+            assert!(sort.parse::<Sort>().is_err(), "{}", sort);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    // failed to read or write files
+    Io(io::Error),
+
+    // files of the index are broken or written by an unsupported version
+    CorruptIndex(String),
+
+    // the tokenizer couldn't be created, e.g. its dictionary is missing
+    AnalyzerInit(String),
+
+    // the query has invalid syntax or refers to a field which can't be searched
+    QueryParse(String),
+
+    // a document doesn't conform to the schema of the index
+    SchemaViolation(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::CorruptIndex(msg) => write!(f, "corrupt index: {}", msg),
+            Error::AnalyzerInit(msg) => write!(f, "failed to initialize analyzer: {}", msg),
+            Error::QueryParse(msg) => write!(f, "invalid query: {}", msg),
+            Error::SchemaViolation(msg) => write!(f, "schema violation: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,353 @@
+use crate::query::{parse_query, PhraseQuery, Query, TermClause};
+use crate::schema::{split_field_term, DEFAULT_FIELD};
+use crate::store::index_error;
+use crate::{Index, Result, Term};
+
+use std::fmt;
+
+// a node of the score breakdown. `value` is computed from the values of `details`
+// as told by `description`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub value: f32,
+    pub description: String,
+    pub details: Vec<Explanation>,
+}
+
+impl Explanation {
+    pub fn new<S: Into<String>>(value: f32, description: S) -> Self {
+        Self {
+            value,
+            description: description.into(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with_details<S: Into<String>>(
+        value: f32,
+        description: S,
+        details: Vec<Explanation>,
+    ) -> Self {
+        Self {
+            value,
+            description: description.into(),
+            details,
+        }
+    }
+
+    fn fmt_indent(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{} = {}",
+            "",
+            self.value,
+            self.description,
+            indent = depth * 2
+        )?;
+        for detail in self.details.iter() {
+            detail.fmt_indent(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+// prints the tree with an indented line per node
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indent(f, 0)
+    }
+}
+
+// explains the score of `doc_id` for `sentence` as `search` computes it.
+// returns None if the document is not stored.
+pub fn explain(index: &Index, sentence: &str, doc_id: usize) -> Result<Option<Explanation>> {
+    if index.doc(doc_id)?.is_none() {
+        return Ok(None);
+    }
+    let query = parse_query(index.tokenize_type, &index.schema, sentence)?;
+
+    let mut clauses = Vec::new();
+    let mut missing = Vec::new();
+    for term in query.terms.iter() {
+        let clause = explain_term(index, term, doc_id)?;
+        if clause.details.is_empty() {
+            missing.push(display_term(&term.term));
+        }
+        clauses.push(clause);
+    }
+    for phrase in query.phrases.iter() {
+        let clause = explain_phrase(index, phrase, doc_id)?;
+        if clause.details.is_empty() {
+            missing.push(display_phrase(phrase));
+        }
+        clauses.push(clause);
+    }
+    // ranges filter documents without adding to the score
+    for range in query.ranges.iter() {
+        if range.matches(index, doc_id)? {
+            clauses.push(Explanation::new(0f32, format!("filter({})", range)));
+        } else {
+            missing.push(range.to_string());
+            clauses.push(Explanation::new(0f32, format!("no match of {}", range)));
+        }
+    }
+    if clauses.is_empty() || !missing.is_empty() {
+        let description = if clauses.is_empty() {
+            "no match, no terms in the query".to_string()
+        } else {
+            format!("no match, missing terms: {}", missing.join(", "))
+        };
+        return Ok(Some(Explanation::with_details(0f32, description, clauses)));
+    }
+
+    let score = clauses.iter().map(|clause| clause.value).sum();
+    Ok(Some(Explanation::with_details(score, "sum of:", clauses)))
+}
+
+// boost * tf * idf of a term, with no details if the document doesn't contain the term
+fn explain_term(index: &Index, clause: &TermClause, doc_id: usize) -> Result<Explanation> {
+    let term = &clause.term;
+    let description = format!("weight({}), product of:", display_term(term));
+    let (field, _) = split_field_term(term);
+    let freq = index.freq(doc_id, term).map_err(index_error)?;
+    if freq == 0 {
+        return Ok(Explanation::new(
+            0f32,
+            format!("no match of {}", display_term(term)),
+        ));
+    }
+    let field_length = index.field_length(doc_id, field).map_err(index_error)?;
+
+    let tf = index.tf(doc_id, term, freq).map_err(index_error)?;
+    let tf = Explanation::with_details(
+        tf,
+        "tf, computed as freq / field length from:",
+        vec![
+            Explanation::new(freq as f32, "freq, occurrences of the term in the field"),
+            Explanation::new(field_length as f32, "field length, the doc length norm"),
+        ],
+    );
+
+    let doc_freq = index.doc_freq(term).map_err(index_error)?;
+    let idf = Explanation::with_details(
+        index.idf(term).map_err(index_error)?,
+        "idf, computed as log2(doc count / (doc freq + 1)) from:",
+        vec![
+            Explanation::new(index.doc_count as f32, "doc count, number of documents"),
+            Explanation::new(
+                doc_freq as f32,
+                "doc freq, number of documents with the term",
+            ),
+        ],
+    );
+
+    let boost = Explanation::with_details(
+        clause.boost,
+        "boost, product of:",
+        vec![
+            Explanation::new(clause.query_boost, "query boost"),
+            Explanation::new(clause.field_boost, "field boost"),
+        ],
+    );
+    Ok(Explanation::with_details(
+        boost.value * tf.value * idf.value,
+        description,
+        vec![boost, tf, idf],
+    ))
+}
+
+// sum of the terms of a phrase, with no details if the document doesn't contain the phrase
+fn explain_phrase(index: &Index, phrase: &[TermClause], doc_id: usize) -> Result<Explanation> {
+    let (field, _) = split_field_term(&phrase[0].term);
+    let texts = phrase
+        .iter()
+        .map(|clause| split_field_term(&clause.term).1)
+        .collect::<Vec<_>>();
+    let query = PhraseQuery::new(field, &texts);
+    if query.weight(index)?.scorer(index)?.advance(doc_id) != doc_id {
+        return Ok(Explanation::new(
+            0f32,
+            format!("no match of {}", display_phrase(phrase)),
+        ));
+    }
+
+    let terms = phrase
+        .iter()
+        .map(|clause| explain_term(index, clause, doc_id))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Explanation::with_details(
+        terms.iter().map(|term| term.value).sum(),
+        format!("phrase({}), sum of:", display_phrase(phrase)),
+        terms,
+    ))
+}
+
+// `title:"quick fox"` for a phrase of the title field
+fn display_phrase(phrase: &[TermClause]) -> String {
+    let texts = phrase
+        .iter()
+        .map(|clause| split_field_term(&clause.term).1)
+        .collect::<Vec<_>>()
+        .join(" ");
+    match split_field_term(&phrase[0].term) {
+        (DEFAULT_FIELD, _) => format!("\"{}\"", texts),
+        (field, _) => format!("{}:\"{}\"", field, texts),
+    }
+}
+
+// "title:dog" for a term of the title field, and "dog" for the default field
+pub(crate) fn display_term(term: &Term) -> String {
+    match split_field_term(term) {
+        (DEFAULT_FIELD, text) => text.to_string(),
+        (field, text) => format!("{}:{}", field, text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::explain::explain;
+    use crate::{doc, search, FieldType, IndexWriter, IndexWriterConfig, Schema};
+
+    #[test]
+    fn explain_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("camel")).unwrap();
+        let index = index_writer.build();
+
+        // the explained score is the score of the search
+        for hit in search(&index, "dog fox", 10).unwrap().docs {
+            let explanation = explain(&index, "dog fox", hit.doc_id).unwrap().unwrap();
+            assert_eq!(explanation.value, hit.score);
+        }
+
+        let explanation = explain(&index, "cat fox", 1).unwrap().unwrap();
+        assert_eq!(
+            explanation.to_string(),
+            "0.6037594 = sum of:
+  0.5 = weight(cat), product of:
+    1 = boost, product of:
+      1 = query boost
+      1 = field boost
+    0.5 = tf, computed as freq / field length from:
+      2 = freq, occurrences of the term in the field
+      4 = field length, the doc length norm
+    1 = idf, computed as log2(doc count / (doc freq + 1)) from:
+      4 = doc count, number of documents
+      1 = doc freq, number of documents with the term
+  0.103759386 = weight(fox), product of:
+    1 = boost, product of:
+      1 = query boost
+      1 = field boost
+    0.25 = tf, computed as freq / field length from:
+      1 = freq, occurrences of the term in the field
+      4 = field length, the doc length norm
+    0.41503754 = idf, computed as log2(doc count / (doc freq + 1)) from:
+      4 = doc count, number of documents
+      2 = doc freq, number of documents with the term
+"
+        );
+
+        let explanation = explain(&index, "dog fox", 0).unwrap().unwrap();
+        assert_eq!(explanation.value, 0f32);
+        assert_eq!(explanation.description, "no match, missing terms: fox");
+        assert_eq!(explanation.details[1].description, "no match of fox");
+
+        assert_eq!(explain(&index, "dog", 9).unwrap(), None);
+    }
+
+    #[test]
+    fn explain_phrase_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("quick brown fox")).unwrap();
+        index_writer.write(doc!("the quick fox")).unwrap();
+        index_writer.write(doc!("camel")).unwrap();
+        let index = index_writer.build();
+
+        let query = "the \"quick fox\"^2";
+        let hit = &search(&index, query, 10).unwrap().docs[0];
+        assert_eq!(hit.doc_id, 1);
+        let explanation = explain(&index, query, 1).unwrap().unwrap();
+        assert_eq!(explanation.value, hit.score);
+        assert_eq!(
+            explanation.details[1].description,
+            "phrase(\"quick fox\"), sum of:"
+        );
+        assert_eq!(explanation.details[1].details.len(), 2);
+
+        // the terms of the phrase aren't consecutive
+        let explanation = explain(&index, "\"quick fox\"", 0).unwrap().unwrap();
+        assert_eq!(
+            explanation.description,
+            "no match, missing terms: \"quick fox\""
+        );
+    }
+
+    #[test]
+    fn explain_range_test() {
+        let mut schema = Schema::new();
+        schema.add_field("date", FieldType::Date);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        for date in ["2024-06-01", "2025-06-01"] {
+            let mut doc = doc!("dog");
+            doc.set_field("date", date);
+            index_writer.write(doc).unwrap();
+        }
+        index_writer.write(doc!("fox")).unwrap();
+        let index = index_writer.build();
+
+        let query = "dog date:[2025-01-01 TO *]";
+        let hit = &search(&index, query, 10).unwrap().docs[0];
+        assert_eq!(hit.doc_id, 1);
+        let explanation = explain(&index, query, 1).unwrap().unwrap();
+        assert_eq!(explanation.value, hit.score);
+        assert_eq!(
+            explanation.details[1].description,
+            "filter(date:[2025-01-01 TO *])"
+        );
+
+        let explanation = explain(&index, query, 0).unwrap().unwrap();
+        assert_eq!(
+            explanation.description,
+            "no match, missing terms: date:[2025-01-01 TO *]"
+        );
+    }
+
+    #[test]
+    fn explain_boost_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .set_boost("title", 2f32);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        let mut doc = doc!("dog cat");
+        doc.set_field("title", "dog");
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("fox")).unwrap();
+        index_writer.write(doc!("raccoon")).unwrap();
+        let index = index_writer.build();
+
+        let query = "title:dog^3 cat^0.5";
+        let hit = &search(&index, query, 10).unwrap().docs[0];
+        let explanation = explain(&index, query, 0).unwrap().unwrap();
+        assert_eq!(explanation.value, hit.score);
+
+        let boost = &explanation.details[0].details[0];
+        assert_eq!(boost.value, 6f32);
+        assert_eq!(boost.details[0].value, 3f32);
+        assert_eq!(boost.details[1].value, 2f32);
+        assert_eq!(
+            explanation.details[0].description,
+            "weight(title:dog), product of:"
+        );
+        assert_eq!(explanation.details[1].details[0].value, 0.5);
+    }
+}
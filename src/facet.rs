@@ -0,0 +1,309 @@
+use crate::doc_values::{ColumnRef, ValueRef};
+use crate::store::index_error;
+use crate::{Collector, Error, FieldType, Index, RangeQuery, Result, Schema};
+
+use std::collections::HashMap;
+
+// number of values of a terms facet unless specified otherwise
+pub const DEFAULT_FACET_LIMIT: usize = 10;
+
+// what a facet counts the matched documents by
+#[derive(Debug, Clone, PartialEq)]
+pub enum Facet {
+    // the `limit` most frequent values of a keyword field
+    Terms {
+        field: String,
+        limit: usize,
+    },
+
+    // ranges of a numeric or date field. a document is counted in every
+    // range containing its value.
+    Ranges {
+        field: String,
+        ranges: Vec<RangeQuery>,
+    },
+}
+
+impl Facet {
+    pub fn terms(field: &str, limit: usize) -> Self {
+        Facet::Terms {
+            field: field.to_string(),
+            limit,
+        }
+    }
+
+    // fails if the ranges are not of the same field
+    pub fn ranges(ranges: Vec<RangeQuery>) -> Result<Self> {
+        let field = match ranges.first() {
+            None => return Err(Error::QueryParse("no ranges of the facet".to_string())),
+            Some(range) => range.field().to_string(),
+        };
+        if let Some(range) = ranges.iter().find(|r| r.field() != field) {
+            return Err(Error::QueryParse(format!(
+                "ranges of a facet must be of the same field: {}",
+                range
+            )));
+        }
+        Ok(Facet::Ranges { field, ranges })
+    }
+
+    // parses `category` or `category:5` as the top values of a keyword field,
+    // and `price:[* TO 100},[100 TO *]` as ranges of a numeric or date field
+    pub fn parse(schema: &Schema, spec: &str) -> Result<Self> {
+        let (field, rest) = match spec.split_once(':') {
+            Some((field, rest)) => (field, Some(rest)),
+            None => (spec, None),
+        };
+        match (schema.field_type(field), rest) {
+            (Some(FieldType::Keyword), None) => Ok(Facet::terms(field, DEFAULT_FACET_LIMIT)),
+            (Some(FieldType::Keyword), Some(limit)) => match limit.parse::<usize>() {
+                Ok(limit) => Ok(Facet::terms(field, limit)),
+                Err(_) => Err(Error::QueryParse(format!("invalid facet: {}", spec))),
+            },
+            (Some(FieldType::Numeric | FieldType::Date), Some(ranges)) => Facet::ranges(
+                ranges
+                    .split(',')
+                    .map(|range| RangeQuery::parse(schema, &format!("{}:{}", field, range.trim())))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            _ => Err(Error::QueryParse(format!("invalid facet: {}", spec))),
+        }
+    }
+
+    pub fn field(&self) -> &str {
+        match self {
+            Facet::Terms { field, .. } | Facet::Ranges { field, .. } => field,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FacetCount {
+    // a value of the keyword field, or the range such as `price:[* TO 100}`
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FacetResult {
+    pub field: String,
+
+    // terms by count in descending order, or ranges in the order of the facet
+    pub counts: Vec<FacetCount>,
+}
+
+// counts matched documents by a facet, using the doc values of the field
+#[derive(Debug)]
+pub struct FacetCollector<'a> {
+    facet: Facet,
+    column: Option<ColumnRef<'a>>,
+    terms: HashMap<&'a str, usize>,
+    ranges: Vec<usize>,
+}
+
+impl<'a> FacetCollector<'a> {
+    // fails if the field of the facet isn't of the type the facet counts
+    pub fn new(index: &'a Index, facet: Facet) -> Result<Self> {
+        let ranges = match &facet {
+            Facet::Terms { field, .. } => {
+                if index.schema().field_type(field) != Some(FieldType::Keyword) {
+                    return Err(Error::QueryParse(format!(
+                        "field is not a keyword: {}",
+                        field
+                    )));
+                }
+                Vec::new()
+            }
+            Facet::Ranges { field, ranges } => {
+                if !matches!(
+                    index.schema().field_type(field),
+                    Some(FieldType::Numeric | FieldType::Date)
+                ) {
+                    return Err(Error::QueryParse(format!(
+                        "field is not numeric or date: {}",
+                        field
+                    )));
+                }
+                vec![0; ranges.len()]
+            }
+        };
+        Ok(Self {
+            column: index.column(facet.field()).map_err(index_error)?,
+            facet,
+            terms: HashMap::new(),
+            ranges,
+        })
+    }
+}
+
+impl<'a> Collector for FacetCollector<'a> {
+    type Output = FacetResult;
+
+    fn collect(&mut self, doc_id: usize, _score: f32) -> Result<()> {
+        let value = match &self.column {
+            Some(column) => column.get(doc_id).map_err(index_error)?,
+            None => None,
+        };
+        match (&self.facet, value) {
+            (Facet::Terms { .. }, Some(ValueRef::Keyword(value))) => {
+                *self.terms.entry(value).or_default() += 1;
+            }
+            (Facet::Ranges { ranges, .. }, Some(ValueRef::Number(value))) => {
+                for (count, range) in self.ranges.iter_mut().zip(ranges) {
+                    if range.contains(value) {
+                        *count += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> FacetResult {
+        let counts = match &self.facet {
+            Facet::Terms { limit, .. } => {
+                let mut counts = self.terms.into_iter().collect::<Vec<_>>();
+                // ties are ordered by value to make the output stable
+                counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+                counts
+                    .into_iter()
+                    .take(*limit)
+                    .map(|(value, count)| FacetCount {
+                        value: value.to_string(),
+                        count,
+                    })
+                    .collect()
+            }
+            Facet::Ranges { ranges, .. } => ranges
+                .iter()
+                .zip(self.ranges)
+                .map(|(range, count)| FacetCount {
+                    value: range.to_string(),
+                    count,
+                })
+                .collect(),
+        };
+        FacetResult {
+            field: self.facet.field().to_string(),
+            counts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::facet::{Facet, FacetCollector, FacetCount, DEFAULT_FACET_LIMIT};
+    use crate::{
+        doc, Error, FieldType, IndexWriter, IndexWriterConfig, Schema, TermQuery, TopDocs,
+    };
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .add_field("category", FieldType::Keyword)
+            .add_field("price", FieldType::Numeric)
+            .add_field("title", FieldType::Text);
+        schema
+    }
+
+    fn counts(counts: &[(&str, usize)]) -> Vec<FacetCount> {
+        counts
+            .iter()
+            .map(|(value, count)| FacetCount {
+                value: value.to_string(),
+                count: *count,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_facet_test() {
+        let schema = schema();
+        assert_eq!(
+            Facet::parse(&schema, "category").unwrap(),
+            Facet::terms("category", DEFAULT_FACET_LIMIT)
+        );
+        assert_eq!(
+            Facet::parse(&schema, "category:3").unwrap(),
+            Facet::terms("category", 3)
+        );
+        match Facet::parse(&schema, "price:[* TO 100}, [100 TO *]").unwrap() {
+            Facet::Ranges { field, ranges } => {
+                assert_eq!(field, "price");
+                assert_eq!(
+                    ranges.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+                    vec!["price:[* TO 100}", "price:[100 TO *]"]
+                );
+            }
+            facet => panic!("{:?}", facet),
+        }
+
+        for spec in [
+            "category:x",
+            "price",
+            "price:[1 TO x]",
+            "title",
+            "unknown",
+            "category:[1 TO 2]",
+        ] {
+            let err = Facet::parse(&schema, spec).unwrap_err();
+            assert!(matches!(err, Error::QueryParse(_)), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn facet_collector_test() {
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema: schema(),
+            ..Default::default()
+        });
+        for (body, category, price) in [
+            ("pen", "stationery", "120"),
+            ("red pen", "stationery", "80"),
+            ("pen case", "bags", "1500"),
+            ("pen", "", "100"),
+            ("pencil", "stationery", "50"),
+            ("book", "books", "800"),
+        ] {
+            let mut doc = doc!(body);
+            doc.set_field("category", category);
+            doc.set_field("price", price);
+            index_writer.write(doc).unwrap();
+        }
+        let index = index_writer.build();
+        let searcher = index.searcher();
+        let query = TermQuery::new("body", "pen");
+
+        let facet = FacetCollector::new(&index, Facet::terms("category", 10)).unwrap();
+        let result = searcher.search(&query, facet).unwrap();
+        assert_eq!(result.field, "category");
+        assert_eq!(result.counts, counts(&[("stationery", 2), ("bags", 1)]));
+        let facet = FacetCollector::new(&index, Facet::terms("category", 1)).unwrap();
+        assert_eq!(
+            searcher.search(&query, facet).unwrap().counts,
+            counts(&[("stationery", 2)])
+        );
+
+        let facet =
+            Facet::parse(index.schema(), "price:[* TO 100},[100 TO 1000},[0 TO *]").unwrap();
+        let facet = FacetCollector::new(&index, facet).unwrap();
+        assert_eq!(
+            searcher.search(&query, facet).unwrap().counts,
+            counts(&[
+                ("price:[* TO 100}", 1),
+                ("price:[100 TO 1000}", 2),
+                ("price:[0 TO *]", 4),
+            ])
+        );
+
+        // facets are counted in the same pass as the top documents
+        let facet = FacetCollector::new(&index, Facet::terms("category", 10)).unwrap();
+        let (top_docs, facet) = searcher.search(&query, (TopDocs::new(1), facet)).unwrap();
+        assert_eq!(top_docs.total_hits, 4);
+        assert_eq!(facet.counts[0].count, 2);
+
+        assert!(FacetCollector::new(&index, Facet::terms("price", 10)).is_err());
+        assert!(FacetCollector::new(&index, Facet::terms("unknown", 10)).is_err());
+    }
+}
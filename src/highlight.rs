@@ -0,0 +1,370 @@
+use crate::query::{is_range, split_boost, split_field, split_parts};
+use crate::token::{tokenize, TokenKind, TokenizeType};
+use crate::Result;
+
+#[derive(Debug, Clone)]
+pub struct HighlighterConfig {
+    pub tokenize_type: TokenizeType,
+
+    // maximum length of a fragment in characters
+    pub fragment_size: usize,
+
+    // maximum number of fragments to return
+    pub number_of_fragments: usize,
+
+    // inserted before and after every highlighted match
+    pub pre_tag: String,
+    pub post_tag: String,
+}
+
+impl Default for HighlighterConfig {
+    fn default() -> Self {
+        Self {
+            tokenize_type: TokenizeType::default(),
+            fragment_size: 100,
+            number_of_fragments: 3,
+            pre_tag: "<em>".to_string(),
+            post_tag: "</em>".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Highlighter {
+    // every entry is a sequence of terms which must appear at consecutive positions.
+    // a single term is a phrase of length 1.
+    phrases: Vec<Vec<String>>,
+
+    config: HighlighterConfig,
+}
+
+impl Highlighter {
+    pub fn new(query: &str) -> Result<Self> {
+        Highlighter::with_config(query, HighlighterConfig::default())
+    }
+
+    // `query` is split into terms by the configured tokenizer, and
+    // double-quoted parts are treated as phrases. `field:` prefixes and
+    // `^boost` suffixes of the search syntax are ignored, and so are ranges.
+    pub fn with_config(query: &str, config: HighlighterConfig) -> Result<Self> {
+        let mut phrases = Vec::new();
+        for (i, part) in query.split('"').enumerate() {
+            let part = if i % 2 == 1 {
+                part.to_string()
+            } else {
+                split_parts(part)
+                    .into_iter()
+                    // `field:` and `^boost` of a phrase are next to the quotes
+                    .filter(|word| {
+                        !is_range(split_boost(word).0)
+                            && !word.ends_with(':')
+                            && !word.starts_with('^')
+                    })
+                    .map(|word| split_field(split_boost(word).0).1)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let terms = tokenize(config.tokenize_type, &part)?
+                .into_iter()
+                .filter_map(|t| match t.kind {
+                    TokenKind::Term(term) if !term.is_empty() => Some(term.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            // odd parts are enclosed by double quotes
+            if i % 2 == 1 {
+                if !terms.is_empty() {
+                    phrases.push(terms);
+                }
+            } else {
+                phrases.extend(terms.into_iter().map(|term| vec![term]));
+            }
+        }
+        Ok(Self { phrases, config })
+    }
+
+    // byte ranges of the matched terms and phrases, in the order of appearance
+    fn matches(&self, text: &str) -> Result<Vec<(usize, usize)>> {
+        let terms = tokenize(self.config.tokenize_type, text)?
+            .into_iter()
+            .filter_map(|t| match t.kind {
+                TokenKind::Term(term) if !term.is_empty() => {
+                    Some((term, t.offset, t.length, t.position))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut matches: Vec<(usize, usize)> = Vec::new();
+        for i in 0..terms.len() {
+            let mut longest = None;
+            for phrase in self.phrases.iter() {
+                if i + phrase.len() > terms.len() {
+                    continue;
+                }
+                let window = &terms[i..i + phrase.len()];
+                let matched = window.iter().zip(phrase.iter()).enumerate().all(
+                    |(j, ((term, _, _, position), expected))| {
+                        term == expected && *position == window[0].3 + j
+                    },
+                );
+                if matched {
+                    let (_, offset, length, _) = window[window.len() - 1];
+                    longest = longest.max(Some(offset + length));
+                }
+            }
+            if let Some(end) = longest {
+                let start = terms[i].1;
+                match matches.last_mut() {
+                    // overlapping matches are merged into one
+                    Some(last) if last.1 >= start => last.1 = last.1.max(end),
+                    _ => matches.push((start, end)),
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    // returns fragments containing highlighted matches, in document order.
+    // returns empty Vec if nothing matches.
+    pub fn fragments(&self, text: &str) -> Result<Vec<String>> {
+        let matches = self.matches(text)?;
+        if matches.is_empty() || self.config.number_of_fragments == 0 {
+            return Ok(Vec::new());
+        }
+
+        let boundaries = tokenize(self.config.tokenize_type, text)?
+            .into_iter()
+            .filter(|t| t.length > 0)
+            .map(|t| (t.offset, t.offset + t.length))
+            .collect::<Vec<_>>();
+
+        // (start, end, indices of matches)
+        let mut fragments: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+        let mut i = 0;
+        while i < matches.len() {
+            let (match_start, match_end) = matches[i];
+            let match_chars = text[match_start..match_end].chars().count();
+            let context = self.config.fragment_size.saturating_sub(match_chars) / 2;
+
+            let start = snap_start(&boundaries, move_back(text, match_start, context));
+            let end = snap_end(
+                &boundaries,
+                move_forward(text, start, self.config.fragment_size).max(match_end),
+            );
+
+            let mut included = vec![i];
+            i += 1;
+            while i < matches.len() && matches[i].1 <= end {
+                included.push(i);
+                i += 1;
+            }
+            fragments.push((start, end, included));
+        }
+
+        // prefer fragments with more matches, then earlier ones
+        let mut order = (0..fragments.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| {
+            fragments[*b]
+                .2
+                .len()
+                .cmp(&fragments[*a].2.len())
+                .then(a.cmp(b))
+        });
+        order.truncate(self.config.number_of_fragments);
+        order.sort_unstable();
+
+        Ok(order
+            .into_iter()
+            .map(|idx| {
+                let (start, end, ref included) = fragments[idx];
+                let mut fragment = String::new();
+                let mut cursor = start;
+                for m in included.iter().map(|m| matches[*m]) {
+                    fragment.push_str(&text[cursor..m.0]);
+                    fragment.push_str(&self.config.pre_tag);
+                    fragment.push_str(&text[m.0..m.1]);
+                    fragment.push_str(&self.config.post_tag);
+                    cursor = m.1;
+                }
+                fragment.push_str(&text[cursor..end]);
+                fragment.trim().to_string()
+            })
+            .collect())
+    }
+
+    // joins fragments with "...".
+    // if nothing matches, the beginning of the text is returned without highlight.
+    pub fn snippet(&self, text: &str) -> Result<String> {
+        let fragments = self.fragments(text)?;
+        if fragments.is_empty() {
+            let end = move_forward(text, 0, self.config.fragment_size);
+            return Ok(text[..snap_end_plain(text, end)].trim().to_string());
+        }
+        Ok(fragments.join(" ... "))
+    }
+}
+
+// byte offset which is `chars` characters before `offset`
+fn move_back(text: &str, offset: usize, chars: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .take(chars)
+        .last()
+        .map_or(offset, |(i, _)| i)
+}
+
+// byte offset which is `chars` characters after `offset`
+fn move_forward(text: &str, offset: usize, chars: usize) -> usize {
+    text[offset..]
+        .char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(i, _)| offset + i)
+}
+
+// avoid cutting a token at the beginning of a fragment
+fn snap_start(boundaries: &[(usize, usize)], start: usize) -> usize {
+    match boundaries.iter().find(|(_, end)| *end > start) {
+        Some((token_start, _)) if *token_start < start => boundaries
+            .iter()
+            .find(|(token_start, _)| *token_start >= start)
+            .map_or(start, |(token_start, _)| *token_start),
+        _ => start,
+    }
+}
+
+// avoid cutting a token at the end of a fragment
+fn snap_end(boundaries: &[(usize, usize)], end: usize) -> usize {
+    match boundaries
+        .iter()
+        .find(|(start, token_end)| *start < end && *token_end > end)
+    {
+        Some((start, _)) => boundaries
+            .iter()
+            .rev()
+            .find(|(_, token_end)| *token_end <= *start)
+            .map_or(end, |(_, token_end)| *token_end),
+        None => end,
+    }
+}
+
+fn snap_end_plain(text: &str, end: usize) -> usize {
+    if end >= text.len() {
+        return text.len();
+    }
+    text[..end]
+        .rfind(char::is_whitespace)
+        .filter(|i| *i > 0)
+        .unwrap_or(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::highlight::{Highlighter, HighlighterConfig};
+    use crate::TokenizeType;
+
+    #[test]
+    fn highlight_term_test() {
+        let highlighter = Highlighter::new("Taisuke").unwrap();
+        assert_eq!(
+            highlighter.fragments("I am Taisuke.").unwrap(),
+            vec!["I am <em>Taisuke</em>.".to_string()]
+        );
+        assert_eq!(
+            highlighter.fragments("I am taisuke.").unwrap(),
+            Vec::<String>::new()
+        );
+
+        let highlighter = Highlighter::new("title:Taisuke^3 date:[2025 TO am]").unwrap();
+        assert_eq!(
+            highlighter.fragments("I am Taisuke.").unwrap(),
+            vec!["I am <em>Taisuke</em>.".to_string()]
+        );
+
+        let highlighter = Highlighter::new("that it").unwrap();
+        assert_eq!(
+            highlighter.fragments("that that is is that that is not is not is that it it is").unwrap(),
+            vec![
+                "<em>that</em> <em>that</em> is is <em>that</em> <em>that</em> is not is not is <em>that</em> <em>it</em> <em>it</em> is"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_phrase_test() {
+        let highlighter = Highlighter::new("\"is not\"").unwrap();
+        assert_eq!(
+            highlighter.fragments("that is is not is not").unwrap(),
+            vec!["that is <em>is not</em> <em>is not</em>".to_string()]
+        );
+        // the field and the boost of a phrase aren't highlighted
+        let highlighter = Highlighter::new("title:\"is not\"^2").unwrap();
+        assert_eq!(
+            highlighter.fragments("title is not 2").unwrap(),
+            vec!["title <em>is not</em> 2".to_string()]
+        );
+
+        // phrase does not match across punctuation
+        let highlighter = Highlighter::new("\"Taisuke it\"").unwrap();
+        assert_eq!(
+            highlighter.fragments("I am Taisuke. it is").unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn highlight_fragments_test() {
+        let text = "dog cat cat fox cat cat cat cat cat cat cat cat cat cat bird cat cat cat cat cat cat cat cat fox";
+        let highlighter = Highlighter::with_config(
+            "fox bird",
+            HighlighterConfig {
+                fragment_size: 11,
+                number_of_fragments: 2,
+                pre_tag: "[".to_string(),
+                post_tag: "]".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            highlighter.fragments(text).unwrap(),
+            vec!["cat [fox] cat".to_string(), "[bird] cat".to_string()]
+        );
+        assert_eq!(
+            highlighter.snippet(text).unwrap(),
+            "cat [fox] cat ... [bird] cat"
+        );
+
+        let highlighter = Highlighter::with_config(
+            "mouse",
+            HighlighterConfig {
+                fragment_size: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(highlighter.snippet(text).unwrap(), "dog cat");
+    }
+
+    #[test]
+    fn highlight_japanese_test() {
+        let highlighter = Highlighter::with_config(
+            "空港",
+            HighlighterConfig {
+                tokenize_type: TokenizeType::Japanese,
+                fragment_size: 6,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            highlighter
+                .fragments("関西国際空港限定トートバッグ")
+                .unwrap(),
+            vec!["国際<em>空港</em>限定".to_string()]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+mod crawl;
+mod csv;
+pub(crate) mod jsonl;
+
+pub use self::csv::{CsvConfig, CsvLoader};
+pub use crawl::{list_files, read_document, CrawlConfig, Crawler};
+pub use jsonl::{JsonLinesConfig, JsonLinesLoader};
+
+use crate::{Document, IndexWriter};
+use std::fmt;
+use std::io;
+
+// what to do when a record can't be converted into a document
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorPolicy {
+    // report the error and continue with the next record
+    #[default]
+    Skip,
+
+    // stop loading at the first error
+    Abort,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineError {
+    // line number starting from 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Line(LineError),
+
+    // the index rejected a document
+    Index(crate::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Line(e) => write!(f, "{}", e),
+            LoadError::Index(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LoadReport {
+    // number of documents written
+    pub documents: usize,
+
+    // errors of skipped records
+    pub errors: Vec<LineError>,
+}
+
+// writes every document yielded by `docs`.
+// I/O and index errors always abort, while errors of each record follow `policy`.
+pub fn load<I>(
+    docs: I,
+    index_writer: &mut IndexWriter,
+    policy: ErrorPolicy,
+) -> Result<LoadReport, LoadError>
+where
+    I: IntoIterator<Item = Result<Document, LoadError>>,
+{
+    let mut report = LoadReport::default();
+    for doc in docs {
+        match doc {
+            Ok(doc) => {
+                index_writer.write(doc).map_err(LoadError::Index)?;
+                report.documents += 1;
+            }
+            Err(LoadError::Line(e)) if policy == ErrorPolicy::Skip => report.errors.push(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
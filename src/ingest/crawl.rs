@@ -0,0 +1,329 @@
+use crate::ingest::LoadError;
+use crate::Document;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// number of bytes inspected to detect binary files
+const BINARY_CHECK_LEN: usize = 8000;
+
+#[derive(Debug, Clone)]
+pub struct CrawlConfig {
+    // glob patterns such as "docs/**/*.md" matched against the path relative to the root.
+    // a pattern without "/" is matched against the file name.
+    // every file is included if empty.
+    pub include: Vec<String>,
+
+    // glob patterns of files and directories to skip
+    pub exclude: Vec<String>,
+
+    // file extensions to read, or every file if empty
+    pub extensions: Vec<String>,
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            extensions: vec!["txt".to_string(), "md".to_string()],
+        }
+    }
+}
+
+// every file under `root` selected by `config`, sorted by path.
+// `root` itself is returned if it is a file.
+pub fn list_files(root: &Path, config: &CrawlConfig) -> io::Result<Vec<PathBuf>> {
+    if !fs::metadata(root)?.is_dir() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    walk(root, root, config, &mut files)?;
+    Ok(files)
+}
+
+fn walk(root: &Path, dir: &Path, config: &CrawlConfig, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        let relative = relative_path(root, &path);
+        if config.exclude.iter().any(|p| glob_match_path(p, &relative)) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, config, files)?;
+            continue;
+        }
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if !config.extensions.is_empty() && !config.extensions.iter().any(|e| e == extension) {
+            continue;
+        }
+        if !config.include.is_empty()
+            && !config.include.iter().any(|p| glob_match_path(p, &relative))
+        {
+            continue;
+        }
+        files.push(path);
+    }
+    Ok(())
+}
+
+// "/" separated path relative to `root`
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn glob_match_path(pattern: &str, relative: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern.as_bytes(), relative.as_bytes())
+    } else {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        glob_match(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+// "**" matches any string, "*" matches any string without "/", "?" matches a character except "/"
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // "**/" also matches no directory
+            glob_match(rest, text)
+                || (0..text.len()).any(|i| text[i] == b'/' && glob_match(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        [b'*', rest @ ..] => {
+            let end = text.iter().position(|c| *c == b'/').unwrap_or(text.len());
+            (0..=end).any(|i| glob_match(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => match text {
+            [c, ..] if *c != b'/' => {
+                // skip a whole UTF-8 character
+                let len = text[1..].iter().take_while(|c| **c & 0xc0 == 0x80).count() + 1;
+                glob_match(rest, &text[len..])
+            }
+            _ => false,
+        },
+        [p, rest @ ..] => {
+            matches!(text, [c, text_rest @ ..] if c == p && glob_match(rest, text_rest))
+        }
+    }
+}
+
+// reads a text file as a document with "path", "title" and body.
+// returns None for binary files.
+pub fn read_document(path: &Path) -> io::Result<Option<Document>> {
+    let bytes = fs::read(path)?;
+    if bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return Ok(None);
+    }
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return Ok(None),
+    };
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+
+    let is_markdown = path.extension().and_then(|ext| ext.to_str()) == Some("md");
+    let title = if is_markdown {
+        markdown_title(text)
+    } else {
+        text.lines().map(str::trim).find(|line| !line.is_empty())
+    };
+    let title = match title {
+        Some(title) => title.to_string(),
+        None => path
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().to_string()),
+    };
+
+    let mut doc = Document::new(text.to_string());
+    doc.set_field("path", path.to_string_lossy());
+    doc.set_field("title", title);
+    Ok(Some(doc))
+}
+
+// the first ATX ("# title") or setext ("title\n=====") heading outside of code blocks
+fn markdown_title(text: &str) -> Option<&str> {
+    let mut in_code = false;
+    let mut prev: Option<&str> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            prev = None;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let hashes = trimmed.bytes().take_while(|c| *c == b'#').count();
+        if (1..=6).contains(&hashes) {
+            let rest = &trimmed[hashes..];
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                let title = rest.trim().trim_end_matches('#').trim();
+                if !title.is_empty() {
+                    return Some(title);
+                }
+            }
+        }
+        if !trimmed.is_empty() && trimmed.bytes().all(|c| c == b'=') {
+            if let Some(title) = prev {
+                return Some(title);
+            }
+        }
+        prev = Some(trimmed).filter(|line| !line.is_empty());
+    }
+    None
+}
+
+// reads text files under a directory as documents
+pub struct Crawler {
+    files: std::vec::IntoIter<PathBuf>,
+
+    // binary files which are not indexed
+    skipped: Vec<PathBuf>,
+}
+
+impl Crawler {
+    pub fn new(root: &Path, config: &CrawlConfig) -> io::Result<Self> {
+        Ok(Self {
+            files: list_files(root, config)?.into_iter(),
+            skipped: Vec::new(),
+        })
+    }
+
+    pub fn skipped(&self) -> &[PathBuf] {
+        &self.skipped
+    }
+}
+
+impl Iterator for Crawler {
+    type Item = Result<Document, LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for path in self.files.by_ref() {
+            match read_document(&path) {
+                Ok(Some(doc)) => return Some(Ok(doc)),
+                Ok(None) => self.skipped.push(path),
+                Err(e) => {
+                    let e = io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+                    return Some(Err(LoadError::Io(e)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ingest::crawl::{glob_match_path, markdown_title};
+    use crate::ingest::{CrawlConfig, Crawler};
+    use crate::store::tests::test_dir;
+    use std::fs;
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match_path("*.md", "README.md"));
+        assert!(glob_match_path("*.md", "docs/guide.md"));
+        assert!(!glob_match_path("*.md", "README.txt"));
+        assert!(glob_match_path("docs/*.md", "docs/guide.md"));
+        assert!(!glob_match_path("docs/*.md", "docs/ja/guide.md"));
+        assert!(glob_match_path("docs/**/*.md", "docs/guide.md"));
+        assert!(glob_match_path("docs/**/*.md", "docs/ja/guide.md"));
+        assert!(glob_match_path("**", "a/b/c"));
+        assert!(glob_match_path("?.txt", "あ.txt"));
+        assert!(!glob_match_path("?.txt", "ab.txt"));
+        assert!(glob_match_path("target", "sub/target"));
+        assert!(glob_match_path(".*", ".git"));
+    }
+
+    #[test]
+    fn markdown_title_test() {
+        assert_eq!(markdown_title("# Title\nbody"), Some("Title"));
+        assert_eq!(markdown_title("intro\n\n## Usage ##\n"), Some("Usage"));
+        assert_eq!(markdown_title("#hashtag\n# Title"), Some("Title"));
+        assert_eq!(
+            markdown_title("```\n# comment\n```\n# Title"),
+            Some("Title")
+        );
+        assert_eq!(markdown_title("Title\n=====\n"), Some("Title"));
+        assert_eq!(markdown_title("no heading"), None);
+    }
+
+    #[test]
+    fn crawler_test() {
+        let dir = test_dir("crawler_test");
+        fs::create_dir_all(dir.join("docs/ja")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("README.md"), "intro\n# imser\nsearch engine").unwrap();
+        fs::write(dir.join("docs/guide.txt"), "\n  Guide  \nI am Taisuke").unwrap();
+        fs::write(dir.join("docs/ja/empty.md"), "").unwrap();
+        fs::write(dir.join("docs/image.md"), b"\x89PNG\x00\x00").unwrap();
+        fs::write(dir.join("docs/data.json"), "{}").unwrap();
+        fs::write(dir.join("target/out.txt"), "build output").unwrap();
+
+        let config = CrawlConfig {
+            exclude: vec!["target".to_string()],
+            ..Default::default()
+        };
+        let mut crawler = Crawler::new(&dir, &config).unwrap();
+        let docs = crawler
+            .by_ref()
+            .map(|doc| {
+                let doc = doc.unwrap();
+                let path = doc.field("path").unwrap().to_string();
+                let path = path
+                    .strip_prefix(dir.to_str().unwrap())
+                    .unwrap()
+                    .to_string();
+                (path, doc.field("title").unwrap().to_string(), doc.body)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            docs,
+            vec![
+                (
+                    "/README.md".to_string(),
+                    "imser".to_string(),
+                    "intro\n# imser\nsearch engine".to_string()
+                ),
+                (
+                    "/docs/guide.txt".to_string(),
+                    "Guide".to_string(),
+                    "\n  Guide  \nI am Taisuke".to_string()
+                ),
+                (
+                    "/docs/ja/empty.md".to_string(),
+                    "empty".to_string(),
+                    "".to_string()
+                ),
+            ]
+        );
+        assert_eq!(crawler.skipped(), &[dir.join("docs/image.md")]);
+
+        let config = CrawlConfig {
+            include: vec!["docs/**".to_string()],
+            exclude: vec!["ja".to_string()],
+            ..Default::default()
+        };
+        let paths = Crawler::new(&dir, &config)
+            .unwrap()
+            .map(|doc| doc.unwrap().field("path").unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec![dir.join("docs/guide.txt").to_string_lossy()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
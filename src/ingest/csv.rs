@@ -0,0 +1,219 @@
+use crate::ingest::{LineError, LoadError};
+use crate::{Document, Schema};
+
+use csv::{ReaderBuilder, StringRecord};
+use std::collections::HashMap;
+use std::io::Read;
+
+#[derive(Debug, Clone)]
+pub struct CsvConfig {
+    // b',' for CSV and b'\t' for TSV
+    pub delimiter: u8,
+
+    // field name => column name in the header row.
+    // fields without a mapping are read from the column of the same name.
+    pub mapping: HashMap<String, String>,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            mapping: HashMap::new(),
+        }
+    }
+}
+
+impl CsvConfig {
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Default::default()
+        }
+    }
+}
+
+// reads records of CSV with a header row, streaming from `reader`.
+// quoted fields may contain delimiters and newlines.
+pub struct CsvLoader<R> {
+    reader: csv::Reader<R>,
+
+    // (field name, column index)
+    columns: Vec<(String, usize)>,
+
+    record: StringRecord,
+    done: bool,
+}
+
+impl<R: Read> CsvLoader<R> {
+    pub fn new(reader: R, schema: &Schema, config: CsvConfig) -> Result<Self, LoadError> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(config.delimiter)
+            .has_headers(true)
+            .from_reader(reader);
+        let headers = reader.headers().map_err(|e| to_load_error(e, 1))?.clone();
+
+        let columns = schema
+            .fields()
+            .filter_map(|field| {
+                let column = config.mapping.get(&field.name).unwrap_or(&field.name);
+                headers
+                    .iter()
+                    .position(|header| header == column)
+                    .map(|index| (field.name.clone(), index))
+            })
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            return Err(LoadError::Line(LineError {
+                line: 1,
+                reason: "no column of the schema found in the header".to_string(),
+            }));
+        }
+
+        Ok(Self {
+            reader,
+            columns,
+            record: StringRecord::new(),
+            done: false,
+        })
+    }
+
+    fn to_document(&self) -> Result<Document, String> {
+        let mut doc = Document::new(String::new());
+        let mut found = false;
+        for (field, index) in self.columns.iter() {
+            // an empty cell is a missing value
+            match self.record.get(*index) {
+                None | Some("") => continue,
+                Some(value) => doc.set_field(field, value),
+            }
+            found = true;
+        }
+        if !found {
+            return Err("no field of the schema found".to_string());
+        }
+        Ok(doc)
+    }
+}
+
+impl<R: Read> Iterator for CsvLoader<R> {
+    type Item = Result<Document, LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let line = self.reader.position().line() as usize;
+        match self.reader.read_record(&mut self.record) {
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Ok(true) => {
+                let line = self.record.position().map_or(line, |p| p.line() as usize);
+                Some(
+                    self.to_document()
+                        .map_err(|reason| LoadError::Line(LineError { line, reason })),
+                )
+            }
+            Err(e) => {
+                let e = to_load_error(e, line);
+                self.done = matches!(e, LoadError::Io(_));
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn to_load_error(e: csv::Error, line: usize) -> LoadError {
+    let line = e.position().map_or(line, |p| p.line() as usize);
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => LoadError::Io(e),
+        csv::ErrorKind::Utf8 { .. } => LoadError::Line(LineError {
+            line,
+            reason: "invalid UTF-8".to_string(),
+        }),
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => LoadError::Line(LineError {
+            line,
+            reason: format!("expected {} columns, but got {}", expected_len, len),
+        }),
+        kind => LoadError::Line(LineError {
+            line,
+            reason: format!("{:?}", kind),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ingest::{CsvConfig, CsvLoader, LoadError};
+    use crate::{doc, Document, FieldType, Schema};
+    use std::io::Cursor;
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("url", FieldType::Stored);
+        schema
+    }
+
+    fn load(input: &[u8], config: CsvConfig) -> Vec<Result<Document, (usize, String)>> {
+        CsvLoader::new(Cursor::new(input), &schema(), config)
+            .unwrap()
+            .map(|doc| match doc {
+                Ok(doc) => Ok(doc),
+                Err(LoadError::Line(e)) => Err((e.line, e.reason)),
+                Err(e) => panic!("unexpected error: {}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn csv_loader_test() {
+        let input = b"id,title,text\n1,dog,\"dog, cat\"\n2,,\"fox\nraccoon\"\n3,\"say \"\"hello\"\"\",bird\n";
+        let mut config = CsvConfig::default();
+        config
+            .mapping
+            .insert("body".to_string(), "text".to_string());
+
+        let mut doc0 = doc!("dog, cat");
+        doc0.set_field("title", "dog");
+        let mut doc2 = doc!("bird");
+        doc2.set_field("title", "say \"hello\"");
+        assert_eq!(
+            load(input, config),
+            vec![Ok(doc0), Ok(doc!("fox\nraccoon")), Ok(doc2)]
+        );
+    }
+
+    #[test]
+    fn tsv_loader_test() {
+        let input = b"body\turl\nI am Taisuke\thttps://example.com/\n";
+        let mut doc = doc!("I am Taisuke");
+        doc.set_field("url", "https://example.com/");
+        assert_eq!(load(input, CsvConfig::tsv()), vec![Ok(doc)]);
+    }
+
+    #[test]
+    fn csv_loader_error_test() {
+        let input = b"body,title\ndog,\n\"fox\ncat\",x,y\n,\nbird,\n\xff,\n";
+        assert_eq!(
+            load(input, CsvConfig::default()),
+            vec![
+                Ok(doc!("dog")),
+                Err((3, "expected 2 columns, but got 3".to_string())),
+                Err((5, "no field of the schema found".to_string())),
+                Ok(doc!("bird")),
+                Err((7, "invalid UTF-8".to_string())),
+            ]
+        );
+
+        match CsvLoader::new(Cursor::new(b"id,text\n"), &schema(), CsvConfig::default()) {
+            Err(LoadError::Line(e)) => assert_eq!(e.line, 1),
+            _ => panic!("header without fields must be an error"),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::ingest::{LineError, LoadError};
+use crate::{Document, Schema};
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::BufRead;
+
+#[derive(Debug, Clone, Default)]
+pub struct JsonLinesConfig {
+    // field name => key of the JSON object.
+    // nested keys can be specified with dots such as "author.name".
+    // fields without a mapping are read from the key of the same name.
+    pub mapping: HashMap<String, String>,
+}
+
+// reads one JSON object per line, streaming from `reader`
+pub struct JsonLinesLoader<R> {
+    reader: R,
+
+    // (field name, key)
+    keys: Vec<(String, String)>,
+
+    line: usize,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: BufRead> JsonLinesLoader<R> {
+    pub fn new(reader: R, schema: &Schema, config: JsonLinesConfig) -> Self {
+        Self {
+            reader,
+            keys: field_keys(schema, &config.mapping),
+            line: 0,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn parse(&self, line: &[u8]) -> Result<Document, String> {
+        let line = std::str::from_utf8(line).map_err(|_| "invalid UTF-8".to_string())?;
+        match serde_json::from_str::<Value>(line) {
+            Ok(value) => to_document(&value, &self.keys),
+            Err(e) => Err(format!("invalid JSON: {}", e)),
+        }
+    }
+}
+
+// (field name, key) for every field of the schema
+pub(crate) fn field_keys(
+    schema: &Schema,
+    mapping: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    schema
+        .fields()
+        .map(|field| {
+            let key = mapping
+                .get(&field.name)
+                .cloned()
+                .unwrap_or_else(|| field.name.clone());
+            (field.name.clone(), key)
+        })
+        .collect()
+}
+
+pub(crate) fn to_document(value: &Value, keys: &[(String, String)]) -> Result<Document, String> {
+    let object = match value {
+        Value::Object(object) => object,
+        _ => return Err("expected a JSON object".to_string()),
+    };
+
+    let mut doc = Document::new(String::new());
+    let mut found = false;
+    for (field, key) in keys.iter() {
+        let value = match lookup(object, key).map(to_text) {
+            None | Some(Ok(None)) => continue,
+            Some(Ok(Some(value))) => value,
+            Some(Err(reason)) => return Err(format!("{}: {}", key, reason)),
+        };
+        doc.set_field(field, value);
+        found = true;
+    }
+    if !found {
+        return Err("no field of the schema found".to_string());
+    }
+    Ok(doc)
+}
+
+impl<R: BufRead> Iterator for JsonLinesLoader<R> {
+    type Item = Result<Document, LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    let mut line = self.buf.as_slice();
+                    while let Some((b'\n' | b'\r', rest)) = line.split_last() {
+                        line = rest;
+                    }
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+                    return Some(self.parse(line).map_err(|reason| {
+                        LoadError::Line(LineError {
+                            line: self.line,
+                            reason,
+                        })
+                    }));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(LoadError::Io(e)));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn lookup<'a>(object: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    if let Some(value) = object.get(key) {
+        return Some(value);
+    }
+    let (head, rest) = key.split_once('.')?;
+    match object.get(head)? {
+        Value::Object(child) => lookup(child, rest),
+        _ => None,
+    }
+}
+
+// null is treated as a missing value, and array elements are joined by newlines
+fn to_text(value: &Value) -> Result<Option<String>, String> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.clone())),
+        Value::Bool(_) | Value::Number(_) => Ok(Some(value.to_string())),
+        Value::Array(values) => {
+            let mut texts = Vec::with_capacity(values.len());
+            for value in values {
+                match value {
+                    Value::Array(_) | Value::Object(_) => {
+                        return Err("nested value is not supported".to_string())
+                    }
+                    _ => texts.extend(to_text(value)?),
+                }
+            }
+            Ok(Some(texts.join("\n")))
+        }
+        Value::Object(_) => Err("object is not supported".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ingest::{load, ErrorPolicy, JsonLinesConfig, JsonLinesLoader, LoadError};
+    use crate::{doc, FieldType, IndexWriter, IndexWriterConfig, Schema};
+    use std::io::Cursor;
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("author", FieldType::Stored);
+        schema
+    }
+
+    #[test]
+    fn jsonl_loader_test() {
+        let input = r#"{"title": "dog", "body": "dog cat", "author": {"name": "Taisuke"}}
+{"body": "fox", "views": 10}
+
+{"body": ["dog", 1, true, null], "title": null}
+"#;
+        let mut config = JsonLinesConfig::default();
+        config
+            .mapping
+            .insert("author".to_string(), "author.name".to_string());
+        let docs = JsonLinesLoader::new(Cursor::new(input), &schema(), config)
+            .map(|doc| doc.unwrap())
+            .collect::<Vec<_>>();
+
+        let mut doc0 = doc!("dog cat");
+        doc0.set_field("title", "dog");
+        doc0.set_field("author", "Taisuke");
+        assert_eq!(docs, vec![doc0, doc!("fox"), doc!("dog\n1\ntrue")]);
+    }
+
+    #[test]
+    fn jsonl_loader_error_test() {
+        let input: &[u8] = b"{\"body\": \"dog\"}\n[1, 2]\n{\"body\": \n{\"title\": {}}\n{\"views\": 1}\n\xff\n{\"body\": \"fox\"}";
+        let errors = JsonLinesLoader::new(Cursor::new(input), &schema(), Default::default())
+            .filter_map(|doc| match doc {
+                Err(LoadError::Line(e)) => Some((e.line, e.reason)),
+                _ => None,
+            })
+            .map(|(line, reason)| (line, reason.split(':').next().unwrap().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (2, "expected a JSON object".to_string()),
+                (3, "invalid JSON".to_string()),
+                (4, "title".to_string()),
+                (5, "no field of the schema found".to_string()),
+                (6, "invalid UTF-8".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_policy_test() {
+        let input = "{\"body\": \"dog\"}\nnull\n{\"body\": \"fox\"}\n";
+
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema: schema(),
+            ..Default::default()
+        });
+        let loader = JsonLinesLoader::new(Cursor::new(input), &schema(), Default::default());
+        let report = load(loader, &mut index_writer, ErrorPolicy::Skip).unwrap();
+        assert_eq!(report.documents, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 2);
+        let index = index_writer.build();
+        assert_eq!(index.doc(1).unwrap().as_deref(), Some(&doc!("fox")));
+
+        let mut index_writer = IndexWriter::new();
+        let loader = JsonLinesLoader::new(Cursor::new(input), &schema(), Default::default());
+        match load(loader, &mut index_writer, ErrorPolicy::Abort) {
+            Err(LoadError::Line(e)) => assert_eq!(e.line, 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::ingest::jsonl;
+use crate::{
+    Document, Error, Explanation, FacetResult, FieldType, Highlighter, HighlighterConfig, Index,
+    IndexStats, Result, Schema, SearchResult,
+};
+
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+// {"total_hits": 1, "hits": [hit]}
+pub fn search_result_to_json(index: &Index, query: &str, result: &SearchResult) -> Result<Value> {
+    let mut hits = Vec::with_capacity(result.docs.len());
+    for doc in result.docs.iter() {
+        if let Some(hit) = hit_to_json(index, query, doc.doc_id, doc.score)? {
+            hits.push(hit);
+        }
+    }
+    Ok(json!({ "total_hits": result.total_hits, "hits": hits }))
+}
+
+// {"id": 0, "score": 0.5, "fields": {...}, "highlights": {"body": [...]}}
+// returns None if the document is not stored.
+pub fn hit_to_json(index: &Index, query: &str, doc_id: usize, score: f32) -> Result<Option<Value>> {
+    let stored = match index.doc(doc_id)? {
+        None => return Ok(None),
+        Some(stored) => stored,
+    };
+    let highlighter = Highlighter::with_config(
+        query,
+        HighlighterConfig {
+            tokenize_type: index.tokenize_type(),
+            ..Default::default()
+        },
+    )?;
+
+    let mut highlights = Map::new();
+    for field in index.schema().fields() {
+        if field.field_type != FieldType::Text {
+            continue;
+        }
+        let fragments = match stored.field(&field.name) {
+            None => continue,
+            Some(value) => highlighter.fragments(value)?,
+        };
+        if !fragments.is_empty() {
+            highlights.insert(field.name.clone(), json!(fragments));
+        }
+    }
+
+    Ok(Some(json!({
+        "id": doc_id,
+        "score": f32_to_json(score),
+        "fields": document_to_json(index.schema(), &stored),
+        "highlights": highlights,
+    })))
+}
+
+// stored values of the fields in the schema
+pub fn document_to_json(schema: &Schema, doc: &Document) -> Value {
+    let mut fields = Map::new();
+    for field in schema.fields() {
+        if let Some(value) = doc.field(&field.name) {
+            fields.insert(field.name.clone(), json!(value));
+        }
+    }
+    Value::Object(fields)
+}
+
+// reads fields of the schema from the keys of the same name
+pub fn document_from_json(schema: &Schema, value: &Value) -> Result<Document> {
+    jsonl::to_document(value, &jsonl::field_keys(schema, &HashMap::new()))
+        .map_err(Error::SchemaViolation)
+}
+
+// [{"field": "category", "counts": [{"value": "books", "count": 3}]}]
+pub fn facets_to_json(facets: &[FacetResult]) -> Value {
+    json!(facets
+        .iter()
+        .map(|facet| json!({
+            "field": facet.field,
+            "counts": facet
+                .counts
+                .iter()
+                .map(|c| json!({"value": c.value, "count": c.count}))
+                .collect::<Vec<_>>(),
+        }))
+        .collect::<Vec<_>>())
+}
+
+// {"value": 0.5, "description": "sum of:", "details": [...]}
+pub fn explanation_to_json(explanation: &Explanation) -> Value {
+    json!({
+        "value": f32_to_json(explanation.value),
+        "description": explanation.description,
+        "details": explanation.details.iter().map(explanation_to_json).collect::<Vec<_>>(),
+    })
+}
+
+// f32 is widened to f64 as is, so parse its shortest representation instead
+fn f32_to_json(value: f32) -> Value {
+    json!(value.to_string().parse::<f64>().unwrap_or_default())
+}
+
+pub fn stats_to_json(stats: &IndexStats) -> Value {
+    json!({
+        "doc_count": stats.doc_count,
+        "term_count": stats.term_count,
+        "posting_count": stats.posting_count,
+        "token_count": stats.token_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{document_from_json, explanation_to_json, search_result_to_json};
+    use crate::{doc, search, Explanation, FieldType, IndexWriter, IndexWriterConfig, Schema};
+    use serde_json::json;
+
+    #[test]
+    fn search_result_to_json_test() {
+        let mut schema = Schema::new();
+        schema.add_field("url", FieldType::Stored);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema: schema.clone(),
+            ..Default::default()
+        });
+        let doc = document_from_json(
+            &schema,
+            &json!({"body": "I am Taisuke", "url": "https://example.com/", "age": 20}),
+        )
+        .unwrap();
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("What is this")).unwrap();
+        let index = index_writer.build();
+
+        let result = search(&index, "Taisuke", 10).unwrap();
+        assert_eq!(
+            search_result_to_json(&index, "Taisuke", &result).unwrap(),
+            json!({
+                "total_hits": 1,
+                "hits": [{
+                    "id": 0,
+                    "score": 0.0,
+                    "fields": {"body": "I am Taisuke", "url": "https://example.com/"},
+                    "highlights": {"body": ["I am <em>Taisuke</em>"]},
+                }],
+            })
+        );
+
+        assert!(document_from_json(&schema, &json!(["I am Taisuke"])).is_err());
+        assert!(document_from_json(&schema, &json!({"title": "Taisuke"})).is_err());
+    }
+
+    #[test]
+    fn explanation_to_json_test() {
+        let explanation =
+            Explanation::with_details(0.1, "sum of:", vec![Explanation::new(0.1, "weight(dog)")]);
+        assert_eq!(
+            explanation_to_json(&explanation),
+            json!({
+                "value": 0.1,
+                "description": "sum of:",
+                "details": [{"value": 0.1, "description": "weight(dog)", "details": []}],
+            })
+        );
+    }
+}
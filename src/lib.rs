@@ -1,51 +1,118 @@
+//! imser is a full-text search engine with a positional inverted index.
+//!
+//! ```
+//! use imser::{doc, IndexWriter, TopDocs};
+//!
+//! let mut index_writer = IndexWriter::new();
+//! index_writer.write(doc!("dog cat cat fox")).unwrap();
+//! index_writer.write(doc!("dog raccoon fox")).unwrap();
+//! index_writer.write(doc!("bird")).unwrap();
+//! let index = index_writer.build();
+//!
+//! let searcher = index.searcher();
+//! let query = searcher.parse_query("fox^2 raccoon").unwrap();
+//! let result = searcher.search(&query, TopDocs::new(10)).unwrap();
+//! assert_eq!(result.total_hits, 1);
+//! assert_eq!(searcher.doc(result.docs[0].doc_id).unwrap().as_deref(), Some(&doc!("dog raccoon fox")));
+//! ```
+
+mod codec;
+mod collector;
 mod doc;
+mod doc_store;
+mod doc_values;
+mod error;
+mod explain;
+mod facet;
+mod highlight;
+pub mod ingest;
+pub mod json;
+mod mapped;
+mod numeric;
+mod query;
+mod schema;
+mod searcher;
+mod segment;
+pub mod server;
+pub mod shell;
+mod store;
 mod token;
-
-pub use doc::Document;
-use std::cmp;
-pub use token::TokenizeType;
-
-use doc::*;
-use std::collections::HashMap;
-use std::iter::Peekable;
-use std::slice::Iter;
-use token::*;
+mod wal;
+
+pub use collector::{Collector, Count, DocSet, TopDocs, TopFieldDocs};
+pub use doc::{DocAndScore, Document};
+pub use doc_values::{Missing, Order, Sort, SortField};
+pub use error::{Error, Result};
+pub use explain::{explain, Explanation};
+pub use facet::{Facet, FacetCollector, FacetCount, FacetResult, DEFAULT_FACET_LIMIT};
+pub use highlight::{Highlighter, HighlighterConfig};
+pub use query::{
+    BooleanQuery, PhraseQuery, Query, RangeQuery, Scorer, TermQuery, Weight, TERMINATED,
+};
+pub use schema::{FieldEntry, FieldType, Schema, DEFAULT_FIELD};
+pub use searcher::{IndexReader, Searcher};
+pub use token::{tokenize, Analyzer, Token, TokenKind, TokenizeType};
+pub use wal::SyncPolicy;
+
+use doc_values::{doc_values, Column, ColumnRef, DocValue, ValueRef};
+use mapped::MappedIndex;
+use numeric::{parse_value, NumericIndex};
+use schema::*;
+use segment::{SegmentWriter, SpillDir, Usage};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::io::{self, Write};
+use std::iter;
+use std::ops::Bound;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use wal::{Op, Wal};
 
 type Term = String;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 struct TermFreq {
-    term_count: usize,
+    // field => number of terms in the field
+    field_lengths: HashMap<String, usize>,
     terms: HashMap<Term, usize>,
 }
 
 impl TermFreq {
     fn new() -> Self {
         Self {
-            term_count: 0,
+            field_lengths: HashMap::new(),
             terms: HashMap::new(),
         }
     }
 
     fn put_term(&mut self, term: Term, term_count: usize) {
-        self.term_count += term_count;
+        let (field, _) = split_field_term(&term);
+        *self.field_lengths.entry(field.to_string()).or_default() += term_count;
         self.terms.insert(term, term_count);
     }
-
-    fn tf(&self, term: &Term) -> f32 {
-        let term_freq = match self.terms.get(term) {
-            None => 0,
-            Some(freq) => *freq,
-        };
-
-        (term_freq as f32) / (self.term_count as f32)
-    }
 }
 
+#[deprecated(note = "renamed to Index")]
+pub type PositionalIndex = Index;
+
 #[derive(Debug, PartialEq)]
-struct PositionalIndex {
+pub struct Index {
+    // number of live documents
     doc_count: usize,
 
+    // id of the next added document. ids of deleted documents are not reused.
+    next_doc_id: usize,
+
+    // tokenizer used at indexing, which is also used to analyze queries
+    tokenize_type: TokenizeType,
+
+    schema: Schema,
+
     // Term to PostingList mapping
     postings: HashMap<Term, PostingList>,
 
@@ -54,15 +121,31 @@ struct PositionalIndex {
 
     // doc_id => TermFreq mapping
     term_freq: HashMap<usize, TermFreq>,
+
+    // field => values of numeric and date fields
+    numeric: HashMap<String, NumericIndex>,
+
+    // field => values of numeric, date and keyword fields by doc id, for sorting
+    doc_values: HashMap<String, Column>,
+
+    // files of an opened index, which are read instead of the maps above until
+    // the index is loaded into memory
+    mapped: Option<MappedIndex>,
 }
 
-impl PositionalIndex {
-    fn new(doc_count: usize) -> Self {
-        PositionalIndex {
+impl Index {
+    fn new(doc_count: usize, tokenize_type: TokenizeType, schema: Schema) -> Self {
+        Index {
             doc_count,
+            next_doc_id: doc_count,
+            tokenize_type,
+            schema,
             postings: HashMap::new(),
             stored: HashMap::new(),
             term_freq: HashMap::new(),
+            numeric: HashMap::new(),
+            doc_values: HashMap::new(),
+            mapped: None,
         }
     }
 
@@ -80,32 +163,386 @@ impl PositionalIndex {
     }
 
     fn store_document(&mut self, id: usize, doc: Document) {
+        for (field, value) in doc_values(&self.schema, &doc) {
+            if let DocValue::Number(value) = value {
+                self.numeric
+                    .entry(field.clone())
+                    .or_default()
+                    .insert(value, id);
+            }
+            self.doc_values.entry(field).or_default().set(id, value);
+        }
         self.stored.insert(id, doc);
     }
 
-    fn doc(&self, id: usize) -> Option<&Document> {
-        self.stored.get(&id)
+    // fails if the document can't be read from the files of an opened index
+    pub fn doc(&self, id: usize) -> Result<Option<Cow<'_, Document>>> {
+        self.read_doc(id).map_err(store::index_error)
+    }
+
+    fn read_doc(&self, id: usize) -> io::Result<Option<Cow<'_, Document>>> {
+        match &self.mapped {
+            Some(mapped) => Ok(mapped.doc(id)?.map(Cow::Owned)),
+            None => Ok(self.stored.get(&id).map(Cow::Borrowed)),
+        }
+    }
+
+    // passes every stored document in ascending order of ids to `f`
+    fn for_each_doc(
+        &self,
+        mut f: impl FnMut(usize, &Document) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if let Some(mapped) = &self.mapped {
+            return mapped.for_each_doc(|id, doc| f(id, &doc));
+        }
+        let mut ids = self.stored.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            f(id, &self.stored[&id])?;
+        }
+        Ok(())
+    }
+
+    // postings of the term ordered by doc id
+    fn read_postings(&self, term: &str) -> io::Result<Option<Cow<'_, [PostingData]>>> {
+        match &self.mapped {
+            Some(mapped) => Ok(mapped.postings(term)?.map(Cow::Owned)),
+            None => Ok(self
+                .postings
+                .get(term)
+                .map(|pl| Cow::Borrowed(pl.postings.as_slice()))),
+        }
+    }
+
+    // number of documents containing the term
+    fn doc_freq(&self, term: &str) -> io::Result<usize> {
+        match &self.mapped {
+            Some(mapped) => mapped.doc_freq(term),
+            None => Ok(self.postings.get(term).map_or(0, |pl| pl.postings.len())),
+        }
+    }
+
+    // terms starting with `prefix` in term order
+    fn terms_with_prefix(&self, prefix: &str) -> io::Result<Vec<Term>> {
+        match &self.mapped {
+            Some(mapped) => mapped.terms_with_prefix(prefix),
+            None => {
+                let mut terms = self
+                    .postings
+                    .keys()
+                    .filter(|term| term.starts_with(prefix))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                terms.sort_unstable();
+                Ok(terms)
+            }
+        }
+    }
+
+    // number of terms of the field in the document
+    fn field_length(&self, doc_id: usize, field: &str) -> io::Result<usize> {
+        match &self.mapped {
+            Some(mapped) => mapped.field_length(doc_id, field),
+            None => Ok(self
+                .term_freq
+                .get(&doc_id)
+                .and_then(|term_freq| term_freq.field_lengths.get(field))
+                .map_or(0, |length| *length)),
+        }
+    }
+
+    // fields with terms, of which the lengths are saved
+    fn norm_fields(&self) -> Vec<String> {
+        match &self.mapped {
+            Some(mapped) => mapped.norm_fields().to_vec(),
+            None => self
+                .term_freq
+                .values()
+                .flat_map(|term_freq| term_freq.field_lengths.keys())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    // ids of documents with a value of the numeric or date field within the
+    // bounds, in ascending order
+    fn numeric_range(
+        &self,
+        field: &str,
+        lower: Bound<f64>,
+        upper: Bound<f64>,
+    ) -> io::Result<Vec<usize>> {
+        match &self.mapped {
+            Some(mapped) => mapped.numeric_range(field, lower, upper),
+            None => Ok(self
+                .numeric
+                .get(field)
+                .map_or_else(Vec::new, |numeric| numeric.range(lower, upper))),
+        }
+    }
+
+    // writes the points of the numeric or date field to its points file
+    fn write_points(&self, field: &str, w: &mut impl Write) -> io::Result<()> {
+        match &self.mapped {
+            Some(mapped) => w.write_all(mapped.points(field)),
+            None => match self.numeric.get(field) {
+                Some(numeric) => numeric.write_to(w),
+                None => Ok(()),
+            },
+        }
+    }
+
+    // values of the numeric, date or keyword field by doc id
+    fn column(&self, field: &str) -> io::Result<Option<ColumnRef<'_>>> {
+        match &self.mapped {
+            Some(mapped) => Ok(mapped.column(field)?.map(ColumnRef::Mapped)),
+            None => Ok(self.doc_values.get(field).map(ColumnRef::Memory)),
+        }
+    }
+
+    // value of the numeric, date or keyword field of the document
+    fn doc_value(&self, field: &str, doc_id: usize) -> io::Result<Option<ValueRef<'_>>> {
+        match self.column(field)? {
+            Some(column) => column.get(doc_id),
+            None => Ok(None),
+        }
+    }
+
+    // writes the values of the numeric, date or keyword field to its column file
+    fn write_column(&self, field: &FieldEntry, w: &mut impl Write) -> io::Result<()> {
+        if let Some(mapped) = &self.mapped {
+            return w.write_all(mapped.column_file(&field.name));
+        }
+        let keyword = field.field_type == FieldType::Keyword;
+        match self.doc_values.get(&field.name) {
+            Some(column) => column.write_to(w, self.next_doc_id, keyword),
+            None => Column::default().write_to(w, self.next_doc_id, keyword),
+        }
+    }
+
+    // a copy of the index in memory, which is read from the files of an opened index
+    pub(crate) fn to_memory(&self) -> Result<Index> {
+        let mut index = Index::new(self.doc_count, self.tokenize_type, self.schema.clone());
+        index.next_doc_id = self.next_doc_id;
+        match &self.mapped {
+            Some(mapped) => mapped.load_into(&mut index).map_err(store::index_error)?,
+            None => {
+                index.postings = self.postings.clone();
+                index.stored = self.stored.clone();
+                index.term_freq = self.term_freq.clone();
+                index.numeric = self.numeric.clone();
+                index.doc_values = self.doc_values.clone();
+            }
+        }
+        Ok(index)
+    }
+
+    // reads every term and document of an opened index into memory, which
+    // `add_document` and `delete_document` do first. the index is unchanged
+    // if it fails.
+    pub fn load(&mut self) -> Result<()> {
+        let mapped = match self.mapped.take() {
+            None => return Ok(()),
+            Some(mapped) => mapped,
+        };
+        if let Err(e) = mapped.load_into(self) {
+            self.postings.clear();
+            self.stored.clear();
+            self.term_freq.clear();
+            self.numeric.clear();
+            self.doc_values.clear();
+            self.mapped = Some(mapped);
+            return Err(store::index_error(e));
+        }
+        Ok(())
+    }
+
+    // indexes a document into the built index and returns its id
+    pub fn add_document(&mut self, doc: Document) -> Result<usize> {
+        self.add_document_with(doc, |_, _| Ok(()))
+    }
+
+    // adds a document as `add_document` does, calling `accepted` with its id
+    // once it is analyzed. nothing is added if either of them fails.
+    pub(crate) fn add_document_with(
+        &mut self,
+        doc: Document,
+        accepted: impl FnOnce(usize, &Document) -> Result<()>,
+    ) -> Result<usize> {
+        self.load()?;
+        let terms = analyze_document(self.tokenize_type, &self.schema, &doc)?;
+        let id = self.next_doc_id;
+        accepted(id, &doc)?;
+        self.next_doc_id += 1;
+        self.doc_count += 1;
+
+        for (term, positions) in terms {
+            self.push_term_freq(id, term.clone(), positions.len());
+            self.push_posting(
+                term,
+                PostingData {
+                    doc_id: id,
+                    positions,
+                },
+            );
+        }
+        self.store_document(id, doc);
+        Ok(id)
+    }
+
+    // removes a document from postings and the store, and returns it if it was
+    // stored. nothing is removed from an opened index which fails to be loaded.
+    pub fn delete_document(&mut self, id: usize) -> Result<Option<Document>> {
+        self.load()?;
+        let doc = match self.stored.remove(&id) {
+            None => return Ok(None),
+            Some(doc) => doc,
+        };
+        self.doc_count -= 1;
+        for (field, value) in doc_values(&self.schema, &doc) {
+            if let (DocValue::Number(value), Some(numeric)) = (&value, self.numeric.get_mut(&field))
+            {
+                numeric.remove(*value, id);
+            }
+            if let Some(column) = self.doc_values.get_mut(&field) {
+                column.remove(id);
+            }
+        }
+
+        let term_freq = match self.term_freq.remove(&id) {
+            None => return Ok(Some(doc)),
+            Some(term_freq) => term_freq,
+        };
+        for term in term_freq.terms.keys() {
+            let posting_list = match self.postings.get_mut(term) {
+                None => continue,
+                Some(posting_list) => posting_list,
+            };
+            if let Ok(i) = posting_list
+                .postings
+                .binary_search_by_key(&id, |posting| posting.doc_id)
+            {
+                posting_list.postings.remove(i);
+            }
+            if posting_list.postings.is_empty() {
+                self.postings.remove(term);
+            }
+        }
+        Ok(Some(doc))
+    }
+
+    // ids of documents whose keyword `field` is `value` in ascending order,
+    // which are found by the postings of the value. fails if the field isn't a
+    // keyword field.
+    pub fn find_documents(&self, field: &str, value: &str) -> Result<Vec<usize>> {
+        if self.schema.field_type(field) != Some(FieldType::Keyword) {
+            return Err(Error::SchemaViolation(format!(
+                "field is not a keyword: {}",
+                field
+            )));
+        }
+        let postings = self
+            .read_postings(&field_term(field, value))
+            .map_err(store::index_error)?;
+        Ok(postings.map_or_else(Vec::new, |postings| {
+            postings.iter().map(|posting| posting.doc_id).collect()
+        }))
+    }
+
+    pub fn searcher(&self) -> Searcher<'_> {
+        Searcher::new(self)
+    }
+
+    pub fn tokenize_type(&self) -> TokenizeType {
+        self.tokenize_type
+    }
+
+    // analyzer used at indexing, which is also used to analyze queries
+    pub fn analyzer(&self) -> &dyn Analyzer {
+        &self.tokenize_type
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
     }
 
-    fn idf(&self, term: &Term) -> f32 {
-        let term_doc_count = match self.postings.get(term) {
-            None => 0,
-            Some(pl) => pl.postings.len(),
-        } + 1;
-        ((self.doc_count as f32) / (term_doc_count as f32))
+    pub fn stats(&self) -> IndexStats {
+        if let Some(mapped) = &self.mapped {
+            let counts = mapped.counts();
+            return IndexStats {
+                doc_count: self.doc_count,
+                term_count: counts.term_count,
+                posting_count: counts.posting_count,
+                token_count: counts.token_count,
+            };
+        }
+        IndexStats {
+            doc_count: self.doc_count,
+            term_count: self.postings.len(),
+            posting_count: self.postings.values().map(|pl| pl.postings.len()).sum(),
+            token_count: self
+                .term_freq
+                .values()
+                .flat_map(|tf| tf.field_lengths.values())
+                .sum(),
+        }
+    }
+
+    fn idf(&self, term: &Term) -> io::Result<f32> {
+        let term_doc_count = self.doc_freq(term)? + 1;
+        Ok(((self.doc_count as f32) / (term_doc_count as f32))
             .log2()
-            .max(0f32)
+            .max(0f32))
     }
 
-    fn tf(&self, doc_id: usize, term: &Term) -> f32 {
-        match self.term_freq.get(&doc_id) {
-            None => 0f32,
-            Some(term_freq) => term_freq.tf(term),
+    // occurrences of the term in the document
+    fn freq(&self, doc_id: usize, term: &Term) -> io::Result<usize> {
+        if self.mapped.is_none() {
+            return Ok(self
+                .term_freq
+                .get(&doc_id)
+                .and_then(|term_freq| term_freq.terms.get(term))
+                .map_or(0, |freq| *freq));
         }
+        let postings = match self.read_postings(term)? {
+            Some(postings) => postings,
+            None => return Ok(0),
+        };
+        Ok(
+            match postings.binary_search_by_key(&doc_id, |posting| posting.doc_id) {
+                Ok(i) => postings[i].positions.len(),
+                Err(_) => 0,
+            },
+        )
+    }
+
+    // freq / length of the field of the term in the document
+    fn tf(&self, doc_id: usize, term: &Term, freq: usize) -> io::Result<f32> {
+        if freq == 0 {
+            return Ok(0f32);
+        }
+        let (field, _) = split_field_term(term);
+        Ok((freq as f32) / (self.field_length(doc_id, field)? as f32))
     }
 }
 
 #[derive(Debug, PartialEq)]
+pub struct IndexStats {
+    pub doc_count: usize,
+
+    // number of distinct terms
+    pub term_count: usize,
+
+    // number of (term, document) pairs
+    pub posting_count: usize,
+
+    // number of indexed terms in all documents
+    pub token_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct PostingList {
     postings: Vec<PostingData>,
 }
@@ -122,7 +559,7 @@ impl PostingList {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 struct PostingData {
     doc_id: usize,
     positions: Vec<usize>,
@@ -155,38 +592,124 @@ impl TermDict {
         *index
     }
 
-    fn term(&self, idx: usize) -> Option<&Term> {
-        self.idx2term.get(&idx)
+    // terms ordered by their indices
+    fn into_terms(self) -> Vec<Term> {
+        let mut terms = self.idx2term.into_iter().collect::<Vec<_>>();
+        terms.sort_unstable_by_key(|(idx, _)| *idx);
+        terms.into_iter().map(|(_, term)| term).collect()
     }
 
-    #[allow(dead_code)]
     fn index(&self, term: &Term) -> Option<usize> {
         self.term2idx.get(term).copied()
     }
+
+    // (term, index) of every term in no particular order
+    fn iter(&self) -> impl Iterator<Item = (&Term, usize)> {
+        self.term2idx.iter().map(|(term, idx)| (term, *idx))
+    }
 }
 
 #[derive(Debug, Default)]
-struct IndexWriterConfig {
+pub struct IndexWriterConfig {
     pub tokenize_type: TokenizeType,
+    pub schema: Schema,
+
+    // number of threads analyzing documents. 0 or 1 analyzes them in the
+    // thread calling `write`.
+    pub threads: usize,
+
+    // bytes of analyzed documents kept in memory, shared by the threads.
+    // documents over it are flushed to a segment on disk. 0 keeps every
+    // document in memory.
+    //
+    // the budget bounds the memory of writing, and of `IndexWriter::save`,
+    // which merges the segments into files. `commit` and `build` return an
+    // index in memory, so they read every segment back and the budget doesn't
+    // hold for them.
+    pub ram_budget: usize,
+
+    // directory under which segments are flushed, or the temp directory of the system
+    pub tmp_dir: Option<PathBuf>,
+
+    // file logging writes and deletes until they are saved, which is
+    // replayed by `IndexWriter::open` after a crash. only `open` uses it.
+    pub wal: Option<PathBuf>,
+
+    // when the log is synced to disk
+    pub wal_sync: SyncPolicy,
+}
+
+// memory used by the documents an index writer keeps, and the segments it
+// flushed. documents waiting for a worker thread, at most `WORKER_QUEUE` for
+// each, are not counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexWriterStats {
+    // approximate bytes of the analyzed documents in memory
+    pub ram_bytes: usize,
+
+    pub buffered_docs: usize,
+    pub flushed_segments: usize,
+    pub flushed_docs: usize,
+}
+
+// documents waiting for a worker, after which `write` blocks until the worker
+// takes one. they are not counted by the budget, so the queue is kept short.
+const WORKER_QUEUE: usize = 16;
+
+// a thread analyzing the documents sent to it into its own segments.
+// it returns the segments and (doc_id, error) of documents it failed to analyze.
+#[derive(Debug)]
+struct Worker {
+    sender: SyncSender<(usize, Document)>,
+    handle: JoinHandle<(SegmentWriter, Vec<(usize, Error)>)>,
+}
+
+impl Worker {
+    fn spawn(tokenize_type: TokenizeType, schema: Schema, mut segments: SegmentWriter) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<(usize, Document)>(WORKER_QUEUE);
+        let handle = thread::spawn(move || {
+            let mut failures = Vec::new();
+            for (id, doc) in receiver {
+                match analyze_fields(tokenize_type, &schema, &doc) {
+                    Ok(terms) => segments.add(id, doc, terms),
+                    Err(e) => failures.push((id, e)),
+                }
+            }
+            (segments, failures)
+        });
+        Self { sender, handle }
+    }
 }
 
 #[derive(Debug)]
-struct IndexWriter {
+pub struct IndexWriter {
     seq: usize,
 
-    term_dict: TermDict,
+    // documents analyzed in the calling thread
+    segments: SegmentWriter,
+
+    // documents are distributed to the workers in turn if there are any
+    workers: Vec<Worker>,
 
-    // (doc_id, dict_index, positions)
-    term_positions: Vec<(usize, usize, Vec<usize>)>,
+    usage: Arc<Usage>,
 
-    // (doc_id, Document)
-    stored: Vec<(usize, Document)>,
+    // ids of written documents which are removed at commit
+    deleted: BTreeSet<usize>,
+
+    wal: Option<Wal>,
 
     tokenize_type: TokenizeType,
+
+    schema: Schema,
+}
+
+impl Default for IndexWriter {
+    fn default() -> Self {
+        IndexWriter::new()
+    }
 }
 
 impl IndexWriter {
-    #[allow(dead_code)]
     pub fn new() -> Self {
         IndexWriter::with_config(IndexWriterConfig {
             ..Default::default()
@@ -194,12 +717,70 @@ impl IndexWriter {
     }
 
     pub fn with_config(config: IndexWriterConfig) -> Self {
+        let tmp_dir = config.tmp_dir.unwrap_or_else(env::temp_dir);
+        let spill_dir = Arc::new(SpillDir::new(&tmp_dir));
+        let usage = Arc::new(Usage::default());
+        let threads = config.threads.max(1);
+        // the budget is divided so that every thread can fill its share
+        let budget = match config.ram_budget {
+            0 => 0,
+            ram_budget => (ram_budget / threads).max(1),
+        };
+        let segments = || {
+            SegmentWriter::new(
+                budget,
+                spill_dir.clone(),
+                usage.clone(),
+                config.schema.clone(),
+            )
+        };
+
+        let workers = match threads {
+            1 => Vec::new(),
+            threads => (0..threads)
+                .map(|_| Worker::spawn(config.tokenize_type, config.schema.clone(), segments()))
+                .collect(),
+        };
         Self {
             seq: 0,
-            term_dict: TermDict::new(),
-            term_positions: Vec::new(),
-            stored: Vec::new(),
+            segments: segments(),
+            workers,
+            usage,
+            deleted: BTreeSet::new(),
+            wal: None,
             tokenize_type: config.tokenize_type,
+            schema: config.schema,
+        }
+    }
+
+    // a writer logging its operations to the WAL of the config, if it has one.
+    // operations left in the log by a writer which crashed before `save` are
+    // replayed first, so the writer has the documents it had.
+    pub fn open(mut config: IndexWriterConfig) -> Result<Self> {
+        let wal = config.wal.take();
+        let sync = config.wal_sync;
+        let mut index_writer = IndexWriter::with_config(config);
+        let path = match wal {
+            None => return Ok(index_writer),
+            Some(path) => path,
+        };
+        let (wal, ops) = Wal::open(&path, sync).map_err(store::index_error)?;
+        for op in ops {
+            match op {
+                Op::Write(_, doc) => index_writer.write(doc)?,
+                Op::Delete(id) => index_writer.delete(id)?,
+            }
+        }
+        index_writer.wal = Some(wal);
+        Ok(index_writer)
+    }
+
+    pub fn stats(&self) -> IndexWriterStats {
+        IndexWriterStats {
+            ram_bytes: self.usage.ram_bytes.load(Ordering::Relaxed),
+            buffered_docs: self.usage.buffered_docs.load(Ordering::Relaxed),
+            flushed_segments: self.usage.flushed_segments.load(Ordering::Relaxed),
+            flushed_docs: self.usage.flushed_docs.load(Ordering::Relaxed),
         }
     }
 
@@ -209,218 +790,354 @@ impl IndexWriter {
         curr
     }
 
-    fn write(&mut self, doc: Document) {
-        let id = self.seq_incr();
-        let tokens = tokenize(self.tokenize_type, doc.body.as_str());
-
-        let mut data: HashMap<usize, Vec<usize>> = HashMap::new();
-        for token in tokens {
-            match token.kind {
-                TokenKind::Term(t) => {
-                    let index = self.term_dict.add_term(t);
-                    data.entry(index)
-                        .or_insert_with(Vec::new)
-                        .push(token.position);
-                }
-                _ => continue,
+    // fails without writing anything if the document can't be analyzed
+    // or has a field not in the schema.
+    //
+    // with worker threads, the document is checked against the schema here and
+    // analyzed later by a worker, whose failure is returned by `commit`.
+    // doc ids are given in the order of writes either way.
+    pub fn write(&mut self, doc: Document) -> Result<()> {
+        if self.workers.is_empty() {
+            let terms = analyze_document(self.tokenize_type, &self.schema, &doc)?;
+            if let Some(wal) = &mut self.wal {
+                wal.log_write(self.seq, &doc)?;
             }
-        }
-        for (index, positions) in data.into_iter() {
-            self.term_positions.push((id, index, positions));
+            let id = self.seq_incr();
+            self.segments.add(id, doc, terms);
+            return Ok(());
         }
 
-        self.stored.push((id, doc));
+        check_document(&self.schema, &doc)?;
+        if let Some(wal) = &mut self.wal {
+            wal.log_write(self.seq, &doc)?;
+        }
+        let id = self.seq_incr();
+        let worker = &self.workers[id % self.workers.len()];
+        // a worker stops receiving only if it panics, which `commit` resumes
+        let _ = worker.sender.send((id, doc));
+        Ok(())
     }
 
-    fn build(self) -> PositionalIndex {
-        let mut index = PositionalIndex::new(self.seq);
-
-        for (doc_id, idx, positions) in self.term_positions {
-            let term = self.term_dict.term(idx).unwrap();
-            index.push_term_freq(doc_id, term.clone(), positions.len());
-            index.push_posting(term.clone(), PostingData { doc_id, positions });
+    // removes the document of `id`, the index of its write, at commit.
+    // ids of documents not written yet are ignored.
+    pub fn delete(&mut self, id: usize) -> Result<()> {
+        if id >= self.seq || self.deleted.contains(&id) {
+            return Ok(());
         }
-
-        for (id, doc) in self.stored {
-            index.store_document(id, doc);
+        if let Some(wal) = &mut self.wal {
+            wal.log_delete(id)?;
         }
-
-        index
+        self.deleted.insert(id);
+        Ok(())
     }
-}
 
-struct MultiTermQuery {
-    terms: Vec<Term>,
-}
+    // commits the documents and saves the index into `dir`, after which the
+    // WAL is cleared. `commit` and `build` leave the WAL as it is.
+    //
+    // once segments are flushed, they are merged into the files of the commit
+    // without reading them into memory, and the index is opened from `dir`.
+    // the files are the same as those of the index of `commit`.
+    pub fn save<P: AsRef<Path>>(mut self, dir: P) -> Result<Index> {
+        let wal = self.wal.take();
+        let (segments, failure) = self.join_workers();
+        let index = if failure.is_none() && self.usage.flushed_segments.load(Ordering::Relaxed) > 0
+        {
+            self.save_flushed(segments, dir.as_ref())?
+        } else {
+            let index = match self.merge(segments, failure) {
+                (index, None) => index,
+                (_, Some(e)) => return Err(e),
+            };
+            index.save(dir)?;
+            index
+        };
+        if let Some(mut wal) = wal {
+            wal.clear()?;
+        }
+        Ok(index)
+    }
 
-impl MultiTermQuery {
-    fn new(terms: Vec<Term>) -> Self {
-        Self { terms }
+    fn save_flushed(self, segments: Vec<SegmentWriter>, dir: &Path) -> Result<Index> {
+        let mut segments = iter::once(self.segments)
+            .chain(segments)
+            .collect::<Vec<_>>();
+        for segments in segments.iter_mut() {
+            segments.flush_all()?;
+        }
+        segment::save_segments(
+            &segments,
+            &self.deleted,
+            self.seq,
+            self.tokenize_type,
+            &self.schema,
+            dir,
+        )?;
+        Index::open(dir)
     }
 
-    fn iter<'a>(&self, index: &'a PositionalIndex) -> DocIterator<'a> {
-        DocIterator::new(self, index)
+    // waits for the workers and merges all segments into an index, which is the
+    // same as the one written by a single thread without a budget. fails with
+    // the error of the first document a worker failed to analyze, or if a
+    // segment failed to be flushed or read back.
+    //
+    // the index is in memory, so flushed segments are read back regardless of
+    // the budget. `save` keeps to the budget instead.
+    pub fn commit(mut self) -> Result<Index> {
+        let (segments, failure) = self.join_workers();
+        match self.merge(segments, failure) {
+            (index, None) => Ok(index),
+            (_, Some(e)) => Err(e),
+        }
     }
-}
 
-struct DocIterator<'a> {
-    cursors: Vec<Peekable<Iter<'a, PostingData>>>,
-    next_doc: Option<usize>,
-}
+    // `commit`, except that documents which a worker failed to analyze, and
+    // segments which can't be read back, are left out of the index.
+    // without workers and a budget, every written document is indexed.
+    // flushed segments are read back into memory as `commit` does.
+    pub fn build(mut self) -> Index {
+        let (segments, failure) = self.join_workers();
+        self.merge(segments, failure).0
+    }
 
-impl<'a> DocIterator<'a> {
-    fn new(query: &MultiTermQuery, index: &'a PositionalIndex) -> Self {
-        let (cursors, next_doc) = Self::build_cursors(query, index);
-        Self { cursors, next_doc }
-    }
-
-    fn build_cursors(
-        query: &MultiTermQuery,
-        index: &'a PositionalIndex,
-    ) -> (Vec<Peekable<Iter<'a, PostingData>>>, Option<usize>) {
-        let mut cursors = Vec::with_capacity(query.terms.len());
-        let mut next_doc = None;
-        for term in query.terms.iter() {
-            match index.postings.get(term) {
-                Some(pl) => {
-                    let mut cursor = pl.postings.iter().peekable();
-                    let peek = cursor.peek();
-                    assert!(peek.is_some(), "posting list is not empty when term exist");
-
-                    // search start from biggest document id
-                    next_doc = next_doc.max(peek.map(|pd| pd.doc_id));
-                    cursors.push(cursor);
-                }
-                None => {
-                    return (Vec::with_capacity(0), None);
+    // waits for the workers, and returns their segments and the error of the
+    // first document they failed to analyze
+    fn join_workers(&mut self) -> (Vec<SegmentWriter>, Option<Error>) {
+        let mut segments = Vec::new();
+        let mut first_failure: Option<(usize, Error)> = None;
+        for worker in std::mem::take(&mut self.workers) {
+            drop(worker.sender);
+            let (worker_segments, failures) = match worker.handle.join() {
+                Ok(output) => output,
+                Err(e) => panic::resume_unwind(e),
+            };
+            for (id, e) in failures {
+                if first_failure.as_ref().is_none_or(|(first, _)| id < *first) {
+                    first_failure = Some((id, e));
                 }
             }
+            segments.push(worker_segments);
         }
+        (segments, first_failure.map(|(_, e)| e))
+    }
 
-        (cursors, next_doc)
+    // merges the segments of the calling thread and the workers into an index
+    fn merge(self, segments: Vec<SegmentWriter>, failure: Option<Error>) -> (Index, Option<Error>) {
+        let mut index = Index::new(self.seq, self.tokenize_type, self.schema);
+        let mut error = None;
+        for segments in iter::once(self.segments).chain(segments) {
+            if let Err(e) = segments.merge_into(&mut index) {
+                error.get_or_insert(e.into());
+            }
+        }
+        // segments may have postings of interleaved documents
+        for posting_list in index.postings.values_mut() {
+            posting_list.postings.sort_unstable_by_key(|p| p.doc_id);
+        }
+        // the index is in memory, which never fails to be loaded
+        for id in self.deleted {
+            let _ = index.delete_document(id);
+        }
+        // documents which failed have ids but are never stored
+        index.doc_count = index.stored.len();
+        (index, failure.or(error))
     }
 }
 
-impl<'a> Iterator for DocIterator<'a> {
-    type Item = usize;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let target = self.next_doc?;
+// term => positions, for every text and keyword field of the schema
+fn analyze_document(
+    tokenize_type: TokenizeType,
+    schema: &Schema,
+    doc: &Document,
+) -> Result<HashMap<Term, Vec<usize>>> {
+    check_document(schema, doc)?;
+    analyze_fields(tokenize_type, schema, doc)
+}
 
-            let mut max_doc = target;
-            for pl in self.cursors.iter_mut() {
-                // skip until posting.doc_id < target
-                // Note: this is naive implementation
-                while pl.next_if(|posting| posting.doc_id < target).is_some() {}
+// fails if the document has a field not in the schema or an invalid value
+fn check_document(schema: &Schema, doc: &Document) -> Result<()> {
+    if let Some(name) = doc
+        .fields
+        .keys()
+        .find(|name| schema.field_type(name).is_none())
+    {
+        return Err(Error::SchemaViolation(format!(
+            "field is not in the schema: {}",
+            name
+        )));
+    }
 
-                // if a pointer reached at the end, nothing matches
-                let posting = pl.peek()?;
-                max_doc = cmp::max(max_doc, posting.doc_id);
+    for field in schema.fields() {
+        if let (FieldType::Numeric | FieldType::Date, Some(value)) =
+            (field.field_type, doc.field(&field.name))
+        {
+            if parse_value(field.field_type, value).is_none() {
+                return Err(Error::SchemaViolation(format!(
+                    "invalid {} value of {}: {}",
+                    field.field_type.as_str(),
+                    field.name,
+                    value
+                )));
             }
-            if max_doc != target {
-                self.next_doc.replace(max_doc);
+        }
+    }
+
+    Ok(())
+}
+
+// term => positions of a document checked by `check_document`
+fn analyze_fields(
+    tokenize_type: TokenizeType,
+    schema: &Schema,
+    doc: &Document,
+) -> Result<HashMap<Term, Vec<usize>>> {
+    let mut data: HashMap<Term, Vec<usize>> = HashMap::new();
+    for field in schema.fields() {
+        let value = match doc.field(&field.name) {
+            None => continue,
+            Some(value) => value,
+        };
+        match field.field_type {
+            FieldType::Text => {}
+            FieldType::Keyword if !value.is_empty() => {
+                data.insert(field_term(&field.name, value), vec![0]);
                 continue;
             }
-            self.next_doc.replace(target + 1);
-            return Some(target);
+            _ => continue,
+        }
+        for token in tokenize(tokenize_type, value)? {
+            match token.kind {
+                TokenKind::Term(t) => {
+                    data.entry(field_term(&field.name, t))
+                        .or_default()
+                        .push(token.position);
+                }
+                _ => continue,
+            }
         }
     }
+    Ok(data)
 }
 
 #[allow(dead_code)]
-fn search_term(index: &PositionalIndex, term: &Term) -> Vec<usize> {
-    let posting_list = match index.postings.get(term.as_str()) {
-        None => return Vec::new(),
-        Some(posting_list) => posting_list,
+fn search_term(index: &Index, term: &Term) -> Vec<usize> {
+    let postings = match index.read_postings(term) {
+        Ok(Some(postings)) => postings,
+        _ => return Vec::new(),
     };
 
-    let idf = index.idf(term);
+    let idf = match index.idf(term) {
+        Ok(idf) => idf,
+        Err(_) => return Vec::new(),
+    };
 
-    let mut docs_scores: Vec<DocAndScore> = posting_list
-        .postings
+    let docs_scores = postings
         .iter()
-        .map(|pl| DocAndScore::new_with_score(pl.doc_id, index.tf(pl.doc_id, term) * idf))
-        .collect();
+        .map(|pl| {
+            let tf = index.tf(pl.doc_id, term, pl.positions.len())?;
+            Ok(DocAndScore::new_with_score(pl.doc_id, tf * idf))
+        })
+        .collect::<io::Result<Vec<_>>>();
+    let mut docs_scores = match docs_scores {
+        Ok(docs_scores) => docs_scores,
+        Err(_) => return Vec::new(),
+    };
     docs_scores.sort();
 
     docs_scores.into_iter().map(|ds| ds.doc_id).collect()
 }
 
-fn search_multi_term(index: &PositionalIndex, query: MultiTermQuery) -> Vec<usize> {
-    query.iter(index).collect()
+fn analyze(tokenize_type: TokenizeType, sentence: &str) -> Result<Vec<Term>> {
+    tokenize_type.terms(sentence)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SearchResult {
+    // number of all matched documents
+    pub total_hits: usize,
+
+    // top documents ordered by score, or by the sort of the collector
+    pub docs: Vec<DocAndScore>,
+}
+
+// returns at most `top_k` documents containing all terms in `sentence`,
+// ordered by the sum of boost * tf * idf of the terms.
+//
+// `field:text` searches a text field instead of the default field, and
+// `text^boost` multiplies the score of the terms by the boost.
+pub fn search(index: &Index, sentence: &str, top_k: usize) -> Result<SearchResult> {
+    let searcher = index.searcher();
+    let query = searcher.parse_query(sentence)?;
+    searcher.search(&query, TopDocs::new(top_k))
 }
 
 pub fn search_main(
     tokenize_type: TokenizeType,
     docs: Vec<Document>,
     sentence: &str,
-) -> Vec<Document> {
-    let mut index_writer = IndexWriter::with_config(IndexWriterConfig { tokenize_type });
+) -> Result<Vec<Document>> {
+    let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+        tokenize_type,
+        ..Default::default()
+    });
     for doc in docs {
-        index_writer.write(doc);
+        index_writer.write(doc)?;
     }
     let index = index_writer.build();
 
-    let terms = tokenize(tokenize_type, sentence)
-        .iter()
-        .filter_map(|t| match t.kind {
-            TokenKind::Term(term) => Some(term.to_string()),
-            _ => None,
-        })
-        .collect::<Vec<_>>();
-    let query = MultiTermQuery::new(terms);
+    let mut query = BooleanQuery::default();
+    for term in analyze(tokenize_type, sentence)? {
+        query.add_must(TermQuery::new(DEFAULT_FIELD, &term));
+    }
 
-    search_multi_term(&index, query)
-        .iter()
-        .map(|id| index.doc(*id).unwrap().clone())
-        .collect()
+    let ids = index.searcher().search(&query, DocSet::new())?;
+    let mut docs = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(doc) = index.doc(id)? {
+            docs.push(doc.into_owned());
+        }
+    }
+    Ok(docs)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        doc, search_main, search_term, IndexWriter, MultiTermQuery, TermDict, TokenizeType,
+        doc, field_term, search, search_main, search_term, BooleanQuery, Error, FieldType, Index,
+        IndexStats, IndexWriter, IndexWriterConfig, Query, Schema, TermDict, TermQuery,
+        TokenizeType, DEFAULT_FIELD, TERMINATED,
     };
 
+    // ids matched by a conjunction of the terms, iterated by its scorer
+    fn conjunction(index: &Index, terms: &[&str]) -> Vec<usize> {
+        let mut query = BooleanQuery::default();
+        for term in terms {
+            query.add_must(TermQuery::new(DEFAULT_FIELD, term));
+        }
+        let mut scorer = query.weight(index).unwrap().scorer(index).unwrap();
+        let mut ids = Vec::new();
+        while scorer.doc() != TERMINATED {
+            ids.push(scorer.doc());
+            scorer.advance(scorer.doc() + 1);
+        }
+        // an exhausted scorer stays exhausted
+        assert_eq!(scorer.advance(0), TERMINATED);
+        ids
+    }
+
     #[test]
-    fn doc_iter_test() {
+    fn conjunction_test() {
         let mut index_writer = IndexWriter::new();
-        index_writer.write(doc!("dog dog dog monkey bird"));
-        index_writer.write(doc!("dog cat cat fox"));
-        index_writer.write(doc!("dog raccoon fox"));
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
         let index = index_writer.build();
 
         // don't exist term
-        let query = MultiTermQuery::new(vec!["mouse".to_string(), "fox".to_string()]);
-        let mut iter = query.iter(&index);
-        assert_eq!(iter.next(), None);
-        assert_eq!(iter.next(), None);
-
-        let query = MultiTermQuery::new(vec!["dog".to_string()]);
-        let mut iter = query.iter(&index);
-        assert_eq!(iter.next(), Some(0));
-        assert_eq!(iter.next(), Some(1));
-        assert_eq!(iter.next(), Some(2));
-        assert_eq!(iter.next(), None);
-
-        let query = MultiTermQuery::new(vec!["dog".to_string(), "fox".to_string()]);
-        let mut iter = query.iter(&index);
-        assert_eq!(iter.next(), Some(1));
-        assert_eq!(iter.next(), Some(2));
-        assert_eq!(iter.next(), None);
-
-        let query = MultiTermQuery::new(vec!["dog".to_string(), "dog".to_string()]);
-        let mut iter = query.iter(&index);
-        assert_eq!(iter.next(), Some(0));
-        assert_eq!(iter.next(), Some(1));
-        assert_eq!(iter.next(), Some(2));
-        assert_eq!(iter.next(), None);
-
-        let query = MultiTermQuery::new(vec!["dog".to_string(), "bird".to_string()]);
-        let mut iter = query.iter(&index);
-        assert_eq!(iter.next(), Some(0));
-        assert_eq!(iter.next(), None);
+        assert_eq!(conjunction(&index, &["mouse", "fox"]), Vec::<usize>::new());
+        assert_eq!(conjunction(&index, &["dog"]), vec![0, 1, 2]);
+        assert_eq!(conjunction(&index, &["dog", "fox"]), vec![1, 2]);
+        assert_eq!(conjunction(&index, &["dog", "dog"]), vec![0, 1, 2]);
+        assert_eq!(conjunction(&index, &["dog", "bird"]), vec![0]);
     }
 
     macro_rules! map (
@@ -464,50 +1181,78 @@ mod tests {
 
         let term = "This".to_string();
         assert_eq!(term_dict.index(&term), Some(0));
-        assert_eq!(term_dict.term(0), Some(&term));
         let term = "is".to_string();
         assert_eq!(term_dict.index(&term), Some(1));
-        assert_eq!(term_dict.term(1), Some(&term));
         let term = "pen".to_string();
         assert_eq!(term_dict.index(&term), Some(3));
-        assert_eq!(term_dict.term(3), Some(&term));
+        assert_eq!(term_dict.add_term("is"), 1);
+        assert_eq!(term_dict.into_terms(), vec!["This", "is", "a", "pen"]);
     }
 
     #[test]
     fn tfidf_test() {
         let mut index_writer = IndexWriter::new();
-        index_writer.write(doc!("dog dog dog monkey bird"));
-        index_writer.write(doc!("dog cat cat fox"));
-        index_writer.write(doc!("dog raccoon fox"));
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
         let index = index_writer.build();
 
         let term = "dog".to_string();
-        assert_eq!(index.idf(&term), 0f32);
-        assert_eq!(index.tf(0, &term), 0.6);
-        assert_eq!(index.tf(1, &term), 0.25);
-        assert_eq!(index.tf(2, &term), 1f32 / 3f32);
+        assert_eq!(index.idf(&term).unwrap(), 0f32);
+        assert_eq!(
+            index.tf(0, &term, index.freq(0, &term).unwrap()).unwrap(),
+            0.6
+        );
+        assert_eq!(
+            index.tf(1, &term, index.freq(1, &term).unwrap()).unwrap(),
+            0.25
+        );
+        assert_eq!(
+            index.tf(2, &term, index.freq(2, &term).unwrap()).unwrap(),
+            1f32 / 3f32
+        );
 
         let term = "bird".to_string();
-        assert_eq!(index.idf(&term), 0.5849625007f32);
-        assert_eq!(index.tf(0, &term), 0.2);
-        assert_eq!(index.tf(1, &term), 0f32);
-        assert_eq!(index.tf(2, &term), 0f32);
+        assert_eq!(index.idf(&term).unwrap(), 0.584_962_5_f32);
+        assert_eq!(
+            index.tf(0, &term, index.freq(0, &term).unwrap()).unwrap(),
+            0.2
+        );
+        assert_eq!(
+            index.tf(1, &term, index.freq(1, &term).unwrap()).unwrap(),
+            0f32
+        );
+        assert_eq!(
+            index.tf(2, &term, index.freq(2, &term).unwrap()).unwrap(),
+            0f32
+        );
 
         let term = "fox".to_string();
-        assert_eq!(index.idf(&term), 0f32);
-        assert_eq!(index.tf(0, &term), 0f32);
-        assert_eq!(index.tf(1, &term), 0.25);
-        assert_eq!(index.tf(2, &term), 1f32 / 3f32);
+        assert_eq!(index.idf(&term).unwrap(), 0f32);
+        assert_eq!(
+            index.tf(0, &term, index.freq(0, &term).unwrap()).unwrap(),
+            0f32
+        );
+        assert_eq!(
+            index.tf(1, &term, index.freq(1, &term).unwrap()).unwrap(),
+            0.25
+        );
+        assert_eq!(
+            index.tf(2, &term, index.freq(2, &term).unwrap()).unwrap(),
+            1f32 / 3f32
+        );
     }
 
     #[test]
     fn indexing_test() {
         let mut index_writer = IndexWriter::new();
-        index_writer.write(doc!("What is this"));
-        index_writer.write(doc!("I am Taisuke"));
-        index_writer.write(doc!(
-            "that that is is that that is not is not is that it it is"
-        ));
+        index_writer.write(doc!("What is this")).unwrap();
+        index_writer.write(doc!("I am Taisuke")).unwrap();
+        index_writer
+            .write(doc!(
+                "that that is is that that is not is not is that it it is"
+            ))
+            .unwrap();
 
         let postings = map! {
             "I" => posting! { 1 => vec![0] },
@@ -531,16 +1276,252 @@ mod tests {
         assert_eq!(index.postings, postings);
         assert_eq!(index.stored, stored);
 
-        assert_eq!(index.doc(100), None);
-        assert_eq!(index.doc(0), Some(&doc!("What is this")));
+        assert_eq!(index.doc(100).unwrap(), None);
+        assert_eq!(
+            index.doc(0).unwrap().as_deref(),
+            Some(&doc!("What is this"))
+        );
+    }
+
+    #[test]
+    fn parallel_indexing_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("tag", FieldType::Keyword)
+            .add_field("price", FieldType::Numeric);
+        let writer = |threads| {
+            IndexWriter::with_config(IndexWriterConfig {
+                schema: schema.clone(),
+                threads,
+                ..Default::default()
+            })
+        };
+        let mut single = writer(1);
+        let mut parallel = writer(4);
+        let words = ["dog", "cat", "fox", "bird", "monkey"];
+        for i in 0..100 {
+            let mut doc = doc!((0..i % 7)
+                .map(|j| words[(i + j) % words.len()])
+                .collect::<Vec<_>>()
+                .join(" "));
+            doc.set_field("title", words[i % words.len()]);
+            doc.set_field("tag", ["a", "b", "c"][i % 3]);
+            doc.set_field("price", i.to_string());
+            single.write(doc.clone()).unwrap();
+            parallel.write(doc).unwrap();
+        }
+
+        // a schema violation is returned by the write, without taking an id
+        let mut doc = doc!("dog");
+        doc.set_field("price", "x");
+        assert!(matches!(
+            parallel.write(doc),
+            Err(Error::SchemaViolation(_))
+        ));
+        single.write(doc!("dog")).unwrap();
+        parallel.write(doc!("dog")).unwrap();
+
+        let parallel = parallel.commit().unwrap();
+        assert_eq!(parallel, single.build());
+        assert_eq!(parallel.stats().doc_count, 101);
+        assert_eq!(parallel.find_documents("tag", "c").unwrap().len(), 33);
+
+        assert_eq!(writer(8).commit().unwrap(), writer(1).build());
+    }
+
+    #[test]
+    fn ram_budget_test() {
+        let tmp_dir = crate::store::tests::test_dir("ram_budget");
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let writer = |threads, ram_budget| {
+            IndexWriter::with_config(IndexWriterConfig {
+                threads,
+                ram_budget,
+                tmp_dir: Some(tmp_dir.clone()),
+                ..Default::default()
+            })
+        };
+        let mut unbudgeted = writer(1, 0);
+        let mut budgeted = writer(1, 512);
+        let mut parallel = writer(3, 512);
+        let words = ["dog", "cat", "fox", "bird", "monkey"];
+        for i in 0..50 {
+            let body = (0..i % 7)
+                .map(|j| words[(i + j) % words.len()])
+                .collect::<Vec<_>>()
+                .join(" ");
+            unbudgeted.write(doc!(&body)).unwrap();
+            budgeted.write(doc!(&body)).unwrap();
+            parallel.write(doc!(&body)).unwrap();
+        }
+
+        let stats = unbudgeted.stats();
+        assert_eq!((stats.buffered_docs, stats.flushed_segments), (50, 0));
+        assert!(stats.ram_bytes > 512);
+        let stats = budgeted.stats();
+        assert!(stats.flushed_segments > 1);
+        assert_eq!(stats.buffered_docs + stats.flushed_docs, 50);
+        assert!(stats.ram_bytes < 512);
+
+        let expected = unbudgeted.build();
+        assert_eq!(budgeted.commit().unwrap(), expected);
+        assert_eq!(parallel.commit().unwrap(), expected);
+        // flushed segments are removed at commit
+        assert_eq!(std::fs::read_dir(&tmp_dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&tmp_dir).unwrap();
+    }
+
+    #[test]
+    fn wal_replay_test() {
+        let dir = crate::store::tests::test_dir("wal_replay");
+        std::fs::create_dir_all(&dir).unwrap();
+        let writer = |threads| {
+            IndexWriter::open(IndexWriterConfig {
+                threads,
+                wal: Some(dir.join("wal")),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        let mut expected = IndexWriter::new();
+        let mut index_writer = writer(1);
+        for body in ["dog cat", "dog fox", "cat bird"] {
+            expected.write(doc!(body)).unwrap();
+            index_writer.write(doc!(body)).unwrap();
+        }
+        expected.delete(1).unwrap();
+        index_writer.delete(1).unwrap();
+        // ids not written yet are ignored
+        index_writer.delete(3).unwrap();
+        expected.write(doc!("fox")).unwrap();
+        let expected = expected.build();
+        assert_eq!(expected.stats().doc_count, 3);
+
+        // a writer crashed before saving is replayed, and writes after it
+        drop(index_writer);
+        let mut index_writer = writer(2);
+        index_writer.write(doc!("fox")).unwrap();
+        drop(index_writer);
+        assert_eq!(writer(1).commit().unwrap(), expected);
+
+        // the log is cleared once the index is saved
+        let index = writer(1).save(dir.join("index")).unwrap();
+        assert_eq!(index.stats().doc_count, 3);
+        assert_eq!(Index::open(dir.join("index")).unwrap().stats().doc_count, 3);
+        assert_eq!(writer(1).build().stats().doc_count, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn field_indexing_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("url", FieldType::Stored);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+
+        let mut doc = doc!("dog cat cat fox");
+        doc.set_field("title", "fox");
+        doc.set_field("url", "https://example.com/fox");
+        index_writer.write(doc.clone()).unwrap();
+        index_writer.write(doc!("dog raccoon")).unwrap();
+        let index = index_writer.build();
+
+        let term = "fox".to_string();
+        assert_eq!(
+            index.tf(0, &term, index.freq(0, &term).unwrap()).unwrap(),
+            0.25
+        );
+        let term = field_term("title", "fox");
+        assert_eq!(
+            index.tf(0, &term, index.freq(0, &term).unwrap()).unwrap(),
+            1f32
+        );
+        assert_eq!(
+            index.tf(1, &term, index.freq(1, &term).unwrap()).unwrap(),
+            0f32
+        );
+        assert_eq!(search_term(&index, &term), vec![0]);
+
+        // stored only
+        let term = field_term("url", "https://example.com/fox");
+        assert_eq!(search_term(&index, &term), Vec::<usize>::new());
+        assert_eq!(index.doc(0).unwrap().as_deref(), Some(&doc));
+    }
+
+    #[test]
+    fn schema_violation_test() {
+        let mut index_writer = IndexWriter::new();
+        let mut doc = doc!("dog cat");
+        doc.set_field("title", "fox");
+        let err = index_writer.write(doc).unwrap_err();
+        assert!(matches!(err, Error::SchemaViolation(_)));
+
+        let mut schema = Schema::new();
+        schema.add_field("price", FieldType::Numeric);
+        let mut index = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        })
+        .build();
+        let mut doc = doc!("dog");
+        doc.set_field("price", "cheap");
+        let err = index.add_document(doc).unwrap_err();
+        assert!(matches!(err, Error::SchemaViolation(_)));
+        assert_eq!(index.stats().doc_count, 0);
+
+        // the rejected document is not written
+        index_writer.write(doc!("dog")).unwrap();
+        assert_eq!(
+            index_writer.build().doc(0).unwrap().as_deref(),
+            Some(&doc!("dog"))
+        );
+    }
+
+    #[test]
+    fn add_and_delete_document_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        let mut index = index_writer.build();
+
+        assert_eq!(index.add_document(doc!("dog raccoon fox")).unwrap(), 2);
+        assert_eq!(search_term(&index, &"fox".to_string()), vec![1, 2]);
+        assert_eq!(
+            index.doc(2).unwrap().as_deref(),
+            Some(&doc!("dog raccoon fox"))
+        );
+
+        assert_eq!(
+            index.delete_document(1).unwrap(),
+            Some(doc!("dog cat cat fox"))
+        );
+        assert_eq!(index.delete_document(1).unwrap(), None);
+        assert_eq!(search_term(&index, &"fox".to_string()), vec![2]);
+        assert_eq!(search_term(&index, &"cat".to_string()), Vec::<usize>::new());
+        assert!(!index.postings.contains_key("cat"));
+        assert_eq!(index.stats().doc_count, 2);
+
+        // ids are not reused
+        assert_eq!(index.add_document(doc!("cat")).unwrap(), 3);
+        assert!(matches!(
+            index.find_documents("body", "cat"),
+            Err(Error::SchemaViolation(_))
+        ));
     }
 
     #[test]
     fn tfidf_term_search_test() {
         let mut index_writer = IndexWriter::new();
-        index_writer.write(doc!("dog dog dog monkey bird"));
-        index_writer.write(doc!("dog cat cat fox"));
-        index_writer.write(doc!("dog raccoon fox"));
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
         let index = index_writer.build();
 
         let term = "dog".to_string();
@@ -553,10 +1534,11 @@ mod tests {
     #[test]
     fn search_term_test() {
         let mut iw = IndexWriter::new();
-        iw.write(doc!("I am Taisuke"));
+        iw.write(doc!("I am Taisuke")).unwrap();
         iw.write(doc!(
             "that that is is that that is not is not is that it it is"
-        ));
+        ))
+        .unwrap();
         let index = iw.build();
 
         let term = "Taisuke".to_string();
@@ -569,6 +1551,78 @@ mod tests {
         assert_eq!(search_term(&index, &term), Vec::<usize>::new());
     }
 
+    #[test]
+    fn search_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("bird fox")).unwrap();
+        index_writer.write(doc!("cat monkey")).unwrap();
+        let index = index_writer.build();
+
+        let ids = |result: crate::SearchResult| {
+            let ids = result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>();
+            (result.total_hits, ids)
+        };
+        assert_eq!(ids(search(&index, "fox", 10).unwrap()), (3, vec![3, 2, 1]));
+        assert_eq!(ids(search(&index, "fox", 2).unwrap()), (3, vec![3, 2]));
+        assert_eq!(ids(search(&index, "dog fox", 10).unwrap()), (2, vec![2, 1]));
+        assert_eq!(ids(search(&index, "mouse", 10).unwrap()), (0, Vec::new()));
+    }
+
+    #[test]
+    fn boost_search_test() {
+        let mut schema = Schema::new();
+        schema.add_field("title", FieldType::Text);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema: schema.clone(),
+            ..Default::default()
+        });
+        index_writer.write(doc!("dog cat cat")).unwrap();
+        let mut doc = doc!("dog dog cat");
+        doc.set_field("title", "dog");
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("fox")).unwrap();
+        index_writer.write(doc!("bird")).unwrap();
+        index_writer.write(doc!("raccoon")).unwrap();
+        let index = index_writer.build();
+
+        let ids =
+            |result: crate::SearchResult| result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>();
+        assert_eq!(ids(search(&index, "dog^2 cat", 10).unwrap()), vec![1, 0]);
+        assert_eq!(ids(search(&index, "dog cat^2", 10).unwrap()), vec![0, 1]);
+        assert_eq!(ids(search(&index, "title:dog^3", 10).unwrap()), vec![1]);
+
+        let doubled = search(&index, "dog^2 cat^2", 10).unwrap().docs;
+        for (hit, doubled) in search(&index, "dog cat", 10)
+            .unwrap()
+            .docs
+            .iter()
+            .zip(doubled)
+        {
+            assert_eq!(hit.score * 2f32, doubled.score);
+        }
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        let index = index_writer.build();
+
+        assert_eq!(
+            index.stats(),
+            IndexStats {
+                doc_count: 2,
+                term_count: 5,
+                posting_count: 6,
+                token_count: 9,
+            }
+        );
+    }
+
     #[test]
     fn search_main_test() {
         let sentences = vec![
@@ -577,13 +1631,13 @@ mod tests {
         ];
         let term = "Taisuke".to_string();
         assert_eq!(
-            search_main(TokenizeType::Whitespace, sentences.clone(), &term),
+            search_main(TokenizeType::Whitespace, sentences.clone(), &term).unwrap(),
             vec![doc!("I am Taisuke"),]
         );
 
         let term = "that".to_string();
         assert_eq!(
-            search_main(TokenizeType::Whitespace, sentences.clone(), &term),
+            search_main(TokenizeType::Whitespace, sentences.clone(), &term).unwrap(),
             vec![doc!(
                 "that that is is that that is not is not is that it it is"
             ),]
@@ -591,7 +1645,7 @@ mod tests {
 
         let term = "foo".to_string();
         assert_eq!(
-            search_main(TokenizeType::Whitespace, sentences.clone(), &term),
+            search_main(TokenizeType::Whitespace, sentences.clone(), &term).unwrap(),
             vec![]
         );
 
@@ -603,7 +1657,7 @@ mod tests {
 
         let term = "すもも".to_string();
         assert_eq!(
-            search_main(TokenizeType::Japanese, sentences.clone(), &term),
+            search_main(TokenizeType::Japanese, sentences.clone(), &term).unwrap(),
             vec![doc!("すもももももももものうち"),]
         );
     }
@@ -0,0 +1,370 @@
+use crate::codec::*;
+use crate::doc_store::StoreReader;
+use crate::doc_values::ColumnReader;
+use crate::numeric::PointsReader;
+use crate::store::{data_file, field_file};
+use crate::{Document, FieldType, Index, PostingData, Schema};
+
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::ops::Bound;
+use std::path::Path;
+
+pub(crate) const TERMS_FILE: &str = "terms";
+pub(crate) const POSTINGS_FILE: &str = "postings";
+pub(crate) const NORMS_FILE: &str = "norms";
+pub(crate) const STORED_FILE: &str = "stored";
+pub(crate) const POINTS_FILE: &str = "points";
+pub(crate) const COLUMN_FILE: &str = "column";
+
+// a file mapped into memory, of which the OS reads only the touched pages
+struct MappedFile {
+    mmap: Mmap,
+}
+
+impl MappedFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: files of a commit are never modified once the commit is
+        // complete. `save` writes the files of a new commit, and removing the old
+        // ones keeps the mapped ones intact.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self { mmap })
+    }
+
+    fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    // bytes from `offset`
+    fn from(&self, offset: u64) -> io::Result<&[u8]> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.mmap.get(offset..))
+            .ok_or_else(|| invalid_data("offset is out of range"))
+    }
+
+    // `i`th little endian u64 of the table at `table`
+    fn u64_at(&self, table: usize, i: usize) -> io::Result<u64> {
+        let start = table + i * 8;
+        match self.mmap.get(start..start + 8) {
+            Some(bytes) => Ok(u64::from_le_bytes(bytes.try_into().unwrap())),
+            None => Err(invalid_data("table is out of range")),
+        }
+    }
+
+    // `i`th little endian u32 from the start of the file
+    fn u32_at(&self, i: usize) -> io::Result<u32> {
+        match self.mmap.get(i * 4..i * 4 + 4) {
+            Some(bytes) => Ok(u32::from_le_bytes(bytes.try_into().unwrap())),
+            None => Err(invalid_data("norm is out of range")),
+        }
+    }
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MappedFile({} bytes)", self.len())
+    }
+}
+
+// counts of the whole index, which are written in the meta file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Counts {
+    pub(crate) term_count: usize,
+    pub(crate) posting_count: usize,
+    pub(crate) token_count: usize,
+}
+
+// an index saved by `save` and mapped into memory. terms, postings and
+// documents are decoded when they are looked up, so opening it costs the same
+// regardless of its size.
+//
+// terms:    (term, doc_freq, postings offset)* in term order, u64 offset of each term
+// postings: (doc_id delta, position_count, position delta*)* of each term
+// norms:    u32 length of each norm field of each doc id
+// stored:   documents in blocks of `StoreWriter`
+// points{i}: points of `PointsReader` of the numeric or date field i of the schema
+// column{i}: values of `ColumnReader` of the numeric, date or keyword field i
+#[derive(Debug)]
+pub(crate) struct MappedIndex {
+    counts: Counts,
+
+    // number of doc ids, including deleted ones
+    doc_slots: usize,
+
+    terms: MappedFile,
+    postings: MappedFile,
+
+    // fields of the norms of a document, in the order of the norms file
+    norm_fields: Vec<String>,
+    norms: MappedFile,
+
+    stored: MappedFile,
+
+    // blocks of the stored file
+    store: StoreReader,
+
+    // numeric and date field => its points file
+    points: HashMap<String, MappedFile>,
+
+    // numeric, date and keyword field => (its column file, whether it's a keyword field)
+    columns: HashMap<String, (MappedFile, bool)>,
+}
+
+impl MappedIndex {
+    pub(crate) fn open(
+        dir: &Path,
+        generation: u64,
+        counts: Counts,
+        doc_slots: usize,
+        norm_fields: Vec<String>,
+        schema: &Schema,
+    ) -> io::Result<Self> {
+        let terms = MappedFile::open(&data_file(dir, TERMS_FILE, generation))?;
+        let postings = MappedFile::open(&data_file(dir, POSTINGS_FILE, generation))?;
+        let norms = MappedFile::open(&data_file(dir, NORMS_FILE, generation))?;
+        let stored = MappedFile::open(&data_file(dir, STORED_FILE, generation))?;
+
+        let mut points = HashMap::new();
+        let mut columns = HashMap::new();
+        for (i, field) in schema.fields().enumerate() {
+            let field_file = |name| data_file(dir, &field_file(name, i), generation);
+            let numeric = matches!(field.field_type, FieldType::Numeric | FieldType::Date);
+            if numeric {
+                let file = MappedFile::open(&field_file(POINTS_FILE))?;
+                if PointsReader::new(&file.mmap)?.len() > doc_slots {
+                    return Err(invalid_data("file size doesn't match the meta"));
+                }
+                points.insert(field.name.clone(), file);
+            }
+            let keyword = field.field_type == FieldType::Keyword;
+            if numeric || keyword {
+                let file = MappedFile::open(&field_file(COLUMN_FILE))?;
+                ColumnReader::new(&file.mmap, doc_slots, keyword)?;
+                columns.insert(field.name.clone(), (file, keyword));
+            }
+        }
+        // only the sizes are checked, and broken contents are found when read
+        let table_len = |count: usize| count.checked_mul(8);
+        if table_len(counts.term_count).is_none_or(|len| terms.len() < len)
+            || doc_slots
+                .checked_mul(norm_fields.len() * 4)
+                .is_none_or(|len| norms.len() != len)
+        {
+            return Err(invalid_data("file size doesn't match the meta"));
+        }
+        let store = StoreReader::open(&stored.mmap)?;
+        Ok(Self {
+            counts,
+            doc_slots,
+            terms,
+            postings,
+            norm_fields,
+            norms,
+            stored,
+            store,
+            points,
+            columns,
+        })
+    }
+
+    pub(crate) fn counts(&self) -> Counts {
+        self.counts
+    }
+
+    pub(crate) fn norm_fields(&self) -> &[String] {
+        &self.norm_fields
+    }
+
+    // (term, doc_freq, postings offset) of the `i`th term in term order
+    fn term_at(&self, i: usize) -> io::Result<(&[u8], usize, u64)> {
+        let table = self.terms.len() - self.counts.term_count * 8;
+        let mut r = self.terms.from(self.terms.u64_at(table, i)?)?;
+        let term = read_bytes(&mut r)?;
+        let doc_freq = read_usize(&mut r)?;
+        let offset = read_vint(&mut r)?;
+        Ok((term, doc_freq, offset))
+    }
+
+    // index of the first term not less than `term`
+    fn lower_bound(&self, term: &str) -> io::Result<usize> {
+        let (mut low, mut high) = (0, self.counts.term_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.term_at(mid)?.0 < term.as_bytes() {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    // (doc_freq, postings offset) of the term
+    fn find_term(&self, term: &str) -> io::Result<Option<(usize, u64)>> {
+        let i = self.lower_bound(term)?;
+        if i == self.counts.term_count {
+            return Ok(None);
+        }
+        let (found, doc_freq, offset) = self.term_at(i)?;
+        Ok((found == term.as_bytes()).then_some((doc_freq, offset)))
+    }
+
+    // number of documents containing the term
+    pub(crate) fn doc_freq(&self, term: &str) -> io::Result<usize> {
+        Ok(self.find_term(term)?.map_or(0, |(n, _)| n))
+    }
+
+    pub(crate) fn postings(&self, term: &str) -> io::Result<Option<Vec<PostingData>>> {
+        match self.find_term(term)? {
+            None => Ok(None),
+            Some((doc_freq, offset)) => self.read_postings(doc_freq, offset).map(Some),
+        }
+    }
+
+    fn read_postings(&self, doc_freq: usize, offset: u64) -> io::Result<Vec<PostingData>> {
+        let mut r = self.postings.from(offset)?;
+        let mut postings = Vec::with_capacity(doc_freq.min(1024));
+        let mut doc_id = 0;
+        for _ in 0..doc_freq {
+            doc_id += read_usize(&mut r)?;
+            if doc_id >= self.doc_slots {
+                return Err(invalid_data("doc id is out of range"));
+            }
+            let position_count = read_usize(&mut r)?;
+            let mut positions = Vec::with_capacity(position_count.min(1024));
+            let mut position = 0;
+            for _ in 0..position_count {
+                position += read_usize(&mut r)?;
+                positions.push(position);
+            }
+            postings.push(PostingData { doc_id, positions });
+        }
+        Ok(postings)
+    }
+
+    // terms starting with `prefix` in term order
+    pub(crate) fn terms_with_prefix(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut terms = Vec::new();
+        for i in self.lower_bound(prefix)?..self.counts.term_count {
+            let term = self.term_at(i)?.0;
+            if !term.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            terms.push(String::from_utf8_lossy(term).into_owned());
+        }
+        Ok(terms)
+    }
+
+    // number of terms of the field in the document
+    pub(crate) fn field_length(&self, doc_id: usize, field: &str) -> io::Result<usize> {
+        let i = match self.norm_fields.iter().position(|f| f == field) {
+            Some(i) if doc_id < self.doc_slots => i,
+            _ => return Ok(0),
+        };
+        let norm = self.norms.u32_at(doc_id * self.norm_fields.len() + i)?;
+        Ok(norm as usize)
+    }
+
+    pub(crate) fn doc(&self, id: usize) -> io::Result<Option<Document>> {
+        if id >= self.doc_slots {
+            return Ok(None);
+        }
+        self.store.doc(&self.stored.mmap, id)
+    }
+
+    // passes every stored document in ascending order of ids to `f`
+    pub(crate) fn for_each_doc(
+        &self,
+        f: impl FnMut(usize, Document) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.store.for_each(&self.stored.mmap, f)
+    }
+
+    // ids of documents with a value of the numeric or date field within the
+    // bounds, in ascending order
+    pub(crate) fn numeric_range(
+        &self,
+        field: &str,
+        lower: Bound<f64>,
+        upper: Bound<f64>,
+    ) -> io::Result<Vec<usize>> {
+        let ids = match self.points.get(field) {
+            None => return Ok(Vec::new()),
+            Some(points) => PointsReader::new(&points.mmap)?.range(lower, upper)?,
+        };
+        if ids.last().is_some_and(|id| *id >= self.doc_slots) {
+            return Err(invalid_data("doc id is out of range"));
+        }
+        Ok(ids)
+    }
+
+    // the points file of the numeric or date field
+    pub(crate) fn points(&self, field: &str) -> &[u8] {
+        self.points.get(field).map_or(&[], |points| &points.mmap)
+    }
+
+    // values of the numeric, date or keyword field by doc id
+    pub(crate) fn column(&self, field: &str) -> io::Result<Option<ColumnReader<'_>>> {
+        match self.columns.get(field) {
+            None => Ok(None),
+            Some((column, keyword)) => {
+                ColumnReader::new(&column.mmap, self.doc_slots, *keyword).map(Some)
+            }
+        }
+    }
+
+    // the column file of the numeric, date or keyword field
+    pub(crate) fn column_file(&self, field: &str) -> &[u8] {
+        self.columns
+            .get(field)
+            .map_or(&[], |(column, _)| &column.mmap)
+    }
+
+    // adds every term and document into an index in memory
+    pub(crate) fn load_into(&self, index: &mut Index) -> io::Result<()> {
+        for i in 0..self.counts.term_count {
+            let (term, doc_freq, offset) = self.term_at(i)?;
+            let term = String::from_utf8(term.to_vec())
+                .map_err(|_| invalid_data("string is not valid UTF-8"))?;
+            for posting in self.read_postings(doc_freq, offset)? {
+                index.push_term_freq(posting.doc_id, term.clone(), posting.positions.len());
+                index.push_posting(term.clone(), posting);
+            }
+        }
+        self.for_each_doc(|id, doc| {
+            index.store_document(id, doc);
+            Ok(())
+        })
+    }
+}
+
+// indexes mapped from the same files are the same
+impl PartialEq for MappedIndex {
+    fn eq(&self, other: &Self) -> bool {
+        self.counts == other.counts
+            && self.doc_slots == other.doc_slots
+            && self.norm_fields == other.norm_fields
+            && self.terms.mmap[..] == other.terms.mmap[..]
+            && self.postings.mmap[..] == other.postings.mmap[..]
+            && self.norms.mmap[..] == other.norms.mmap[..]
+            && self.stored.mmap[..] == other.stored.mmap[..]
+            && self.points.len() == other.points.len()
+            && self.points.iter().all(|(field, points)| {
+                other
+                    .points
+                    .get(field)
+                    .is_some_and(|other| points.mmap[..] == other.mmap[..])
+            })
+            && self.columns.len() == other.columns.len()
+            && self.columns.iter().all(|(field, (column, _))| {
+                other
+                    .columns
+                    .get(field)
+                    .is_some_and(|(other, _)| column.mmap[..] == other.mmap[..])
+            })
+    }
+}
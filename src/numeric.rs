@@ -0,0 +1,357 @@
+use crate::codec::invalid_data;
+use crate::FieldType;
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::ops::Bound;
+
+// bytes of a point in a points file
+const POINT_SIZE: usize = 16;
+
+// value of a numeric or date field. dates are seconds since 1970-01-01T00:00:00Z.
+// returns None if the text is not a valid value of the type.
+pub(crate) fn parse_value(field_type: FieldType, text: &str) -> Option<f64> {
+    match field_type {
+        FieldType::Numeric => text.trim().parse::<f64>().ok().filter(|v| v.is_finite()),
+        FieldType::Date => parse_date(text.trim()).map(|secs| secs as f64),
+        _ => None,
+    }
+}
+
+// parses "2025-01-31" or "2025-01-31T12:30:00" with an optional "Z", in UTC
+fn parse_date(text: &str) -> Option<i64> {
+    let (date, time) = match text.split_once('T') {
+        Some((date, time)) => (date, Some(time.strip_suffix('Z').unwrap_or(time))),
+        None => (text, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year = parse_digits(parts.next()?, 4)?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let secs = match time {
+        None => 0,
+        Some(time) => {
+            let mut parts = time.splitn(3, ':');
+            let hour = parse_digits(parts.next()?, 2)?;
+            let minute = parse_digits(parts.next()?, 2)?;
+            let second = parse_digits(parts.next()?, 2)?;
+            if hour > 23 || minute > 59 || second > 59 {
+                return None;
+            }
+            hour * 3600 + minute * 60 + second
+        }
+    };
+    Some(days_from_civil(year, month, day) * 86400 + secs)
+}
+
+fn parse_digits(text: &str, len: usize) -> Option<i64> {
+    if text.len() != len || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // count years from March so that the leap day is the last day of a year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = (month + 9) % 12;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// maps a value to u64 so that the order of the integers is the order of the values.
+// the sign bit is flipped for positive values, and all bits for negative ones.
+pub(crate) fn sortable_bits(value: f64) -> u64 {
+    // -0.0 and 0.0 are the same value
+    let bits = (value + 0f64).to_bits();
+    if bits >> 63 == 0 {
+        bits | (1 << 63)
+    } else {
+        !bits
+    }
+}
+
+// (value, doc id) of every document with a value in the field, ordered by value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct NumericIndex {
+    points: BTreeSet<(u64, usize)>,
+}
+
+impl NumericIndex {
+    pub(crate) fn insert(&mut self, value: f64, doc_id: usize) {
+        self.points.insert((sortable_bits(value), doc_id));
+    }
+
+    pub(crate) fn remove(&mut self, value: f64, doc_id: usize) {
+        self.points.remove(&(sortable_bits(value), doc_id));
+    }
+
+    // ids of documents with a value within the bounds, in ascending order
+    pub(crate) fn range(&self, lower: Bound<f64>, upper: Bound<f64>) -> Vec<usize> {
+        // BTreeSet::range panics if the lower bound is greater than the upper one
+        let (lower, upper) = match point_bounds(lower, upper) {
+            None => return Vec::new(),
+            Some(bounds) => bounds,
+        };
+        let to_point = |(bits, doc_id): Point| (bits, doc_id as usize);
+        let mut ids = self
+            .points
+            .range((lower.map(to_point), upper.map(to_point)))
+            .map(|(_, doc_id)| *doc_id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    // writes the points in the layout which `PointsReader` reads
+    pub(crate) fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        for (bits, doc_id) in self.points.iter() {
+            write_point(w, *bits, *doc_id)?;
+        }
+        Ok(())
+    }
+}
+
+// writes a point of a points file, which are written in order of (bits, doc id)
+pub(crate) fn write_point(w: &mut impl Write, bits: u64, doc_id: usize) -> io::Result<()> {
+    w.write_all(&bits.to_le_bytes())?;
+    w.write_all(&(doc_id as u64).to_le_bytes())
+}
+
+// (sortable bits, doc id) of a value of a document
+type Point = (u64, u64);
+
+// bounds of the points with a value within the bounds, or None if the lower
+// bound is greater than the upper one
+fn point_bounds(lower: Bound<f64>, upper: Bound<f64>) -> Option<(Bound<Point>, Bound<Point>)> {
+    let lower = match lower {
+        Bound::Included(v) => Bound::Included((sortable_bits(v), 0)),
+        Bound::Excluded(v) => Bound::Excluded((sortable_bits(v), u64::MAX)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let upper = match upper {
+        Bound::Included(v) => Bound::Included((sortable_bits(v), u64::MAX)),
+        Bound::Excluded(v) => Bound::Excluded((sortable_bits(v), 0)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    if let (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u)) =
+        (lower, upper)
+    {
+        if l > u {
+            return None;
+        }
+    }
+    Some((lower, upper))
+}
+
+// points of a field written by `NumericIndex::write_to`, which are looked up
+// without reading them all.
+//
+// points: (sortable bits, doc_id)* as little endian u64s in ascending order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PointsReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PointsReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> io::Result<Self> {
+        if !data.len().is_multiple_of(POINT_SIZE) {
+            return Err(invalid_data("points file is truncated"));
+        }
+        Ok(Self { data })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.data.len() / POINT_SIZE
+    }
+
+    fn point(&self, i: usize) -> Point {
+        let u64_at =
+            |start: usize| u64::from_le_bytes(self.data[start..start + 8].try_into().unwrap());
+        (u64_at(i * POINT_SIZE), u64_at(i * POINT_SIZE + 8))
+    }
+
+    // number of the points for which `pred` holds, which are the first ones
+    fn partition_point(&self, pred: impl Fn(Point) -> bool) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if pred(self.point(mid)) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    // ids of documents with a value within the bounds, in ascending order
+    pub(crate) fn range(&self, lower: Bound<f64>, upper: Bound<f64>) -> io::Result<Vec<usize>> {
+        let (lower, upper) = match point_bounds(lower, upper) {
+            None => return Ok(Vec::new()),
+            Some(bounds) => bounds,
+        };
+        let start = self.partition_point(|point| match lower {
+            Bound::Included(l) => point < l,
+            Bound::Excluded(l) => point <= l,
+            Bound::Unbounded => false,
+        });
+        let end = self.partition_point(|point| match upper {
+            Bound::Included(u) => point <= u,
+            Bound::Excluded(u) => point < u,
+            Bound::Unbounded => true,
+        });
+        let mut ids = (start..end)
+            .map(|i| {
+                usize::try_from(self.point(i).1).map_err(|_| invalid_data("doc id is out of range"))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        ids.sort_unstable();
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::numeric::{parse_date, parse_value, sortable_bits, NumericIndex, PointsReader};
+    use crate::FieldType;
+    use std::ops::Bound;
+
+    #[test]
+    fn parse_value_test() {
+        assert_eq!(parse_value(FieldType::Numeric, "12.5"), Some(12.5));
+        assert_eq!(parse_value(FieldType::Numeric, " -3 "), Some(-3f64));
+        assert_eq!(parse_value(FieldType::Numeric, "inf"), None);
+        assert_eq!(parse_value(FieldType::Numeric, "abc"), None);
+        assert_eq!(parse_value(FieldType::Text, "1"), None);
+
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("1970-01-02T00:00:01Z"), Some(86401));
+        assert_eq!(parse_date("2000-03-01"), Some(951868800));
+        assert_eq!(parse_date("2024-02-29T23:59:59"), Some(1709251199));
+        assert_eq!(parse_date("1969-12-31"), Some(-86400));
+        for date in [
+            "2025-02-29",
+            "2025-13-01",
+            "2025-1-01",
+            "2025-01-01T24:00:00",
+            "x",
+        ] {
+            assert_eq!(parse_date(date), None, "{}", date);
+        }
+        assert_eq!(parse_value(FieldType::Date, "1970-01-02"), Some(86400f64));
+    }
+
+    #[test]
+    fn sortable_bits_test() {
+        let values = [
+            f64::MIN,
+            -2.5,
+            -1f64,
+            -0f64,
+            0f64,
+            1e-9,
+            1f64,
+            2.5,
+            f64::MAX,
+        ];
+        for pair in values.windows(2) {
+            assert!(
+                sortable_bits(pair[0]) <= sortable_bits(pair[1]),
+                "{:?}",
+                pair
+            );
+        }
+        assert_eq!(sortable_bits(-0f64), sortable_bits(0f64));
+    }
+
+    #[test]
+    fn numeric_index_test() {
+        let mut index = NumericIndex::default();
+        index.insert(3f64, 0);
+        index.insert(-1f64, 1);
+        index.insert(3f64, 2);
+        index.insert(10f64, 3);
+
+        assert_eq!(
+            index.range(Bound::Included(3f64), Bound::Included(10f64)),
+            vec![0, 2, 3]
+        );
+        assert_eq!(
+            index.range(Bound::Excluded(3f64), Bound::Unbounded),
+            vec![3]
+        );
+        assert_eq!(
+            index.range(Bound::Unbounded, Bound::Excluded(3f64)),
+            vec![1]
+        );
+        assert_eq!(
+            index.range(Bound::Included(5f64), Bound::Included(4f64)),
+            Vec::<usize>::new()
+        );
+
+        index.remove(3f64, 0);
+        assert_eq!(
+            index.range(Bound::Unbounded, Bound::Unbounded),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn points_reader_test() {
+        let mut index = NumericIndex::default();
+        for (id, value) in [3f64, -1f64, 3f64, 10f64, 0f64, 7.5]
+            .into_iter()
+            .enumerate()
+        {
+            index.insert(value, id);
+        }
+        let mut data = Vec::new();
+        index.write_to(&mut data).unwrap();
+        let reader = PointsReader::new(&data).unwrap();
+        assert_eq!(reader.len(), 6);
+
+        // the points written are found as in memory
+        let values = [-2f64, -1f64, 0f64, 3f64, 5f64, 10f64, 11f64];
+        let mut bounds = vec![Bound::Unbounded];
+        for value in values {
+            bounds.push(Bound::Included(value));
+            bounds.push(Bound::Excluded(value));
+        }
+        for lower in bounds.iter() {
+            for upper in bounds.iter() {
+                assert_eq!(
+                    reader.range(*lower, *upper).unwrap(),
+                    index.range(*lower, *upper),
+                    "{:?} {:?}",
+                    lower,
+                    upper
+                );
+            }
+        }
+
+        assert!(PointsReader::new(&data[..20]).is_err());
+        let empty = PointsReader::new(&[]).unwrap();
+        assert_eq!(
+            empty.range(Bound::Unbounded, Bound::Unbounded).unwrap(),
+            Vec::<usize>::new()
+        );
+    }
+}
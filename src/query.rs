@@ -0,0 +1,1502 @@
+use crate::doc_values::ValueRef;
+use crate::numeric::parse_value;
+use crate::schema::{field_term, split_field_term};
+use crate::store::index_error;
+use crate::{
+    analyze, Error, FieldType, Index, PostingData, Result, Schema, Term, TokenizeType,
+    DEFAULT_FIELD,
+};
+
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+use std::io;
+use std::ops::{Bound, RangeBounds};
+
+// returned by `Scorer::doc` when the scorer has no more documents
+pub const TERMINATED: usize = usize::MAX;
+
+// matches documents of an index and scores them.
+//
+// a query is independent of any index. it creates a weight holding the
+// statistics of an index, which creates scorers iterating over the matches.
+// queries and weights can be shared by threads searching concurrently.
+pub trait Query: Debug + Send + Sync {
+    fn weight(&self, index: &Index) -> Result<Box<dyn Weight>>;
+}
+
+// a query bound to the statistics of an index, such as idf
+pub trait Weight: Send + Sync {
+    fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>>;
+}
+
+// iterates over matched documents in ascending id order. a new scorer is
+// positioned at its first match.
+pub trait Scorer {
+    // the current document, or TERMINATED if there are no more matches
+    fn doc(&self) -> usize;
+
+    // moves to the first match whose id is `target` or greater, and returns it.
+    // the scorer stays at the current document if it is already at or beyond `target`.
+    fn advance(&mut self, target: usize) -> usize;
+
+    // score of the current document
+    fn score(&mut self) -> f32;
+}
+
+impl<S: Scorer + ?Sized> Scorer for Box<S> {
+    fn doc(&self) -> usize {
+        (**self).doc()
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        (**self).advance(target)
+    }
+
+    fn score(&mut self) -> f32 {
+        (**self).score()
+    }
+}
+
+// matches no documents
+#[derive(Debug)]
+pub(crate) struct EmptyScorer;
+
+impl Scorer for EmptyScorer {
+    fn doc(&self) -> usize {
+        TERMINATED
+    }
+
+    fn advance(&mut self, _target: usize) -> usize {
+        TERMINATED
+    }
+
+    fn score(&mut self) -> f32 {
+        0f32
+    }
+}
+
+// calls `f` with every match of `query` and its score
+pub(crate) fn for_each_match(
+    query: &dyn Query,
+    index: &Index,
+    f: &mut dyn FnMut(usize, f32) -> Result<()>,
+) -> Result<()> {
+    let mut scorer = query.weight(index)?.scorer(index)?;
+    let mut doc = scorer.doc();
+    while doc != TERMINATED {
+        f(doc, scorer.score())?;
+        doc = scorer.advance(doc + 1);
+    }
+    Ok(())
+}
+
+// matches documents containing a term in a field. the score is
+// boost * field boost * tf * idf.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermQuery {
+    term: Term,
+    boost: f32,
+}
+
+impl TermQuery {
+    // `text` is searched as is, so it should be a term produced by the analyzer of the index
+    pub fn new(field: &str, text: &str) -> Self {
+        Self {
+            term: field_term(field, text),
+            boost: 1f32,
+        }
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    fn term_weight(&self, index: &Index) -> Result<TermWeight> {
+        let (field, _) = split_field_term(&self.term);
+        Ok(TermWeight {
+            term: self.term.clone(),
+            boost: self.boost * index.schema.boost(field),
+            idf: index.idf(&self.term).map_err(index_error)?,
+        })
+    }
+}
+
+impl Query for TermQuery {
+    fn weight(&self, index: &Index) -> Result<Box<dyn Weight>> {
+        Ok(Box::new(self.term_weight(index)?))
+    }
+}
+
+#[derive(Debug)]
+struct TermWeight {
+    term: Term,
+
+    // query boost * field boost
+    boost: f32,
+    idf: f32,
+}
+
+impl TermWeight {
+    // None if no document contains the term
+    fn term_scorer<'a>(&self, index: &'a Index) -> Result<Option<TermScorer<'a>>> {
+        let postings = match index.read_postings(&self.term).map_err(index_error)? {
+            None => return Ok(None),
+            Some(postings) => postings,
+        };
+        // scores are computed here, so that failures to read the norms are returned
+        let scores = postings
+            .iter()
+            .map(|posting| {
+                let tf = index.tf(posting.doc_id, &self.term, posting.positions.len())?;
+                Ok(self.boost * tf * self.idf)
+            })
+            .collect::<io::Result<_>>()
+            .map_err(index_error)?;
+        Ok(Some(TermScorer {
+            postings,
+            scores,
+            cursor: 0,
+        }))
+    }
+}
+
+impl Weight for TermWeight {
+    fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>> {
+        Ok(match self.term_scorer(index)? {
+            Some(scorer) => Box::new(scorer),
+            None => Box::new(EmptyScorer),
+        })
+    }
+}
+struct TermScorer<'a> {
+    postings: Cow<'a, [PostingData]>,
+
+    // scores of the postings
+    scores: Vec<f32>,
+    cursor: usize,
+}
+
+impl TermScorer<'_> {
+    // positions of the term in the current document
+    fn positions(&self) -> &[usize] {
+        self.postings
+            .get(self.cursor)
+            .map_or(&[], |posting| &posting.positions)
+    }
+}
+
+impl Scorer for TermScorer<'_> {
+    fn doc(&self) -> usize {
+        self.postings
+            .get(self.cursor)
+            .map_or(TERMINATED, |posting| posting.doc_id)
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        // postings are sorted by doc id
+        self.cursor += self.postings[self.cursor..].partition_point(|p| p.doc_id < target);
+        self.doc()
+    }
+
+    fn score(&mut self) -> f32 {
+        self.scores.get(self.cursor).copied().unwrap_or(0f32)
+    }
+}
+
+// moves all scorers to the first document which all of them match, starting
+// from `target`. returns TERMINATED if any of them is exhausted.
+fn align<S: Scorer>(scorers: &mut [S], mut target: usize) -> usize {
+    'align: loop {
+        for scorer in scorers.iter_mut() {
+            let doc = scorer.advance(target);
+            if doc == TERMINATED {
+                return TERMINATED;
+            }
+            if doc != target {
+                target = doc;
+                continue 'align;
+            }
+        }
+        return target;
+    }
+}
+
+// matches documents containing the terms at consecutive positions of a field.
+// the score is the sum of the scores of the terms as `TermQuery`.
+// matches nothing if there are no terms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhraseQuery {
+    terms: Vec<TermQuery>,
+}
+
+impl PhraseQuery {
+    // `texts` are searched as is, so they should be terms produced by the analyzer of the index
+    pub fn new<S: AsRef<str>>(field: &str, texts: &[S]) -> Self {
+        Self {
+            terms: texts
+                .iter()
+                .map(|text| TermQuery::new(field, text.as_ref()))
+                .collect(),
+        }
+    }
+
+    pub fn with_boost(mut self, boost: f32) -> Self {
+        for term in self.terms.iter_mut() {
+            term.boost = boost;
+        }
+        self
+    }
+}
+
+impl Query for PhraseQuery {
+    fn weight(&self, index: &Index) -> Result<Box<dyn Weight>> {
+        Ok(Box::new(PhraseWeight {
+            terms: self
+                .terms
+                .iter()
+                .map(|t| t.term_weight(index))
+                .collect::<Result<_>>()?,
+        }))
+    }
+}
+
+struct PhraseWeight {
+    terms: Vec<TermWeight>,
+}
+
+impl Weight for PhraseWeight {
+    fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>> {
+        let scorers = self
+            .terms
+            .iter()
+            .map(|term| term.term_scorer(index))
+            .collect::<Result<Option<Vec<_>>>>()?;
+        Ok(match scorers {
+            Some(scorers) if !scorers.is_empty() => Box::new(PhraseScorer::new(scorers)),
+            _ => Box::new(EmptyScorer),
+        })
+    }
+}
+
+struct PhraseScorer<'a> {
+    terms: Vec<TermScorer<'a>>,
+    doc: usize,
+}
+
+impl<'a> PhraseScorer<'a> {
+    fn new(terms: Vec<TermScorer<'a>>) -> Self {
+        let mut scorer = Self { terms, doc: 0 };
+        scorer.doc = scorer.find(0);
+        scorer
+    }
+
+    // first document from `target` where all terms appear and form the phrase
+    fn find(&mut self, mut target: usize) -> usize {
+        loop {
+            let doc = align(&mut self.terms, target);
+            if doc == TERMINATED || self.has_phrase() {
+                return doc;
+            }
+            target = doc + 1;
+        }
+    }
+
+    fn has_phrase(&self) -> bool {
+        let (first, rest) = match self.terms.split_first() {
+            None => return false,
+            Some(terms) => terms,
+        };
+        first.positions().iter().any(|start| {
+            rest.iter()
+                .enumerate()
+                .all(|(i, term)| term.positions().binary_search(&(start + i + 1)).is_ok())
+        })
+    }
+}
+
+impl Scorer for PhraseScorer<'_> {
+    fn doc(&self) -> usize {
+        self.doc
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        if self.doc < target {
+            self.doc = self.find(target);
+        }
+        self.doc
+    }
+
+    fn score(&mut self) -> f32 {
+        self.terms.iter_mut().map(|term| term.score()).sum()
+    }
+}
+
+// combines clauses of any query type. a document matches if it is matched by
+// all of the `must` clauses and none of the `must_not` clauses. without `must`
+// clauses, it also has to be matched by one of the `should` clauses.
+// the score is the sum of the scores of the matching `must` and `should` clauses.
+// matches nothing if there are no `must` or `should` clauses.
+#[derive(Debug, Default)]
+pub struct BooleanQuery {
+    must: Vec<Box<dyn Query>>,
+    should: Vec<Box<dyn Query>>,
+    must_not: Vec<Box<dyn Query>>,
+}
+
+impl BooleanQuery {
+    pub fn new(must: Vec<Box<dyn Query>>) -> Self {
+        Self {
+            must,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_must<Q: Query + 'static>(&mut self, query: Q) -> &mut Self {
+        self.must.push(Box::new(query));
+        self
+    }
+
+    pub fn add_should<Q: Query + 'static>(&mut self, query: Q) -> &mut Self {
+        self.should.push(Box::new(query));
+        self
+    }
+
+    pub fn add_must_not<Q: Query + 'static>(&mut self, query: Q) -> &mut Self {
+        self.must_not.push(Box::new(query));
+        self
+    }
+}
+
+fn weights(queries: &[Box<dyn Query>], index: &Index) -> Result<Vec<Box<dyn Weight>>> {
+    queries.iter().map(|query| query.weight(index)).collect()
+}
+
+impl Query for BooleanQuery {
+    fn weight(&self, index: &Index) -> Result<Box<dyn Weight>> {
+        Ok(Box::new(BooleanWeight {
+            must: weights(&self.must, index)?,
+            should: weights(&self.should, index)?,
+            must_not: weights(&self.must_not, index)?,
+        }))
+    }
+}
+
+struct BooleanWeight {
+    must: Vec<Box<dyn Weight>>,
+    should: Vec<Box<dyn Weight>>,
+    must_not: Vec<Box<dyn Weight>>,
+}
+
+fn scorers<'a>(weights: &[Box<dyn Weight>], index: &'a Index) -> Result<Vec<Box<dyn Scorer + 'a>>> {
+    weights.iter().map(|weight| weight.scorer(index)).collect()
+}
+
+impl Weight for BooleanWeight {
+    fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>> {
+        let scorer: Box<dyn Scorer + 'a> = match (self.must.is_empty(), self.should.is_empty()) {
+            (true, true) => return Ok(Box::new(EmptyScorer)),
+            (true, false) => Box::new(DisjunctionScorer::new(scorers(&self.should, index)?)),
+            (false, true) => Box::new(ConjunctionScorer::new(scorers(&self.must, index)?)),
+            // `should` clauses only add to the score of the required matches
+            (false, false) => Box::new(OptionalScorer {
+                required: ConjunctionScorer::new(scorers(&self.must, index)?),
+                optional: DisjunctionScorer::new(scorers(&self.should, index)?),
+            }),
+        };
+        if self.must_not.is_empty() {
+            return Ok(scorer);
+        }
+        let excluded = DisjunctionScorer::new(scorers(&self.must_not, index)?);
+        Ok(Box::new(ExclusionScorer::new(scorer, excluded)))
+    }
+}
+
+// matches documents matched by all of the scorers
+struct ConjunctionScorer<'a> {
+    scorers: Vec<Box<dyn Scorer + 'a>>,
+    doc: usize,
+}
+
+impl<'a> ConjunctionScorer<'a> {
+    fn new(mut scorers: Vec<Box<dyn Scorer + 'a>>) -> Self {
+        let doc = align(&mut scorers, 0);
+        Self { scorers, doc }
+    }
+}
+
+impl Scorer for ConjunctionScorer<'_> {
+    fn doc(&self) -> usize {
+        self.doc
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        if self.doc < target {
+            self.doc = align(&mut self.scorers, target);
+        }
+        self.doc
+    }
+
+    fn score(&mut self) -> f32 {
+        self.scorers.iter_mut().map(|scorer| scorer.score()).sum()
+    }
+}
+
+// matches documents matched by any of the scorers. the score is the sum of
+// the scorers on the current document.
+struct DisjunctionScorer<'a> {
+    scorers: Vec<Box<dyn Scorer + 'a>>,
+    doc: usize,
+}
+
+impl<'a> DisjunctionScorer<'a> {
+    fn new(scorers: Vec<Box<dyn Scorer + 'a>>) -> Self {
+        let doc = scorers
+            .iter()
+            .map(|scorer| scorer.doc())
+            .min()
+            .unwrap_or(TERMINATED);
+        Self { scorers, doc }
+    }
+}
+
+impl Scorer for DisjunctionScorer<'_> {
+    fn doc(&self) -> usize {
+        self.doc
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        if self.doc < target {
+            self.doc = self
+                .scorers
+                .iter_mut()
+                .map(|scorer| scorer.advance(target))
+                .min()
+                .unwrap_or(TERMINATED);
+        }
+        self.doc
+    }
+
+    fn score(&mut self) -> f32 {
+        let doc = self.doc;
+        self.scorers
+            .iter_mut()
+            .filter(|scorer| scorer.doc() == doc)
+            .map(|scorer| scorer.score())
+            .sum()
+    }
+}
+
+// matches documents of `required`, adding the score of `optional` if it
+// matches the document too
+struct OptionalScorer<'a> {
+    required: ConjunctionScorer<'a>,
+    optional: DisjunctionScorer<'a>,
+}
+
+impl Scorer for OptionalScorer<'_> {
+    fn doc(&self) -> usize {
+        self.required.doc()
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        self.required.advance(target)
+    }
+
+    fn score(&mut self) -> f32 {
+        let doc = self.required.doc();
+        let score = self.required.score();
+        if self.optional.advance(doc) == doc {
+            score + self.optional.score()
+        } else {
+            score
+        }
+    }
+}
+
+// matches documents of `scorer` which `excluded` doesn't match
+struct ExclusionScorer<'a> {
+    scorer: Box<dyn Scorer + 'a>,
+    excluded: DisjunctionScorer<'a>,
+    doc: usize,
+}
+
+impl<'a> ExclusionScorer<'a> {
+    fn new(scorer: Box<dyn Scorer + 'a>, excluded: DisjunctionScorer<'a>) -> Self {
+        let mut scorer = Self {
+            scorer,
+            excluded,
+            doc: 0,
+        };
+        scorer.doc = scorer.find(0);
+        scorer
+    }
+
+    // first document from `target` which isn't excluded
+    fn find(&mut self, mut target: usize) -> usize {
+        loop {
+            let doc = self.scorer.advance(target);
+            if doc == TERMINATED || self.excluded.advance(doc) != doc {
+                return doc;
+            }
+            target = doc + 1;
+        }
+    }
+}
+
+impl Scorer for ExclusionScorer<'_> {
+    fn doc(&self) -> usize {
+        self.doc
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        if self.doc < target {
+            self.doc = self.find(target);
+        }
+        self.doc
+    }
+
+    fn score(&mut self) -> f32 {
+        self.scorer.score()
+    }
+}
+
+// matches documents whose value of a numeric or date field is within the bounds.
+// it is a filter, so the score is always 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeQuery {
+    field: String,
+    lower: Bound<f64>,
+    upper: Bound<f64>,
+
+    // the query as written, such as `date:[2025-01-01 TO *]`
+    text: String,
+}
+
+impl RangeQuery {
+    // values of date fields are seconds since 1970-01-01T00:00:00Z
+    pub fn new(field: &str, lower: Bound<f64>, upper: Bound<f64>) -> Self {
+        let format = |bound: &Bound<f64>| match bound {
+            Bound::Included(v) | Bound::Excluded(v) => v.to_string(),
+            Bound::Unbounded => "*".to_string(),
+        };
+        let text = format!(
+            "{}:{}{} TO {}{}",
+            field,
+            if matches!(lower, Bound::Excluded(_)) {
+                '{'
+            } else {
+                '['
+            },
+            format(&lower),
+            format(&upper),
+            if matches!(upper, Bound::Excluded(_)) {
+                '}'
+            } else {
+                ']'
+            },
+        );
+        Self {
+            field: field.to_string(),
+            lower,
+            upper,
+            text,
+        }
+    }
+
+    // parses `price:[10 TO 20]` or `date:[2025-01-01 TO 2025-12-31]`.
+    // `[` and `]` include the bound, `{` and `}` exclude it, and `*` is unbounded.
+    pub fn parse(schema: &Schema, text: &str) -> Result<Self> {
+        let invalid = || Error::QueryParse(format!("invalid range: {}", text));
+        let (field, range) = text.split_once(':').ok_or_else(invalid)?;
+        let field_type = match schema.field_type(field) {
+            Some(field_type @ (FieldType::Numeric | FieldType::Date)) => field_type,
+            _ => {
+                return Err(Error::QueryParse(format!(
+                    "field is not numeric or date: {}",
+                    field
+                )))
+            }
+        };
+        let inclusive_lower = match range.chars().next() {
+            Some('[') => true,
+            Some('{') => false,
+            _ => return Err(invalid()),
+        };
+        let inclusive_upper = match range.chars().last() {
+            Some(']') => true,
+            Some('}') => false,
+            _ => return Err(invalid()),
+        };
+        let (lower, upper) = match range[1..range.len() - 1]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [lower, "TO", upper] => (*lower, *upper),
+            _ => return Err(invalid()),
+        };
+        let bound = |value: &str, inclusive: bool| match value {
+            "*" => Some(Bound::Unbounded),
+            _ => {
+                let value = parse_value(field_type, value)?;
+                Some(match inclusive {
+                    true => Bound::Included(value),
+                    false => Bound::Excluded(value),
+                })
+            }
+        };
+        Ok(Self {
+            field: field.to_string(),
+            lower: bound(lower, inclusive_lower).ok_or_else(invalid)?,
+            upper: bound(upper, inclusive_upper).ok_or_else(invalid)?,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    // whether the value is within the bounds
+    pub(crate) fn contains(&self, value: f64) -> bool {
+        (self.lower, self.upper).contains(&value)
+    }
+
+    // whether the document has a value within the bounds
+    pub(crate) fn matches(&self, index: &Index, doc_id: usize) -> Result<bool> {
+        match index.doc_value(&self.field, doc_id).map_err(index_error)? {
+            Some(ValueRef::Number(value)) => Ok(self.contains(value)),
+            _ => Ok(false),
+        }
+    }
+}
+
+impl fmt::Display for RangeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Query for RangeQuery {
+    fn weight(&self, _index: &Index) -> Result<Box<dyn Weight>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl Weight for RangeQuery {
+    fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>> {
+        let ids = index
+            .numeric_range(&self.field, self.lower, self.upper)
+            .map_err(index_error)?;
+        Ok(Box::new(DocIdScorer { ids, cursor: 0 }))
+    }
+}
+
+// matches the ids in ascending order with the score 0
+struct DocIdScorer {
+    ids: Vec<usize>,
+    cursor: usize,
+}
+
+impl Scorer for DocIdScorer {
+    fn doc(&self) -> usize {
+        self.ids.get(self.cursor).copied().unwrap_or(TERMINATED)
+    }
+
+    fn advance(&mut self, target: usize) -> usize {
+        self.cursor += self.ids[self.cursor..].partition_point(|id| *id < target);
+        self.doc()
+    }
+
+    fn score(&mut self) -> f32 {
+        0f32
+    }
+}
+
+// a term of a query with the boost multiplied into its score
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TermClause {
+    pub(crate) term: Term,
+
+    // query boost * field boost
+    pub(crate) boost: f32,
+
+    pub(crate) query_boost: f32,
+    pub(crate) field_boost: f32,
+}
+
+// splits `dog^3` into ("dog", Some(3.0))
+pub(crate) fn split_boost(part: &str) -> (&str, Option<f32>) {
+    match part.rsplit_once('^') {
+        Some((text, boost)) if !text.is_empty() => match boost.parse::<f32>() {
+            Ok(boost) if boost.is_finite() && boost >= 0f32 => (text, Some(boost)),
+            _ => (part, None),
+        },
+        _ => (part, None),
+    }
+}
+
+// splits `title:dog` into (Some("title"), "dog"). the field is not checked against any schema.
+pub(crate) fn split_field(part: &str) -> (Option<&str>, &str) {
+    match part.split_once(':') {
+        Some((field, text)) if is_field_name(field) && !text.is_empty() => (Some(field), text),
+        _ => (None, part),
+    }
+}
+
+fn is_field_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+// splits a query into whitespace separated parts, keeping a range such as
+// `date:[2025-01-01 TO 2025-12-31]` or a phrase such as `title:"quick fox"^2` in one part
+pub(crate) fn split_parts(sentence: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = sentence.trim_start();
+    while !rest.is_empty() {
+        // a boost of a range is a part of it
+        let end = range_len(rest)
+            .map(|len| {
+                rest[len..]
+                    .find(char::is_whitespace)
+                    .map_or(rest.len(), |n| len + n)
+            })
+            .or_else(|| phrase_len(rest))
+            .or_else(|| rest.find(char::is_whitespace))
+            .unwrap_or(rest.len());
+        parts.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    parts
+}
+
+// length of the range at the beginning of `text`, if any
+fn range_len(text: &str) -> Option<usize> {
+    let (field, range) = text.split_once(':')?;
+    if !is_field_name(field) || !range.starts_with(['[', '{']) {
+        return None;
+    }
+    let close = range.find([']', '}'])?;
+    Some(field.len() + 1 + close + 1)
+}
+
+pub(crate) fn is_range(part: &str) -> bool {
+    range_len(part) == Some(part.len())
+}
+
+// length of the phrase at the beginning of `text` with its `^boost`, if any
+fn phrase_len(text: &str) -> Option<usize> {
+    let start = match text.split_once(':') {
+        Some((field, rest)) if is_field_name(field) && rest.starts_with('"') => field.len() + 1,
+        _ if text.starts_with('"') => 0,
+        _ => return None,
+    };
+    let close = start + 1 + text[start + 1..].find('"')?;
+    let end = text[close..]
+        .find(char::is_whitespace)
+        .map_or(text.len(), |len| close + len);
+    Some(end)
+}
+
+// strips the double quotes of a phrase
+fn unquote(text: &str) -> Option<&str> {
+    text.strip_prefix('"')?.strip_suffix('"')
+}
+
+// clauses of a query, which are all required to match
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ParsedQuery {
+    pub(crate) terms: Vec<TermClause>,
+
+    // terms which must appear at consecutive positions of a field
+    pub(crate) phrases: Vec<Vec<TermClause>>,
+
+    // filters by numeric and date fields
+    pub(crate) ranges: Vec<RangeQuery>,
+}
+
+// parses `title:dog^3 body:dog cat date:[2025-01-01 TO *]` into clauses.
+//
+// a part prefixed by a text field of the schema searches the field, and other
+// parts search the default field. a keyword field matches the text as is. `^boost` multiplies the score of the part.
+// `field:` of a field which isn't in the schema is a part of the text.
+// a numeric or date field is filtered by a range, or by a value as `price:10`.
+// double-quoted terms such as `title:"quick fox"^2` are a phrase.
+pub(crate) fn parse_query(
+    tokenize_type: TokenizeType,
+    schema: &Schema,
+    sentence: &str,
+) -> Result<ParsedQuery> {
+    let mut query = ParsedQuery::default();
+    for part in split_parts(sentence) {
+        if is_range(part) {
+            query.ranges.push(RangeQuery::parse(schema, part)?);
+            continue;
+        }
+        let (text, query_boost) = split_boost(part);
+        // `^` within a phrase is a part of the text
+        let suffix = part.rsplit('"').next().unwrap_or(part);
+        if query_boost.is_none() && suffix.contains('^') {
+            return Err(Error::QueryParse(format!("invalid boost: {}", part)));
+        }
+        let (field, text) = match split_field(text) {
+            (Some(field), field_text) => match schema.field_type(field) {
+                Some(FieldType::Text | FieldType::Keyword) => (field, field_text),
+                Some(FieldType::Numeric | FieldType::Date) if query_boost.is_some() => {
+                    return Err(Error::QueryParse(format!(
+                        "boosts are not supported on numeric and date fields: {}",
+                        part
+                    )))
+                }
+                Some(FieldType::Numeric | FieldType::Date) => {
+                    let range = format!("{}:[{} TO {}]", field, field_text, field_text);
+                    let mut range = RangeQuery::parse(schema, &range)?;
+                    range.text = part.to_string();
+                    query.ranges.push(range);
+                    continue;
+                }
+                Some(_) => {
+                    return Err(Error::QueryParse(format!(
+                        "field is not indexed: {}",
+                        field
+                    )))
+                }
+                None => (DEFAULT_FIELD, text),
+            },
+            _ => (DEFAULT_FIELD, text),
+        };
+        let query_boost = query_boost.unwrap_or(1f32);
+        let field_boost = schema.boost(field);
+        let phrase = unquote(text);
+        let text = phrase.unwrap_or(text);
+        let terms = match schema.field_type(field) {
+            // a keyword is a single term as is
+            Some(FieldType::Keyword) => vec![text.to_string()],
+            _ => analyze(tokenize_type, text)?,
+        };
+        let terms = terms
+            .into_iter()
+            .map(|term| TermClause {
+                term: field_term(field, &term),
+                boost: query_boost * field_boost,
+                query_boost,
+                field_boost,
+            })
+            .collect::<Vec<_>>();
+        // a phrase of a single term is the term
+        if phrase.is_some() && terms.len() > 1 {
+            query.phrases.push(terms);
+        } else {
+            query.terms.extend(terms);
+        }
+    }
+    Ok(query)
+}
+
+// `BooleanQuery` of the clauses
+pub(crate) fn to_query(parsed: &ParsedQuery) -> BooleanQuery {
+    let mut query = BooleanQuery::default();
+    for clause in parsed.terms.iter() {
+        let (field, text) = split_field_term(&clause.term);
+        query.add_must(TermQuery::new(field, text).with_boost(clause.query_boost));
+    }
+    for phrase in parsed.phrases.iter() {
+        let (field, _) = split_field_term(&phrase[0].term);
+        let texts = phrase
+            .iter()
+            .map(|clause| split_field_term(&clause.term).1)
+            .collect::<Vec<_>>();
+        query.add_must(PhraseQuery::new(field, &texts).with_boost(phrase[0].query_boost));
+    }
+    for range in parsed.ranges.iter() {
+        query.add_must(range.clone());
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{
+        for_each_match, parse_query, split_boost, split_field, split_parts, BooleanQuery,
+        PhraseQuery, Query, RangeQuery, Scorer, TermClause, TermQuery, Weight, TERMINATED,
+    };
+    use crate::schema::field_term;
+    use crate::{
+        doc, Error, FieldType, Index, IndexWriter, IndexWriterConfig, Result, Schema, TokenizeType,
+    };
+    use std::ops::Bound;
+
+    fn matches(query: &dyn Query, index: &Index) -> Vec<(usize, f32)> {
+        let mut docs = Vec::new();
+        for_each_match(query, index, &mut |doc_id, score| {
+            docs.push((doc_id, score));
+            Ok(())
+        })
+        .unwrap();
+        docs
+    }
+
+    fn ids(query: &dyn Query, index: &Index) -> Vec<usize> {
+        matches(query, index)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn term_query_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .set_boost("title", 2f32);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        let mut doc = doc!("dog cat cat fox");
+        doc.set_field("title", "fox");
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("bird")).unwrap();
+        let index = index_writer.build();
+
+        assert_eq!(
+            matches(&TermQuery::new("body", "fox"), &index),
+            vec![
+                (1, 0.25 * index.idf(&"fox".to_string()).unwrap()),
+                (2, 1f32 / 3f32 * index.idf(&"fox".to_string()).unwrap())
+            ]
+        );
+        assert_eq!(matches(&TermQuery::new("body", "mouse"), &index), vec![]);
+
+        // boost * field boost * tf * idf
+        let idf = index.idf(&field_term("title", "fox")).unwrap();
+        assert_eq!(
+            matches(&TermQuery::new("title", "fox").with_boost(3f32), &index),
+            vec![(1, 6f32 * idf)]
+        );
+    }
+
+    #[test]
+    fn boolean_query_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("bird fox")).unwrap();
+        let index = index_writer.build();
+
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(TermQuery::new("body", "dog"))
+            .add_must(TermQuery::new("body", "fox"));
+        let ids = matches(&query, &index)
+            .into_iter()
+            .map(|(doc_id, _)| doc_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2]);
+
+        // the score is the sum of the clauses
+        let dog = matches(&TermQuery::new("body", "dog"), &index);
+        let fox = matches(&TermQuery::new("body", "fox"), &index);
+        assert_eq!(matches(&query, &index)[0].1, dog[1].1 + fox[0].1);
+
+        query.add_must(TermQuery::new("body", "mouse"));
+        assert_eq!(matches(&query, &index), vec![]);
+        assert_eq!(matches(&BooleanQuery::default(), &index), vec![]);
+    }
+
+    #[test]
+    fn boolean_should_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("bird fox")).unwrap();
+        let index = index_writer.build();
+        let dog = matches(&TermQuery::new("body", "dog"), &index);
+        let fox = matches(&TermQuery::new("body", "fox"), &index);
+
+        // without `must` clauses, any of the `should` clauses matches
+        let mut query = BooleanQuery::default();
+        query
+            .add_should(TermQuery::new("body", "monkey"))
+            .add_should(TermQuery::new("body", "fox"))
+            .add_should(TermQuery::new("body", "mouse"));
+        assert_eq!(ids(&query, &index), vec![0, 1, 2, 3]);
+        // the score is the sum of the matching clauses
+        let monkey = matches(&TermQuery::new("body", "monkey"), &index);
+        assert_eq!(matches(&query, &index)[0], monkey[0]);
+        assert_eq!(matches(&query, &index)[1], fox[0]);
+
+        // with `must` clauses, `should` clauses only add to the score
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(TermQuery::new("body", "dog"))
+            .add_should(TermQuery::new("body", "fox"));
+        assert_eq!(
+            matches(&query, &index),
+            vec![dog[0], (1, dog[1].1 + fox[0].1), (2, dog[2].1 + fox[1].1)]
+        );
+    }
+
+    #[test]
+    fn boolean_must_not_test() {
+        let mut index_writer = IndexWriter::new();
+        for body in ["dog", "dog cat", "dog fox", "fox", "dog", "cat"] {
+            index_writer.write(doc!(body)).unwrap();
+        }
+        let index = index_writer.build();
+
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(TermQuery::new("body", "dog"))
+            .add_must_not(TermQuery::new("body", "cat"))
+            .add_must_not(TermQuery::new("body", "fox"));
+        assert_eq!(ids(&query, &index), vec![0, 4]);
+        // excluded clauses don't change the score
+        let dog = matches(&TermQuery::new("body", "dog"), &index);
+        assert_eq!(matches(&query, &index), vec![dog[0], dog[3]]);
+
+        let mut scorer = query.weight(&index).unwrap().scorer(&index).unwrap();
+        assert_eq!(scorer.advance(1), 4);
+        assert_eq!(scorer.advance(5), TERMINATED);
+
+        let mut query = BooleanQuery::default();
+        query
+            .add_should(TermQuery::new("body", "cat"))
+            .add_should(TermQuery::new("body", "fox"))
+            .add_must_not(PhraseQuery::new("body", &["dog", "cat"]));
+        assert_eq!(ids(&query, &index), vec![2, 3, 5]);
+
+        // `must_not` clauses alone match nothing
+        let mut query = BooleanQuery::default();
+        query.add_must_not(TermQuery::new("body", "cat"));
+        assert_eq!(ids(&query, &index), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn scorer_advance_test() {
+        let mut index_writer = IndexWriter::new();
+        for body in ["dog", "cat", "dog fox", "dog", "fox", "dog fox"] {
+            index_writer.write(doc!(body)).unwrap();
+        }
+        let index = index_writer.build();
+
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(TermQuery::new("body", "dog"))
+            .add_must(TermQuery::new("body", "fox"));
+        let mut scorer = query.weight(&index).unwrap().scorer(&index).unwrap();
+        assert_eq!(scorer.doc(), 2);
+        assert!(scorer.score() > 0f32);
+
+        // skips documents before the target
+        assert_eq!(scorer.advance(3), 5);
+        // doesn't move backwards
+        assert_eq!(scorer.advance(1), 5);
+        assert_eq!(scorer.advance(6), TERMINATED);
+        assert_eq!(scorer.doc(), TERMINATED);
+    }
+
+    #[test]
+    fn phrase_query_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("that is is not")).unwrap();
+        index_writer.write(doc!("not is that")).unwrap();
+        index_writer.write(doc!("is that is not it")).unwrap();
+        index_writer.write(doc!("is. not")).unwrap();
+        let index = index_writer.build();
+
+        assert_eq!(
+            ids(&PhraseQuery::new("body", &["is", "not"]), &index),
+            vec![0, 2]
+        );
+        assert_eq!(
+            ids(&PhraseQuery::new("body", &["is", "that", "is"]), &index),
+            vec![2]
+        );
+        assert_eq!(
+            ids(&PhraseQuery::new("body", &["not"]), &index),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(
+            ids(&PhraseQuery::new("body", &["is", "mouse"]), &index),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            ids(&PhraseQuery::new::<&str>("body", &[]), &index),
+            Vec::<usize>::new()
+        );
+
+        // the score is the sum of the terms
+        let phrase = matches(&PhraseQuery::new("body", &["is", "not"]), &index);
+        let mut terms = BooleanQuery::default();
+        terms
+            .add_must(TermQuery::new("body", "is"))
+            .add_must(TermQuery::new("body", "not"));
+        assert_eq!(phrase[0], matches(&terms, &index)[0]);
+
+        // phrases compose with other queries
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(PhraseQuery::new("body", &["is", "not"]))
+            .add_must(TermQuery::new("body", "it"));
+        assert_eq!(ids(&query, &index), vec![2]);
+    }
+
+    // matches documents of the inner query with a constant score
+    #[derive(Debug)]
+    struct ConstScoreQuery<Q: Query>(Q, f32);
+
+    struct ConstScoreWeight(Box<dyn Weight>, f32);
+
+    struct ConstScorer<'a>(Box<dyn Scorer + 'a>, f32);
+
+    impl<Q: Query> Query for ConstScoreQuery<Q> {
+        fn weight(&self, index: &Index) -> Result<Box<dyn Weight>> {
+            Ok(Box::new(ConstScoreWeight(self.0.weight(index)?, self.1)))
+        }
+    }
+
+    impl Weight for ConstScoreWeight {
+        fn scorer<'a>(&self, index: &'a Index) -> Result<Box<dyn Scorer + 'a>> {
+            Ok(Box::new(ConstScorer(self.0.scorer(index)?, self.1)))
+        }
+    }
+
+    impl Scorer for ConstScorer<'_> {
+        fn doc(&self) -> usize {
+            self.0.doc()
+        }
+
+        fn advance(&mut self, target: usize) -> usize {
+            self.0.advance(target)
+        }
+
+        fn score(&mut self) -> f32 {
+            self.1
+        }
+    }
+
+    #[test]
+    fn custom_query_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.write(doc!("dog fox")).unwrap();
+        index_writer.write(doc!("bird")).unwrap();
+        let index = index_writer.build();
+
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(ConstScoreQuery(TermQuery::new("body", "dog"), 1f32))
+            .add_must(TermQuery::new("body", "fox"));
+        let fox = matches(&TermQuery::new("body", "fox"), &index);
+        assert_eq!(matches(&query, &index), vec![(1, 1f32 + fox[0].1)]);
+    }
+
+    #[test]
+    fn split_test() {
+        assert_eq!(split_boost("dog"), ("dog", None));
+        assert_eq!(split_boost("title:dog^3"), ("title:dog", Some(3f32)));
+        assert_eq!(split_boost("dog^0.5"), ("dog", Some(0.5)));
+        assert_eq!(split_boost("dog^"), ("dog^", None));
+        assert_eq!(split_boost("^3"), ("^3", None));
+        assert_eq!(split_boost("dog^-1"), ("dog^-1", None));
+
+        assert_eq!(split_field("dog"), (None, "dog"));
+        assert_eq!(split_field("title:dog"), (Some("title"), "dog"));
+        assert_eq!(split_field("title:"), (None, "title:"));
+        assert_eq!(split_field("空港:羽田"), (None, "空港:羽田"));
+    }
+
+    #[test]
+    fn parse_query_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("url", FieldType::Stored)
+            .set_boost("title", 2f32);
+
+        let clause = |term: &str, query_boost: f32, field_boost: f32| TermClause {
+            term: term.to_string(),
+            boost: query_boost * field_boost,
+            query_boost,
+            field_boost,
+        };
+        assert_eq!(
+            parse_query(
+                TokenizeType::Whitespace,
+                &schema,
+                "title:dog^3 body:dog cat^0.5 x:y^2"
+            )
+            .unwrap()
+            .terms,
+            vec![
+                clause(&field_term("title", "dog"), 3f32, 2f32),
+                clause("dog", 1f32, 1f32),
+                clause("cat", 0.5, 1f32),
+                // the tokenizer splits the text of unknown fields
+                clause("x", 2f32, 1f32),
+                clause("y", 2f32, 1f32),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_phrase_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("tag", FieldType::Keyword);
+
+        assert_eq!(
+            split_parts(r#"dog "quick fox" title:"a b"^2 "a^b c" "x"#),
+            vec![
+                r#"dog"#,
+                r#""quick fox""#,
+                r#"title:"a b"^2"#,
+                r#""a^b c""#,
+                r#""x"#
+            ]
+        );
+
+        let query = parse_query(
+            TokenizeType::Whitespace,
+            &schema,
+            r#""quick fox" title:"a b"^2 "dog" tag:"Rust Lang" "a^b c""#,
+        )
+        .unwrap();
+        let terms = |phrase: &[TermClause]| {
+            phrase
+                .iter()
+                .map(|clause| (clause.term.clone(), clause.query_boost))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            query.phrases.iter().map(|p| terms(p)).collect::<Vec<_>>(),
+            vec![
+                vec![("quick".to_string(), 1f32), ("fox".to_string(), 1f32)],
+                vec![
+                    (field_term("title", "a"), 2f32),
+                    (field_term("title", "b"), 2f32)
+                ],
+                vec![
+                    ("a".to_string(), 1f32),
+                    ("b".to_string(), 1f32),
+                    ("c".to_string(), 1f32)
+                ],
+            ]
+        );
+        // a phrase of a single term is the term, and a keyword is a single term
+        assert_eq!(
+            terms(&query.terms),
+            vec![
+                ("dog".to_string(), 1f32),
+                (field_term("tag", "Rust Lang"), 1f32)
+            ]
+        );
+
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("quick brown fox")).unwrap();
+        index_writer.write(doc!("the quick fox")).unwrap();
+        index_writer.write(doc!("fox quick")).unwrap();
+        let index = index_writer.build();
+        let searcher = index.searcher();
+        let query = searcher.parse_query(r#""quick fox""#).unwrap();
+        assert_eq!(
+            matches(&query, &index),
+            matches(&PhraseQuery::new("body", &["quick", "fox"]), &index)
+        );
+        assert_eq!(ids(&query, &index), vec![1]);
+        let query = searcher.parse_query(r#"fox "fox quick""#).unwrap();
+        assert_eq!(ids(&query, &index), vec![2]);
+    }
+
+    #[test]
+    fn parse_range_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("price", FieldType::Numeric)
+            .add_field("date", FieldType::Date);
+
+        assert_eq!(
+            split_parts(" dog date:[2025-01-01 TO 2025-12-31]  price:{1 TO *} x:[y"),
+            vec![
+                "dog",
+                "date:[2025-01-01 TO 2025-12-31]",
+                "price:{1 TO *}",
+                "x:[y"
+            ]
+        );
+
+        let query = parse_query(
+            TokenizeType::Whitespace,
+            &schema,
+            "dog date:[2025-01-01 TO 2025-12-31] price:{1 TO *] price:5",
+        )
+        .unwrap();
+        assert_eq!(query.terms.len(), 1);
+        assert_eq!(query.ranges.len(), 3);
+        assert_eq!(
+            (query.ranges[0].lower, query.ranges[0].upper),
+            (
+                Bound::Included(1735689600f64),
+                Bound::Included(1767139200f64)
+            )
+        );
+        assert_eq!(
+            (query.ranges[1].lower, query.ranges[1].upper),
+            (Bound::Excluded(1f64), Bound::Unbounded)
+        );
+        assert_eq!(
+            (query.ranges[2].lower, query.ranges[2].upper),
+            (Bound::Included(5f64), Bound::Included(5f64))
+        );
+        assert_eq!(
+            query.ranges[0].to_string(),
+            "date:[2025-01-01 TO 2025-12-31]"
+        );
+        assert_eq!(query.ranges[2].to_string(), "price:5");
+        assert_eq!(
+            RangeQuery::new("price", Bound::Excluded(1f64), Bound::Unbounded).to_string(),
+            "price:{1 TO *]"
+        );
+
+        for range in [
+            "price:[1 TO]",
+            "price:[1 2]",
+            "price:[a TO 2]",
+            "date:[2025-13-01 TO *]",
+            "body:[1 TO 2]",
+            "x:[1 TO 2]",
+        ] {
+            let err = RangeQuery::parse(&schema, range).unwrap_err();
+            assert!(matches!(err, Error::QueryParse(_)), "{}", range);
+        }
+    }
+
+    #[test]
+    fn range_query_test() {
+        let mut schema = Schema::new();
+        schema.add_field("price", FieldType::Numeric);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        for (body, price) in [
+            ("dog", "10"),
+            ("dog cat", "25.5"),
+            ("cat", "-3"),
+            ("dog", ""),
+        ] {
+            let mut doc = doc!(body);
+            if !price.is_empty() {
+                doc.set_field("price", price);
+            }
+            index_writer.write(doc).unwrap();
+        }
+        let mut index = index_writer.build();
+
+        let range = |lower, upper| RangeQuery::new("price", lower, upper);
+        assert_eq!(
+            matches(&range(Bound::Included(10f64), Bound::Unbounded), &index),
+            vec![(0, 0f32), (1, 0f32)]
+        );
+        assert_eq!(
+            ids(&range(Bound::Unbounded, Bound::Excluded(10f64)), &index),
+            vec![2]
+        );
+        assert_eq!(
+            ids(
+                &RangeQuery::new("body", Bound::Unbounded, Bound::Unbounded),
+                &index
+            ),
+            Vec::<usize>::new()
+        );
+
+        // a range filters other clauses without changing the score
+        let mut query = BooleanQuery::default();
+        query
+            .add_must(TermQuery::new("body", "dog"))
+            .add_must(range(Bound::Included(20f64), Bound::Unbounded));
+        let dog = matches(&TermQuery::new("body", "dog"), &index);
+        assert_eq!(matches(&query, &index), vec![dog[1]]);
+
+        index.delete_document(1).unwrap();
+        assert_eq!(ids(&query, &index), Vec::<usize>::new());
+        let mut doc = doc!("dog");
+        doc.set_field("price", "30");
+        let id = index.add_document(doc).unwrap();
+        assert_eq!(ids(&query, &index), vec![id]);
+    }
+
+    #[test]
+    fn keyword_query_test() {
+        let mut schema = Schema::new();
+        schema.add_field("tag", FieldType::Keyword);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        for (body, tag) in [("dog", "Rust-Lang"), ("dog cat", "rust"), ("cat", "")] {
+            let mut doc = doc!(body);
+            doc.set_field("tag", tag);
+            index_writer.write(doc).unwrap();
+        }
+        let index = index_writer.build();
+        let searcher = index.searcher();
+
+        // a keyword matches only as a whole, with its case
+        let parsed = parse_query(TokenizeType::Whitespace, &index.schema, "tag:Rust-Lang").unwrap();
+        assert_eq!(parsed.terms[0].term, field_term("tag", "Rust-Lang"));
+        let query = searcher.parse_query("tag:Rust-Lang").unwrap();
+        assert_eq!(ids(&query, &index), vec![0]);
+        let query = searcher.parse_query("cat tag:rust").unwrap();
+        assert_eq!(ids(&query, &index), vec![1]);
+        let query = searcher.parse_query("tag:Rust").unwrap();
+        assert_eq!(ids(&query, &index), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn parse_query_error_test() {
+        let mut schema = Schema::new();
+        schema
+            .add_field("url", FieldType::Stored)
+            .add_field("price", FieldType::Numeric);
+
+        for query in [
+            "dog^x",
+            "dog^",
+            "dog^-1",
+            "url:a",
+            "price:a",
+            "url:[1 TO 2]",
+        ] {
+            let err = parse_query(TokenizeType::Whitespace, &schema, query).unwrap_err();
+            assert!(matches!(err, Error::QueryParse(_)), "{}", query);
+        }
+
+        // numeric clauses are filters, which have no score to boost
+        for (query, part) in [
+            ("price:5^2", "price:5^2"),
+            ("price:[1 TO 2]^2 dog", "price:[1 TO 2]^2"),
+        ] {
+            let err = parse_query(TokenizeType::Whitespace, &schema, query).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "invalid query: boosts are not supported on numeric and date fields: {}",
+                    part
+                )
+            );
+        }
+        let err = parse_query(TokenizeType::Whitespace, &schema, "url:a^2").unwrap_err();
+        assert_eq!(err.to_string(), "invalid query: field is not indexed: url");
+    }
+}
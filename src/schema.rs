@@ -0,0 +1,219 @@
+use crate::Term;
+use std::str::FromStr;
+
+// the field searched when a query doesn't specify one
+pub const DEFAULT_FIELD: &str = "body";
+
+// separates the field name and the term text in a field-qualified term.
+// tokenizers never produce it, so it can't be confused with a term.
+const FIELD_SEPARATOR: char = '\u{0}';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    // tokenized, indexed and stored
+    Text,
+
+    // stored only
+    Stored,
+
+    // a number indexed for range queries, and stored
+    Numeric,
+
+    // a date such as "2025-01-31" or "2025-01-31T12:30:00Z" in UTC,
+    // indexed for range queries, and stored
+    Date,
+
+    // a value indexed as a single term as is, for exact matches, sorting
+    // and facets, and stored
+    Keyword,
+}
+
+impl FieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Stored => "stored",
+            FieldType::Numeric => "numeric",
+            FieldType::Date => "date",
+            FieldType::Keyword => "keyword",
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(FieldType::Text),
+            "stored" => Ok(FieldType::Stored),
+            "numeric" => Ok(FieldType::Numeric),
+            "date" => Ok(FieldType::Date),
+            "keyword" => Ok(FieldType::Keyword),
+            _ => Err(format!("unknown field type: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldEntry {
+    pub name: String,
+    pub field_type: FieldType,
+
+    // multiplied into the score of every term of the field
+    pub boost: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    fields: Vec<FieldEntry>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::new()
+    }
+}
+
+impl Schema {
+    // schema which has only the body field
+    pub fn new() -> Self {
+        Self {
+            fields: vec![FieldEntry {
+                name: DEFAULT_FIELD.to_string(),
+                field_type: FieldType::Text,
+                boost: 1f32,
+            }],
+        }
+    }
+
+    // adds a field, or replaces the type if the field already exists
+    pub fn add_field(&mut self, name: &str, field_type: FieldType) -> &mut Self {
+        match self.fields.iter_mut().find(|f| f.name == name) {
+            Some(entry) => entry.field_type = field_type,
+            None => self.fields.push(FieldEntry {
+                name: name.to_string(),
+                field_type,
+                boost: 1f32,
+            }),
+        }
+        self
+    }
+
+    // sets the default boost of a field. does nothing if the field doesn't exist.
+    pub fn set_boost(&mut self, name: &str, boost: f32) -> &mut Self {
+        if let Some(entry) = self.fields.iter_mut().find(|f| f.name == name) {
+            entry.boost = boost;
+        }
+        self
+    }
+
+    // default boost of a field, which is 1 unless set otherwise
+    pub fn boost(&self, name: &str) -> f32 {
+        self.fields
+            .iter()
+            .find(|f| f.name == name)
+            .map_or(1f32, |f| f.boost)
+    }
+
+    pub fn field_type(&self, name: &str) -> Option<FieldType> {
+        self.fields
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.field_type)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &FieldEntry> {
+        self.fields.iter()
+    }
+
+    // parses "name:type" such as "title:text", optionally followed by "^boost"
+    pub fn parse_field(spec: &str) -> Result<FieldEntry, String> {
+        let (name, field_type) = match spec.split_once(':') {
+            Some((name, field_type)) if !name.is_empty() => (name, field_type),
+            _ => return Err(format!("invalid field: {}", spec)),
+        };
+        let (field_type, boost) = match field_type.split_once('^') {
+            Some((field_type, boost)) => match boost.parse::<f32>() {
+                Ok(boost) if boost.is_finite() && boost >= 0f32 => (field_type, boost),
+                _ => return Err(format!("invalid boost: {}", spec)),
+            },
+            None => (field_type, 1f32),
+        };
+        Ok(FieldEntry {
+            name: name.to_string(),
+            field_type: field_type.parse()?,
+            boost,
+        })
+    }
+}
+
+// terms of the default field are kept as is, so that an index without
+// other fields looks the same as before fields were introduced.
+pub(crate) fn field_term(field: &str, text: &str) -> Term {
+    if field == DEFAULT_FIELD {
+        return text.to_string();
+    }
+    format!("{}{}{}", field, FIELD_SEPARATOR, text)
+}
+
+// (field, text) of a term made by `field_term`
+pub(crate) fn split_field_term(term: &str) -> (&str, &str) {
+    match term.split_once(FIELD_SEPARATOR) {
+        Some((field, text)) => (field, text),
+        None => (DEFAULT_FIELD, term),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{field_term, split_field_term, FieldEntry, FieldType, Schema};
+
+    #[test]
+    fn schema_test() {
+        let mut schema = Schema::new();
+        assert_eq!(schema.field_type("body"), Some(FieldType::Text));
+        assert_eq!(schema.field_type("title"), None);
+
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("url", FieldType::Stored)
+            .add_field("body", FieldType::Stored);
+        assert_eq!(schema.field_type("title"), Some(FieldType::Text));
+        assert_eq!(schema.field_type("url"), Some(FieldType::Stored));
+        assert_eq!(schema.field_type("body"), Some(FieldType::Stored));
+        assert_eq!(
+            schema.fields().map(|f| f.name.as_str()).collect::<Vec<_>>(),
+            vec!["body", "title", "url"]
+        );
+
+        assert_eq!(schema.boost("title"), 1f32);
+        schema.set_boost("title", 2f32).set_boost("unknown", 3f32);
+        assert_eq!(schema.boost("title"), 2f32);
+        assert_eq!(schema.boost("unknown"), 1f32);
+
+        assert_eq!(
+            Schema::parse_field("title:text"),
+            Ok(FieldEntry {
+                name: "title".to_string(),
+                field_type: FieldType::Text,
+                boost: 1f32,
+            })
+        );
+        assert_eq!(Schema::parse_field("title:text^2.5").unwrap().boost, 2.5);
+        assert!(Schema::parse_field("title:text^x").is_err());
+        assert!(Schema::parse_field("title").is_err());
+        assert!(Schema::parse_field(":text").is_err());
+        assert!(Schema::parse_field("title:integer").is_err());
+    }
+
+    #[test]
+    fn field_term_test() {
+        assert_eq!(field_term("body", "dog"), "dog");
+        assert_eq!(split_field_term("dog"), ("body", "dog"));
+
+        let term = field_term("title", "dog");
+        assert_ne!(term, "dog");
+        assert_eq!(split_field_term(&term), ("title", "dog"));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::query::{for_each_match, parse_query, to_query};
+use crate::{BooleanQuery, Collector, Document, Index, Query, Result};
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+// searches an index. searchers are cheap to create and don't modify the index.
+// a searcher is Send and Sync, so threads can share one and search concurrently.
+#[derive(Debug, Clone, Copy)]
+pub struct Searcher<'a> {
+    index: &'a Index,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(index: &'a Index) -> Self {
+        Self { index }
+    }
+
+    pub fn index(&self) -> &'a Index {
+        self.index
+    }
+
+    // parses `title:dog^3 cat` into a query matching documents containing all of the terms.
+    // `field:` searches a text field instead of the default field, and `^boost`
+    // multiplies the score of the part.
+    pub fn parse_query(&self, sentence: &str) -> Result<BooleanQuery> {
+        Ok(to_query(&parse_query(
+            self.index.tokenize_type,
+            &self.index.schema,
+            sentence,
+        )?))
+    }
+
+    // passes every document matched by `query` to `collector`, and returns its output
+    pub fn search<C: Collector>(&self, query: &dyn Query, mut collector: C) -> Result<C::Output> {
+        for_each_match(query, self.index, &mut |doc_id, score| {
+            collector.collect(doc_id, score)
+        })?;
+        Ok(collector.finish())
+    }
+
+    pub fn doc(&self, id: usize) -> Result<Option<Cow<'a, Document>>> {
+        self.index.doc(id)
+    }
+}
+
+// an immutable index shared by threads. clones are cheap and refer to the same
+// index, which every thread searches without any locking.
+#[derive(Debug, Clone)]
+pub struct IndexReader {
+    index: Arc<Index>,
+}
+
+impl IndexReader {
+    pub fn new(index: Index) -> Self {
+        Self {
+            index: Arc::new(index),
+        }
+    }
+
+    pub fn searcher(&self) -> Searcher<'_> {
+        Searcher::new(&self.index)
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+}
+
+impl From<Index> for IndexReader {
+    fn from(index: Index) -> Self {
+        IndexReader::new(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::searcher::IndexReader;
+    use crate::{doc, BooleanQuery, Index, IndexWriter, Query, Searcher, TermQuery, TopDocs};
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn searcher_test() {
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        index_writer.write(doc!("dog cat cat fox")).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("bird fox")).unwrap();
+        let index = index_writer.build();
+        let searcher = index.searcher();
+
+        let query = searcher.parse_query("dog bird").unwrap();
+        let result = searcher.search(&query, TopDocs::new(10)).unwrap();
+        assert_eq!(result.total_hits, 1);
+        assert_eq!(
+            searcher.doc(result.docs[0].doc_id).unwrap().as_deref(),
+            Some(&doc!("dog dog dog monkey bird"))
+        );
+
+        let result = searcher
+            .search(&TermQuery::new("body", "bird"), TopDocs::new(1))
+            .unwrap();
+        assert_eq!(result.total_hits, 2);
+        assert_eq!(
+            searcher.doc(result.docs[0].doc_id).unwrap().as_deref(),
+            Some(&doc!("bird fox"))
+        );
+    }
+
+    #[test]
+    fn send_sync_test() {
+        assert_send_sync::<Index>();
+        assert_send_sync::<Searcher<'static>>();
+        assert_send_sync::<IndexReader>();
+        assert_send_sync::<BooleanQuery>();
+        assert_send_sync::<Box<dyn Query>>();
+    }
+
+    #[test]
+    fn concurrent_search_test() {
+        let words = ["dog", "cat", "fox", "bird", "monkey", "raccoon", "owl"];
+        let mut index_writer = IndexWriter::new();
+        for i in 0..500usize {
+            let body = (0..1 + i % 5)
+                .map(|j| words[(i * 7 + j * 3) % words.len()])
+                .collect::<Vec<_>>()
+                .join(" ");
+            index_writer.write(doc!(body)).unwrap();
+        }
+        let reader = IndexReader::new(index_writer.build());
+        let queries = [
+            "dog",
+            "cat fox",
+            "bird owl",
+            "monkey",
+            "raccoon dog cat",
+            "x",
+        ];
+        let expected = queries
+            .iter()
+            .map(|query| {
+                let searcher = reader.searcher();
+                let query = searcher.parse_query(query).unwrap();
+                searcher.search(&query, TopDocs::new(10)).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // threads own clones of the reader
+        let handles = (0..8)
+            .map(|t| {
+                let reader = reader.clone();
+                thread::spawn(move || {
+                    let searcher = reader.searcher();
+                    (0..200)
+                        .map(|i| {
+                            let n = (t + i) % queries.len();
+                            let query = searcher.parse_query(queries[n]).unwrap();
+                            (n, searcher.search(&query, TopDocs::new(10)).unwrap())
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            for (n, result) in handle.join().unwrap() {
+                assert_eq!(result, expected[n]);
+            }
+        }
+
+        // threads share one searcher and one query
+        let searcher = reader.searcher();
+        let query = searcher.parse_query("cat fox").unwrap();
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        let result = searcher.search(&query, TopDocs::new(10)).unwrap();
+                        assert_eq!(result, expected[1]);
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,846 @@
+use crate::codec::*;
+use crate::doc_values::{doc_values, write_column, DocValue};
+use crate::mapped::{COLUMN_FILE, POINTS_FILE};
+use crate::numeric::{sortable_bits, write_point};
+use crate::schema::split_field_term;
+use crate::store::CommitWriter;
+use crate::{Document, FieldType, Index, PostingData, Schema, Term, TermDict, TokenizeType};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// analyzed documents in memory, which are merged into the index at commit
+#[derive(Debug)]
+pub(crate) struct Segment {
+    term_dict: TermDict,
+
+    // (doc_id, dict_index, positions)
+    term_positions: Vec<(usize, usize, Vec<usize>)>,
+
+    // (doc_id, Document)
+    stored: Vec<(usize, Document)>,
+
+    // approximate heap bytes of the above
+    ram_bytes: usize,
+}
+
+impl Segment {
+    pub(crate) fn new() -> Self {
+        Self {
+            term_dict: TermDict::new(),
+            term_positions: Vec::new(),
+            stored: Vec::new(),
+            ram_bytes: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, id: usize, doc: Document, terms: HashMap<Term, Vec<usize>>) {
+        for (term, positions) in terms {
+            if self.term_dict.index(&term).is_none() {
+                // the term is kept by both maps of the dictionary
+                self.ram_bytes += 2 * (term.len() + size_of::<(usize, Term)>());
+            }
+            self.ram_bytes +=
+                size_of::<(usize, usize, Vec<usize>)>() + positions.len() * size_of::<usize>();
+            let index = self.term_dict.add_term(term);
+            self.term_positions.push((id, index, positions));
+        }
+        self.ram_bytes += size_of::<(usize, Document)>()
+            + doc.body.len()
+            + doc
+                .fields
+                .iter()
+                .map(|(name, value)| name.len() + value.len() + 2 * size_of::<String>())
+                .sum::<usize>();
+        self.stored.push((id, doc));
+    }
+
+    pub(crate) fn merge_into(self, index: &mut Index) {
+        let terms = self.term_dict.into_terms();
+        for (doc_id, idx, positions) in self.term_positions {
+            let term = &terms[idx];
+            index.push_term_freq(doc_id, term.clone(), positions.len());
+            index.push_posting(term.clone(), PostingData { doc_id, positions });
+        }
+
+        for (id, doc) in self.stored {
+            index.store_document(id, doc);
+        }
+    }
+
+    // writes the segment into files of `path` followed by the extensions of
+    // SEGMENT_FILES, which are sorted so that segments are merged into a commit
+    // by reading each of them once.
+    //
+    // .postings: term_count, (term, posting_count, (doc_id, position_count, position delta*)*)*
+    // .norms:    doc_count, (doc_id, field_count, (field, length)*)*
+    // .docs:     doc_count, (doc_id, body, field_count, (name, value)*)*
+    // .points:   for each numeric or date field, point_count, (sortable bits, doc_id)*
+    // .columns:  for each numeric, date or keyword field, value_count, (doc_id, value)*
+    //
+    // terms are in term order, points in order of values, and the others in
+    // order of doc ids.
+    fn write_files(&self, path: &Path, schema: &Schema) -> io::Result<()> {
+        let file = |ext: &str| -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::new(File::create(path.with_extension(ext))?))
+        };
+
+        let mut w = file(POSTINGS_EXT)?;
+        let mut entries = vec![Vec::new(); self.term_dict.len];
+        for (doc_id, idx, positions) in self.term_positions.iter() {
+            entries[*idx].push((*doc_id, positions));
+        }
+        let mut terms = self.term_dict.iter().collect::<Vec<_>>();
+        let mut term_by_idx = vec![""; terms.len()];
+        for (term, idx) in terms.iter() {
+            term_by_idx[*idx] = term.as_str();
+        }
+        terms.sort_unstable();
+        write_usize(&mut w, terms.len())?;
+        for (term, idx) in terms {
+            write_str(&mut w, term)?;
+            write_usize(&mut w, entries[idx].len())?;
+            for (doc_id, positions) in entries[idx].iter() {
+                write_usize(&mut w, *doc_id)?;
+                write_usize(&mut w, positions.len())?;
+                let mut prev_position = 0;
+                for position in positions.iter() {
+                    write_usize(&mut w, position - prev_position)?;
+                    prev_position = *position;
+                }
+            }
+        }
+        w.flush()?;
+
+        // terms of a document are added together
+        let mut norms: Vec<(usize, BTreeMap<&str, usize>)> = Vec::new();
+        for (doc_id, idx, positions) in self.term_positions.iter() {
+            if norms.last().is_none_or(|(last, _)| last != doc_id) {
+                norms.push((*doc_id, BTreeMap::new()));
+            }
+            let (field, _) = split_field_term(term_by_idx[*idx]);
+            *norms.last_mut().unwrap().1.entry(field).or_default() += positions.len();
+        }
+        let mut w = file(NORMS_EXT)?;
+        write_usize(&mut w, norms.len())?;
+        for (doc_id, lengths) in norms {
+            write_usize(&mut w, doc_id)?;
+            write_usize(&mut w, lengths.len())?;
+            for (field, length) in lengths {
+                write_str(&mut w, field)?;
+                write_usize(&mut w, length)?;
+            }
+        }
+        w.flush()?;
+
+        let mut w = file(DOCS_EXT)?;
+        write_usize(&mut w, self.stored.len())?;
+        for (id, doc) in self.stored.iter() {
+            write_usize(&mut w, *id)?;
+            write_doc(&mut w, doc)?;
+        }
+        w.flush()?;
+
+        let mut values: HashMap<String, Vec<(usize, DocValue)>> = HashMap::new();
+        for (id, doc) in self.stored.iter() {
+            for (field, value) in doc_values(schema, doc) {
+                values.entry(field).or_default().push((*id, value));
+            }
+        }
+        let mut points = file(POINTS_EXT)?;
+        let mut columns = file(COLUMNS_EXT)?;
+        for field in schema.fields() {
+            let values = values.get(&field.name).map_or(&[][..], |values| values);
+            if matches!(field.field_type, FieldType::Numeric | FieldType::Date) {
+                let mut field_points = values
+                    .iter()
+                    .filter_map(|(id, value)| match value {
+                        DocValue::Number(value) => Some((sortable_bits(*value), *id)),
+                        DocValue::Keyword(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                field_points.sort_unstable();
+                write_usize(&mut points, field_points.len())?;
+                for (bits, id) in field_points {
+                    write_vint(&mut points, bits)?;
+                    write_usize(&mut points, id)?;
+                }
+            }
+            if sortable(field.field_type) {
+                write_usize(&mut columns, values.len())?;
+                for (id, value) in values {
+                    write_usize(&mut columns, *id)?;
+                    write_value(&mut columns, value)?;
+                }
+            }
+        }
+        points.flush()?;
+        columns.flush()
+    }
+
+    // merges the postings and documents of a segment written by `write_files`
+    // into the index
+    fn merge_files(path: &Path, index: &mut Index) -> io::Result<()> {
+        let mut terms = TermReader::open(path)?;
+        while let Some(term) = terms.next_term()? {
+            while let Some(posting) = terms.postings.take()? {
+                index.push_term_freq(posting.doc_id, term.clone(), posting.positions.len());
+                index.push_posting(term.clone(), posting);
+            }
+        }
+
+        let mut docs = Entries::open(&path.with_extension(DOCS_EXT), read_doc_entry)?;
+        docs.next_section()?;
+        while let Some((id, doc)) = docs.take()? {
+            index.store_document(id, doc);
+        }
+        Ok(())
+    }
+}
+
+// extensions of the files of a flushed segment
+const POSTINGS_EXT: &str = "postings";
+const NORMS_EXT: &str = "norms";
+const DOCS_EXT: &str = "docs";
+const POINTS_EXT: &str = "points";
+const COLUMNS_EXT: &str = "columns";
+const SEGMENT_FILES: [&str; 5] = [POSTINGS_EXT, NORMS_EXT, DOCS_EXT, POINTS_EXT, COLUMNS_EXT];
+
+// fields of which the values are saved in a column file
+fn sortable(field_type: FieldType) -> bool {
+    matches!(
+        field_type,
+        FieldType::Numeric | FieldType::Date | FieldType::Keyword
+    )
+}
+
+fn write_doc(w: &mut impl Write, doc: &Document) -> io::Result<()> {
+    write_str(w, &doc.body)?;
+    write_usize(w, doc.fields.len())?;
+    for (name, value) in doc.fields.iter() {
+        write_str(w, name)?;
+        write_str(w, value)?;
+    }
+    Ok(())
+}
+
+fn read_doc_entry(r: &mut BufReader<File>) -> io::Result<(usize, Document)> {
+    let id = read_usize(r)?;
+    let mut doc = Document::new(read_string(r)?);
+    for _ in 0..read_usize(r)? {
+        let name = read_string(r)?;
+        let value = read_string(r)?;
+        doc.set_field(&name, value);
+    }
+    Ok((id, doc))
+}
+
+fn read_posting(r: &mut BufReader<File>) -> io::Result<PostingData> {
+    let doc_id = read_usize(r)?;
+    let position_count = read_usize(r)?;
+    let mut positions = Vec::with_capacity(position_count.min(1024));
+    let mut position = 0;
+    for _ in 0..position_count {
+        position += read_usize(r)?;
+        positions.push(position);
+    }
+    Ok(PostingData { doc_id, positions })
+}
+
+fn read_norms(r: &mut BufReader<File>) -> io::Result<(usize, Vec<(String, usize)>)> {
+    let doc_id = read_usize(r)?;
+    let field_count = read_usize(r)?;
+    let mut lengths = Vec::with_capacity(field_count.min(1024));
+    for _ in 0..field_count {
+        lengths.push((read_string(r)?, read_usize(r)?));
+    }
+    Ok((doc_id, lengths))
+}
+
+fn read_point(r: &mut BufReader<File>) -> io::Result<(u64, usize)> {
+    Ok((read_vint(r)?, read_usize(r)?))
+}
+
+// a value of a column: 0 and the bits of a number, or 1 and a keyword
+fn write_value(w: &mut impl Write, value: &DocValue) -> io::Result<()> {
+    match value {
+        DocValue::Number(value) => {
+            write_usize(w, 0)?;
+            write_vint(w, value.to_bits())
+        }
+        DocValue::Keyword(value) => {
+            write_usize(w, 1)?;
+            write_str(w, value)
+        }
+    }
+}
+
+fn read_value(r: &mut BufReader<File>) -> io::Result<(usize, DocValue)> {
+    let doc_id = read_usize(r)?;
+    let value = match read_usize(r)? {
+        0 => DocValue::Number(f64::from_bits(read_vint(r)?)),
+        1 => DocValue::Keyword(read_string(r)?),
+        _ => return Err(invalid_data("unknown type of value")),
+    };
+    Ok((doc_id, value))
+}
+
+// entries of a segment file, which are read one at a time. the file is a
+// sequence of sections, each of which is a count of entries followed by them.
+struct Entries<T> {
+    r: BufReader<File>,
+    read: fn(&mut BufReader<File>) -> io::Result<T>,
+
+    // offset of the current section
+    start: u64,
+    // entries of the section left after `head`
+    left: usize,
+    head: Option<T>,
+}
+
+impl<T> Entries<T> {
+    fn open(path: &Path, read: fn(&mut BufReader<File>) -> io::Result<T>) -> io::Result<Self> {
+        Ok(Self {
+            r: BufReader::new(File::open(path)?),
+            read,
+            start: 0,
+            left: 0,
+            head: None,
+        })
+    }
+
+    // starts the next section after the entries of the current one are taken
+    fn next_section(&mut self) -> io::Result<()> {
+        self.start = self.r.stream_position()?;
+        self.left = read_usize(&mut self.r)?;
+        self.advance()
+    }
+
+    // starts the current section again
+    fn rewind_section(&mut self) -> io::Result<()> {
+        self.r.seek(SeekFrom::Start(self.start))?;
+        self.next_section()
+    }
+
+    fn advance(&mut self) -> io::Result<()> {
+        self.head = match self.left {
+            0 => None,
+            _ => {
+                self.left -= 1;
+                Some((self.read)(&mut self.r)?)
+            }
+        };
+        Ok(())
+    }
+
+    // the next entry of the section, or None at its end
+    fn take(&mut self) -> io::Result<Option<T>> {
+        let head = self.head.take();
+        if head.is_some() {
+            self.advance()?;
+        }
+        Ok(head)
+    }
+}
+
+// entries of the files of the segments with the extension
+fn open_entries<T>(
+    paths: &[&PathBuf],
+    ext: &str,
+    read: fn(&mut BufReader<File>) -> io::Result<T>,
+) -> io::Result<Vec<Entries<T>>> {
+    paths
+        .iter()
+        .map(|path| Entries::open(&path.with_extension(ext), read))
+        .collect()
+}
+
+// the segment whose next entry comes first by `key`
+fn first<'a, T: 'a, K: Ord>(
+    heads: impl Iterator<Item = Option<&'a T>>,
+    key: impl Fn(&T) -> K,
+) -> Option<usize> {
+    heads
+        .enumerate()
+        .filter_map(|(i, head)| Some((key(head?), i)))
+        .min()
+        .map(|(_, i)| i)
+}
+
+fn heads<T>(entries: &[Entries<T>]) -> impl Iterator<Item = Option<&T>> {
+    entries.iter().map(|entries| entries.head.as_ref())
+}
+
+// terms of the postings file of a segment, of which the postings are
+// sections of the file
+struct TermReader {
+    postings: Entries<PostingData>,
+    terms_left: usize,
+    term: Option<Term>,
+}
+
+impl TermReader {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut postings = Entries::open(&path.with_extension(POSTINGS_EXT), read_posting)?;
+        let terms_left = read_usize(&mut postings.r)?;
+        Ok(Self {
+            postings,
+            terms_left,
+            term: None,
+        })
+    }
+
+    // starts the next term after the postings of the current one are taken
+    fn next_term(&mut self) -> io::Result<Option<Term>> {
+        self.term = match self.terms_left {
+            0 => None,
+            _ => {
+                self.terms_left -= 1;
+                let term = read_string(&mut self.postings.r)?;
+                self.postings.next_section()?;
+                Some(term)
+            }
+        };
+        Ok(self.term.clone())
+    }
+}
+
+// writes the documents of the segments into a commit in `dir` without
+// reading them into memory at once, which are the same files as those of the
+// index which the segments are merged into. documents of `deleted` are left
+// out. every document of the writers is on disk.
+pub(crate) fn save_segments(
+    writers: &[SegmentWriter],
+    deleted: &BTreeSet<usize>,
+    next_doc_id: usize,
+    tokenize_type: TokenizeType,
+    schema: &Schema,
+    dir: &Path,
+) -> io::Result<()> {
+    let paths = writers
+        .iter()
+        .flat_map(|writer| writer.flushed.iter())
+        .collect::<Vec<_>>();
+
+    // fields with terms of live documents, of which the lengths are saved
+    let mut norm_fields = BTreeSet::new();
+    for mut norms in open_entries(&paths, NORMS_EXT, read_norms)? {
+        norms.next_section()?;
+        while let Some((doc_id, lengths)) = norms.take()? {
+            if !deleted.contains(&doc_id) {
+                norm_fields.extend(lengths.into_iter().map(|(field, _)| field));
+            }
+        }
+    }
+    let norm_fields = norm_fields.into_iter().collect::<Vec<_>>();
+    let mut commit = CommitWriter::create(dir, norm_fields.clone())?;
+
+    let mut terms = paths
+        .iter()
+        .map(|path| TermReader::open(path))
+        .collect::<io::Result<Vec<_>>>()?;
+    for terms in terms.iter_mut() {
+        terms.next_term()?;
+    }
+    while let Some(term) = terms.iter().filter_map(|terms| terms.term.as_ref()).min() {
+        let term = term.clone();
+        // postings of the term in the segments which have it
+        let postings = |terms: &[TermReader]| -> Option<usize> {
+            let heads = terms
+                .iter()
+                .map(|terms| match terms.term.as_ref() == Some(&term) {
+                    true => terms.postings.head.as_ref(),
+                    false => None,
+                });
+            first(heads, |posting| posting.doc_id)
+        };
+        while let Some(i) = postings(&terms) {
+            let posting = terms[i].postings.take()?.unwrap();
+            if !deleted.contains(&posting.doc_id) {
+                commit.add_posting(&posting)?;
+            }
+        }
+        commit.finish_term(&term)?;
+        for terms in terms.iter_mut() {
+            if terms.term.as_ref() == Some(&term) {
+                terms.next_term()?;
+            }
+        }
+    }
+
+    let mut norms = open_entries(&paths, NORMS_EXT, read_norms)?;
+    for norms in norms.iter_mut() {
+        norms.next_section()?;
+    }
+    while let Some(i) = first(heads(&norms), |(doc_id, _)| *doc_id) {
+        let (doc_id, lengths) = norms[i].take()?.unwrap();
+        if deleted.contains(&doc_id) {
+            continue;
+        }
+        let lengths = norm_fields
+            .iter()
+            .map(|field| {
+                lengths
+                    .iter()
+                    .find(|(name, _)| name == field)
+                    .map_or(0, |(_, length)| *length)
+            })
+            .collect::<Vec<_>>();
+        commit.add_norms(doc_id, &lengths)?;
+    }
+
+    let mut doc_count = 0;
+    let mut docs = open_entries(&paths, DOCS_EXT, read_doc_entry)?;
+    for docs in docs.iter_mut() {
+        docs.next_section()?;
+    }
+    while let Some(i) = first(heads(&docs), |(id, _)| *id) {
+        let (id, doc) = docs[i].take()?.unwrap();
+        if !deleted.contains(&id) {
+            commit.add_doc(id, &doc)?;
+            doc_count += 1;
+        }
+    }
+
+    let mut points = open_entries(&paths, POINTS_EXT, read_point)?;
+    let mut columns = open_entries(&paths, COLUMNS_EXT, read_value)?;
+    for (i, field) in schema.fields().enumerate() {
+        let keyword = field.field_type == FieldType::Keyword;
+        if !keyword && sortable(field.field_type) {
+            let mut w = commit.field_file(POINTS_FILE, i)?;
+            for points in points.iter_mut() {
+                points.next_section()?;
+            }
+            while let Some(s) = first(heads(&points), |point| *point) {
+                let (bits, doc_id) = points[s].take()?.unwrap();
+                if !deleted.contains(&doc_id) {
+                    write_point(&mut w, bits, doc_id)?;
+                }
+            }
+            w.finish()?;
+        }
+        if sortable(field.field_type) {
+            let mut w = commit.field_file(COLUMN_FILE, i)?;
+            for columns in columns.iter_mut() {
+                columns.next_section()?;
+            }
+            // values of keyword fields are read twice
+            write_column(&mut w, next_doc_id, keyword, |f| {
+                for columns in columns.iter_mut() {
+                    columns.rewind_section()?;
+                }
+                while let Some(s) = first(heads(&columns), |(doc_id, _)| *doc_id) {
+                    let (doc_id, value) = columns[s].take()?.unwrap();
+                    if !deleted.contains(&doc_id) {
+                        f(doc_id, value.as_ref())?;
+                    }
+                }
+                Ok(())
+            })?;
+            w.finish()?;
+        }
+    }
+    commit.finish(tokenize_type, doc_count, next_doc_id, schema)
+}
+// directory of flushed segments, which is created at the first flush and
+// removed with the last reference to it
+#[derive(Debug)]
+pub(crate) struct SpillDir {
+    path: PathBuf,
+    next_segment: AtomicUsize,
+}
+
+impl SpillDir {
+    // a directory unique to the writer under `parent`
+    pub(crate) fn new(parent: &Path) -> Self {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let dir = format!(
+            "imser-segments-{}-{}",
+            process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        );
+        Self {
+            path: parent.join(dir),
+            next_segment: AtomicUsize::new(0),
+        }
+    }
+
+    fn segment_path(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.path)?;
+        let n = self.next_segment.fetch_add(1, Ordering::Relaxed);
+        Ok(self.path.join(format!("segment-{}", n)))
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// memory and disk usage of a writer, shared by its threads
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    pub(crate) ram_bytes: AtomicUsize,
+    pub(crate) buffered_docs: AtomicUsize,
+    pub(crate) flushed_segments: AtomicUsize,
+    pub(crate) flushed_docs: AtomicUsize,
+}
+
+// segments of a thread: the flushed ones on disk and the one in memory.
+// the segment in memory is flushed when it exceeds the budget.
+#[derive(Debug)]
+pub(crate) struct SegmentWriter {
+    buffer: Segment,
+    flushed: Vec<PathBuf>,
+
+    // bytes of the segment in memory, or 0 for no limit
+    budget: usize,
+
+    spill_dir: Arc<SpillDir>,
+    usage: Arc<Usage>,
+
+    // the first failure to flush, after which documents are kept in memory
+    error: Option<io::Error>,
+
+    schema: Schema,
+}
+
+impl SegmentWriter {
+    pub(crate) fn new(
+        budget: usize,
+        spill_dir: Arc<SpillDir>,
+        usage: Arc<Usage>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            buffer: Segment::new(),
+            flushed: Vec::new(),
+            budget,
+            spill_dir,
+            usage,
+            error: None,
+            schema,
+        }
+    }
+
+    pub(crate) fn add(&mut self, id: usize, doc: Document, terms: HashMap<Term, Vec<usize>>) {
+        let ram_bytes = self.buffer.ram_bytes;
+        self.buffer.add(id, doc, terms);
+        self.usage
+            .ram_bytes
+            .fetch_add(self.buffer.ram_bytes - ram_bytes, Ordering::Relaxed);
+        self.usage.buffered_docs.fetch_add(1, Ordering::Relaxed);
+
+        if self.budget > 0 && self.buffer.ram_bytes >= self.budget && self.error.is_none() {
+            if let Err(e) = self.flush() {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    // writes the segment in memory into files, and keeps it if they fail to be written
+    fn flush(&mut self) -> io::Result<()> {
+        let path = self.spill_dir.segment_path()?;
+        if let Err(e) = self.buffer.write_files(&path, &self.schema) {
+            for ext in SEGMENT_FILES {
+                let _ = fs::remove_file(path.with_extension(ext));
+            }
+            return Err(e);
+        }
+        self.flushed.push(path);
+        let segment = std::mem::replace(&mut self.buffer, Segment::new());
+        let (ram_bytes, docs) = (segment.ram_bytes, segment.stored.len());
+
+        self.usage.ram_bytes.fetch_sub(ram_bytes, Ordering::Relaxed);
+        self.usage.buffered_docs.fetch_sub(docs, Ordering::Relaxed);
+        self.usage.flushed_segments.fetch_add(1, Ordering::Relaxed);
+        self.usage.flushed_docs.fetch_add(docs, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // flushes the segment in memory, so that every document is in the flushed
+    // segments. fails if a segment failed to be flushed.
+    pub(crate) fn flush_all(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if self.buffer.stored.is_empty() {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    // merges the flushed segments and then the one in memory. fails if a segment
+    // failed to be flushed or can't be read back, but merges every readable one.
+    pub(crate) fn merge_into(self, index: &mut Index) -> io::Result<()> {
+        let mut error = self.error;
+        for path in self.flushed {
+            if let Err(e) = Segment::merge_files(&path, index) {
+                error.get_or_insert(e);
+            }
+            for ext in SEGMENT_FILES {
+                let _ = fs::remove_file(path.with_extension(ext));
+            }
+        }
+        self.buffer.merge_into(index);
+        match error {
+            None => Ok(()),
+            Some(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::segment::{SegmentWriter, SpillDir, Usage, SEGMENT_FILES};
+    use crate::store::tests::test_dir;
+    use crate::{
+        analyze_fields, doc, FieldType, Index, IndexWriter, IndexWriterConfig, Schema, TokenizeType,
+    };
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn segment_writer_test() {
+        let parent = test_dir("segment_writer");
+        fs::create_dir_all(&parent).unwrap();
+        let spill_dir = Arc::new(SpillDir::new(&parent));
+        let usage = Arc::new(Usage::default());
+        let mut writer = SegmentWriter::new(100, spill_dir.clone(), usage.clone(), Schema::new());
+
+        let mut expected = IndexWriter::new();
+        for (id, body) in ["dog cat", "dog fox", "bird", "dog dog monkey"]
+            .into_iter()
+            .enumerate()
+        {
+            let terms = analyze_fields(TokenizeType::Whitespace, &Schema::new(), &doc!(body));
+            writer.add(id, doc!(body), terms.unwrap());
+            expected.write(doc!(body)).unwrap();
+        }
+        assert!(usage.flushed_segments.load(Ordering::Relaxed) > 0);
+        assert_eq!(
+            usage.flushed_docs.load(Ordering::Relaxed)
+                + usage.buffered_docs.load(Ordering::Relaxed),
+            4
+        );
+        assert_eq!(
+            fs::read_dir(&spill_dir.path).unwrap().count(),
+            writer.flushed.len() * SEGMENT_FILES.len()
+        );
+
+        let mut index = Index::new(4, TokenizeType::Whitespace, Schema::new());
+        writer.merge_into(&mut index).unwrap();
+        for posting_list in index.postings.values_mut() {
+            posting_list.postings.sort_unstable_by_key(|p| p.doc_id);
+        }
+        assert_eq!(index, expected.build());
+
+        // the directory is removed with the last reference
+        let path = spill_dir.path.clone();
+        drop(spill_dir);
+        assert!(!path.exists());
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn flush_failure_test() {
+        let parent = test_dir("flush_failure");
+        let spill_dir = Arc::new(SpillDir::new(&parent));
+        // the docs file of the first segment can't be created
+        fs::create_dir_all(spill_dir.path.join("segment-0.docs")).unwrap();
+        let usage = Arc::new(Usage::default());
+        let mut writer = SegmentWriter::new(1, spill_dir.clone(), usage.clone(), Schema::new());
+
+        let terms = analyze_fields(TokenizeType::Whitespace, &Schema::new(), &doc!("dog"));
+        writer.add(0, doc!("dog"), terms.unwrap());
+        assert!(writer.error.is_some());
+        assert_eq!(usage.buffered_docs.load(Ordering::Relaxed), 1);
+        assert_eq!(usage.flushed_segments.load(Ordering::Relaxed), 0);
+        // files written before the failure are removed
+        assert_eq!(fs::read_dir(&spill_dir.path).unwrap().count(), 1);
+
+        // the document is kept in memory
+        let mut index = Index::new(1, TokenizeType::Whitespace, Schema::new());
+        assert!(writer.merge_into(&mut index).is_err());
+        assert_eq!(index.doc(0).unwrap().unwrap().body, "dog");
+        drop(spill_dir);
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn save_segments_test() {
+        let parent = test_dir("save_segments");
+        let (streamed, committed) = (parent.join("streamed"), parent.join("committed"));
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("tag", FieldType::Keyword)
+            .add_field("price", FieldType::Numeric)
+            .add_field("date", FieldType::Date);
+        let writer = |threads, ram_budget| {
+            IndexWriter::with_config(IndexWriterConfig {
+                threads,
+                ram_budget,
+                tmp_dir: Some(parent.clone()),
+                schema: schema.clone(),
+                ..Default::default()
+            })
+        };
+        let (mut budgeted, mut unbudgeted) = (writer(3, 1024), writer(1, 0));
+        let words = ["dog", "cat", "fox", "bird", "monkey"];
+        for i in 0..60 {
+            let body = (0..i % 7)
+                .map(|j| words[(i + j) % words.len()])
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut doc = doc!(&body);
+            if i % 3 > 0 {
+                doc.set_field("title", words[i % 4].to_string());
+                doc.set_field("tag", words[i % 5].to_string());
+            }
+            if i % 4 > 0 {
+                doc.set_field("price", format!("{}", (i % 9) as f64 - 2.5));
+                doc.set_field("date", format!("2024-01-{:02}", i % 28 + 1));
+            }
+            budgeted.write(doc.clone()).unwrap();
+            unbudgeted.write(doc).unwrap();
+        }
+        for id in [0, 7, 31, 59] {
+            budgeted.delete(id).unwrap();
+            unbudgeted.delete(id).unwrap();
+        }
+        assert!(budgeted.stats().flushed_segments > 3);
+
+        let index = budgeted.save(&streamed).unwrap();
+        let expected = unbudgeted.commit().unwrap();
+        expected.save(&committed).unwrap();
+        assert_eq!(index, Index::open(&committed).unwrap());
+        assert_eq!(index.stats().doc_count, 56);
+
+        // the files of both commits are the same
+        let files = |dir: &Path| {
+            let mut files = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    (entry.file_name(), fs::read(entry.path()).unwrap())
+                })
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        assert_eq!(files(&streamed), files(&committed));
+        // flushed segments are removed with the writer
+        assert_eq!(fs::read_dir(&parent).unwrap().count(), 2);
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
@@ -0,0 +1,1232 @@
+use crate::json::{
+    document_from_json, explanation_to_json, facets_to_json, search_result_to_json, stats_to_json,
+};
+use crate::store::index_error;
+use crate::wal::{Op, Wal};
+use crate::{
+    explain, DocAndScore, Document, Error, Facet, FacetCollector, Index, Result, Sort, SyncPolicy,
+    TopDocs, TopFieldDocs,
+};
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+const MAX_HEADER_LEN: usize = 64 * 1024;
+const MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+const DEFAULT_TOP_K: usize = 10;
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_COMMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    // keyword field identifying documents for update and deletion
+    pub key_field: String,
+
+    // directory to save the index to when updates are published, or None to
+    // keep it in memory
+    pub path: Option<PathBuf>,
+
+    // write-ahead log of updates not saved yet, which `Server::open` opens.
+    // it needs `path`, where the updates are saved before they are removed.
+    pub wal: Option<PathBuf>,
+
+    pub wal_sync: SyncPolicy,
+
+    // updates are published together once in the interval, which saves the
+    // index and makes searches see them. until then, the WAL keeps them on
+    // disk, and they are lost by a crash without one. zero publishes every update.
+    pub commit_interval: Duration,
+
+    // connections handled at once by `serve`
+    pub max_connections: usize,
+
+    // time a connection may wait for the client to send or receive
+    pub timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            key_field: "id".to_string(),
+            path: None,
+            wal: None,
+            wal_sync: SyncPolicy::default(),
+            commit_interval: DEFAULT_COMMIT_INTERVAL,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(method: &str, path: &str, body: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    // {"error": {"status": 400, "reason": "..."}}
+    fn error(status: u16, reason: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "error": { "status": status, "reason": reason } }),
+        }
+    }
+
+    // errors caused by the request are 400, and others are 500
+    fn from_error(e: &Error) -> Self {
+        match e {
+            Error::QueryParse(_) | Error::SchemaViolation(_) => {
+                Response::error(400, &e.to_string())
+            }
+            _ => Response::error(500, &e.to_string()),
+        }
+    }
+}
+
+// serves the index over HTTP with JSON bodies.
+//
+// POST   /search           {"query": "...", "top_k": 10, "offset": 0, "search_after": [score, id],
+//                           "sort": "date:desc,score", "facets": ["category", "price:[* TO 100},[100 TO *]"]}
+// POST   /explain          {"query": "...", "id": 0}
+// POST   /documents        {"id": "1", "body": "..."} or an array of them
+// DELETE /documents/{key}
+// GET    /stats
+pub struct Server {
+    // the index which searches read, which is replaced once updates are
+    // published. the lock is held only to take or replace it.
+    reader: RwLock<Arc<Index>>,
+
+    // updates take the writer in turn, without blocking searches
+    writer: Mutex<Writer>,
+
+    config: ServerConfig,
+}
+
+// the index which updates modify, and the WAL they are logged to
+struct Writer {
+    // a copy of the index in memory, which is made at the first update
+    index: Option<Index>,
+
+    wal: Option<Wal>,
+
+    // whether the index has updates not published yet
+    pending: bool,
+}
+
+impl Writer {
+    fn get(&mut self, reader: &RwLock<Arc<Index>>) -> Result<(&mut Index, &mut Option<Wal>)> {
+        let index = match self.index {
+            Some(ref mut index) => index,
+            None => {
+                let reader = reader.read().unwrap_or_else(|e| e.into_inner()).clone();
+                self.index.insert(reader.to_memory()?)
+            }
+        };
+        Ok((index, &mut self.wal))
+    }
+}
+
+// adds a document, replacing those with the same key, and returns its id.
+// the write is logged once the index accepts the document, before it's added.
+// replaced documents aren't logged, since replaying the write replaces them.
+fn write_document(
+    index: &mut Index,
+    wal: Option<&mut Wal>,
+    key_field: &str,
+    doc: Document,
+) -> Result<usize> {
+    let replaced = match doc.field(key_field) {
+        Some(key) => index.find_documents(key_field, key)?,
+        None => Vec::new(),
+    };
+    let id = index.add_document_with(doc, |id, doc| match wal {
+        Some(wal) => Ok(wal.log_write(id, doc)?),
+        None => Ok(()),
+    })?;
+    for id in replaced {
+        index.delete_document(id)?;
+    }
+    Ok(id)
+}
+
+impl Server {
+    // a server without a WAL, whatever the config has
+    pub fn new(index: Index, config: ServerConfig) -> Self {
+        Self {
+            reader: RwLock::new(Arc::new(index)),
+            writer: Mutex::new(Writer {
+                index: None,
+                wal: None,
+                pending: false,
+            }),
+            config,
+        }
+    }
+
+    // a server logging updates to the WAL of the config, if it has one, once
+    // they are accepted. `index` is the one saved at the path of the config.
+    // updates are removed from the log once they are saved there.
+    //
+    // updates left in the log by a server which crashed or failed to save them
+    // are applied to the index and saved first. writes which the index already
+    // has, as its ids tell, are skipped, and deletes are by id, so updates
+    // saved right before a crash are not applied twice.
+    pub fn open(mut index: Index, config: ServerConfig) -> Result<Self> {
+        let path = match &config.wal {
+            None => return Ok(Server::new(index, config)),
+            Some(path) => path,
+        };
+        let dir = match &config.path {
+            Some(dir) => dir,
+            None => {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "a write-ahead log needs a path to save the index",
+                )))
+            }
+        };
+        let (mut wal, ops) = Wal::open(path, config.wal_sync).map_err(index_error)?;
+        if !ops.is_empty() {
+            for op in ops {
+                match op {
+                    Op::Write(id, _) if id < index.next_doc_id => {}
+                    Op::Write(id, doc) => {
+                        if write_document(&mut index, None, &config.key_field, doc)? != id {
+                            return Err(Error::CorruptIndex(format!(
+                                "write-ahead log doesn't follow the index: {}",
+                                path.display()
+                            )));
+                        }
+                    }
+                    Op::Delete(id) => {
+                        index.delete_document(id)?;
+                    }
+                }
+            }
+            index.save(dir)?;
+            wal.clear()?;
+        }
+        Ok(Self {
+            reader: RwLock::new(Arc::new(index)),
+            writer: Mutex::new(Writer {
+                index: None,
+                wal: Some(wal),
+                pending: false,
+            }),
+            config,
+        })
+    }
+
+    // handles a request in-process, without any connection
+    pub fn handle(&self, request: &Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or("");
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["search"]) => self.search(&request.body),
+            ("POST", ["explain"]) => self.explain(&request.body),
+            ("POST", ["documents"]) => self.add_documents(&request.body),
+            ("DELETE", ["documents", key]) => match percent_decode(key) {
+                Some(key) => self.delete_documents(&key),
+                None => Response::error(400, "invalid key"),
+            },
+            ("GET", ["stats"]) => self.stats(),
+            (_, ["search"])
+            | (_, ["explain"])
+            | (_, ["documents"])
+            | (_, ["documents", _])
+            | (_, ["stats"]) => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn search(&self, body: &[u8]) -> Response {
+        let body = match serde_json::from_slice::<Value>(body) {
+            Ok(body) => body,
+            Err(e) => return Response::error(400, &format!("invalid JSON: {}", e)),
+        };
+        let query = match body.get("query").and_then(Value::as_str) {
+            Some(query) => query,
+            None => return Response::error(400, "query is required"),
+        };
+        let top_k = match body.get("top_k") {
+            None => DEFAULT_TOP_K,
+            Some(top_k) => match top_k.as_u64() {
+                Some(top_k) => top_k as usize,
+                None => return Response::error(400, "top_k must be a non-negative integer"),
+            },
+        };
+        let offset = match body.get("offset") {
+            None => 0,
+            Some(offset) => match offset.as_u64() {
+                Some(offset) => offset as usize,
+                None => return Response::error(400, "offset must be a non-negative integer"),
+            },
+        };
+        let mut collector = TopDocs::new(top_k).with_offset(offset);
+        if let Some(after) = body.get("search_after") {
+            // score and id of the last hit of the previous page
+            match after.as_array().map(Vec::as_slice) {
+                Some([score, id]) => match (score.as_f64(), id.as_u64()) {
+                    (Some(score), Some(id)) => {
+                        collector = collector
+                            .search_after(DocAndScore::new_with_score(id as usize, score as f32))
+                    }
+                    _ => return Response::error(400, "search_after must be [score, id]"),
+                },
+                _ => return Response::error(400, "search_after must be [score, id]"),
+            }
+        }
+        let sort = match body.get("sort") {
+            None => None,
+            Some(_) if body.get("search_after").is_some() => {
+                return Response::error(400, "search_after can't be used with sort")
+            }
+            Some(sort) => match sort.as_str().map(str::parse::<Sort>) {
+                Some(Ok(sort)) => Some(sort),
+                Some(Err(e)) => return Response::from_error(&e),
+                None => return Response::error(400, "sort must be a string"),
+            },
+        };
+        let facets = match body.get("facets") {
+            None => Vec::new(),
+            Some(facets) => match facets
+                .as_array()
+                .and_then(|facets| facets.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+            {
+                Some(facets) => facets,
+                None => return Response::error(400, "facets must be an array of strings"),
+            },
+        };
+
+        let index = self.reader();
+        let searcher = index.searcher();
+        let facets = match facets
+            .into_iter()
+            .map(|spec| {
+                Facet::parse(index.schema(), spec)
+                    .and_then(|facet| FacetCollector::new(&index, facet))
+            })
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(facets) => facets,
+            Err(e) => return Response::from_error(&e),
+        };
+        let with_facets = !facets.is_empty();
+        match searcher
+            .parse_query(query)
+            .and_then(|query| match sort {
+                None => searcher.search(&query, (collector, facets)),
+                Some(sort) => searcher.search(
+                    &query,
+                    (
+                        TopFieldDocs::new(&index, sort, top_k)?.with_offset(offset),
+                        facets,
+                    ),
+                ),
+            })
+            .and_then(|(result, facets)| {
+                let mut body = search_result_to_json(&index, query, &result)?;
+                if with_facets {
+                    body["facets"] = facets_to_json(&facets);
+                }
+                Ok(body)
+            }) {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::from_error(&e),
+        }
+    }
+
+    fn explain(&self, body: &[u8]) -> Response {
+        let body = match serde_json::from_slice::<Value>(body) {
+            Ok(body) => body,
+            Err(e) => return Response::error(400, &format!("invalid JSON: {}", e)),
+        };
+        let query = match body.get("query").and_then(Value::as_str) {
+            Some(query) => query,
+            None => return Response::error(400, "query is required"),
+        };
+        let id = match body.get("id").and_then(Value::as_u64) {
+            Some(id) => id as usize,
+            None => return Response::error(400, "id must be a non-negative integer"),
+        };
+
+        let index = self.reader();
+        match explain(&index, query, id) {
+            Ok(Some(explanation)) => Response::ok(explanation_to_json(&explanation)),
+            Ok(None) => Response::error(404, "document not found"),
+            Err(e) => Response::from_error(&e),
+        }
+    }
+
+    fn add_documents(&self, body: &[u8]) -> Response {
+        let values = match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(values)) => values,
+            Ok(value) => vec![value],
+            Err(e) => return Response::error(400, &format!("invalid JSON: {}", e)),
+        };
+
+        let schema = self.reader().schema().clone();
+        let mut docs = Vec::with_capacity(values.len());
+        for (i, value) in values.iter().enumerate() {
+            match document_from_json(&schema, value) {
+                Ok(doc) => docs.push(doc),
+                Err(reason) => return Response::error(400, &format!("document {}: {}", i, reason)),
+            }
+        }
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let (index, wal) = match writer.get(&self.reader) {
+            Ok(writer) => writer,
+            Err(e) => return Response::from_error(&e),
+        };
+        let mut ids = Vec::with_capacity(docs.len());
+        let mut result = Ok(());
+        for doc in docs {
+            match write_document(index, wal.as_mut(), &self.config.key_field, doc) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        // documents added before the error are kept
+        writer.pending |= !ids.is_empty();
+        if let Err(e) = self.updated(&mut writer) {
+            return Response::error(500, &format!("failed to save index: {}", e));
+        }
+        if let Err(e) = result {
+            return Response::from_error(&e);
+        }
+        Response {
+            status: 201,
+            body: json!({ "ids": ids }),
+        }
+    }
+
+    fn delete_documents(&self, key: &str) -> Response {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let (index, wal) = match writer.get(&self.reader) {
+            Ok(writer) => writer,
+            Err(e) => return Response::from_error(&e),
+        };
+        let ids = match index.find_documents(&self.config.key_field, key) {
+            Ok(ids) => ids,
+            Err(e) => return Response::from_error(&e),
+        };
+        if ids.is_empty() {
+            return Response::error(404, "document not found");
+        }
+        for id in ids.iter() {
+            if let Err(e) = wal.as_mut().map_or(Ok(()), |wal| wal.log_delete(*id)) {
+                return Response::from_error(&e.into());
+            }
+            if let Err(e) = index.delete_document(*id) {
+                return Response::from_error(&e);
+            }
+        }
+        writer.pending = true;
+        if let Err(e) = self.updated(&mut writer) {
+            return Response::error(500, &format!("failed to save index: {}", e));
+        }
+        Response::ok(json!({ "deleted": ids.len() }))
+    }
+
+    fn stats(&self) -> Response {
+        Response::ok(stats_to_json(&self.reader().stats()))
+    }
+
+    // the index which searches read
+    fn reader(&self) -> Arc<Index> {
+        self.reader
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // publishes the updates not published yet. `serve` calls it at every
+    // commit interval, and so does dropping the server.
+    pub fn commit(&self) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        self.publish(&mut writer)
+    }
+
+    // publishes an update right away if the commit interval is zero
+    fn updated(&self, writer: &mut Writer) -> Result<()> {
+        if self.config.commit_interval.is_zero() {
+            self.publish(writer)?;
+        }
+        Ok(())
+    }
+
+    // saves the index of the writer, after which the updates logged to the WAL
+    // are removed, and makes searches read it. searches in progress keep
+    // reading the index they started with. the updates stay pending if it fails.
+    fn publish(&self, writer: &mut Writer) -> Result<()> {
+        let index = match writer.index {
+            Some(ref index) if writer.pending => index,
+            _ => return Ok(()),
+        };
+        let reader = match self.config.path {
+            Some(ref path) => {
+                index.save(path)?;
+                if let Some(wal) = &mut writer.wal {
+                    wal.clear()?;
+                }
+                Index::open(path)?
+            }
+            None => index.to_memory()?,
+        };
+        *self.reader.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(reader);
+        writer.pending = false;
+        Ok(())
+    }
+
+    // accepts connections forever, handling each of them in its own thread.
+    // once `max_connections` are open, the next one is accepted when one closes.
+    // updates are published at every commit interval by another thread.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let open = (Mutex::new(0usize), Condvar::new());
+        let interval = self.config.commit_interval;
+        // stops the thread publishing updates when dropped
+        let (stop, stopped) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            if !interval.is_zero() {
+                scope.spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                        if let Err(e) = self.commit() {
+                            eprintln!("failed to save index: {}", e);
+                        }
+                    }
+                });
+            }
+            let _stop = stop;
+            for stream in listener.incoming() {
+                let stream = stream?;
+                let mut count = open.0.lock().unwrap_or_else(|e| e.into_inner());
+                while *count >= self.config.max_connections.max(1) {
+                    count = open.1.wait(count).unwrap_or_else(|e| e.into_inner());
+                }
+                *count += 1;
+                drop(count);
+
+                let open = &open;
+                scope.spawn(move || {
+                    if let Err(e) = self.handle_connection(stream) {
+                        eprintln!("connection error: {}", e);
+                    }
+                    *open.0.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                    open.1.notify_one();
+                });
+            }
+            Ok(())
+        })
+    }
+
+    // reads one HTTP/1.1 request and writes the response, then closes the
+    // connection. fails if the client doesn't send or receive for the timeout.
+    pub fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.config.timeout))?;
+        stream.set_write_timeout(Some(self.config.timeout))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match read_request(&mut reader) {
+            Ok(request) => self.handle(&request),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                Response::error(400, &e.to_string())
+            }
+            Err(e) => return Err(e),
+        };
+        write_response(stream, &response)
+    }
+}
+
+// publishes the updates not published yet
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+    let mut header_len = 0;
+    let mut read_line = |reader: &mut R| -> io::Result<String> {
+        let mut line = String::new();
+        let len = reader
+            .take((MAX_HEADER_LEN - header_len) as u64)
+            .read_line(&mut line)?;
+        header_len += len;
+        if !line.ends_with('\n') {
+            return Err(invalid_request("incomplete request header"));
+        }
+        Ok(line.trim_end().to_string())
+    };
+
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), path.to_string())
+        }
+        _ => return Err(invalid_request("invalid request line")),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_request("invalid header"))?;
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if name == "content-length" {
+            content_length = value
+                .parse::<usize>()
+                .map_err(|_| invalid_request("invalid content-length"))?;
+        } else if name == "transfer-encoding" {
+            return Err(invalid_request("transfer-encoding is not supported"));
+        }
+    }
+    if content_length > MAX_BODY_LEN {
+        return Err(invalid_request("request body is too large"));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response<W: Write>(mut w: W, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        w,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason_phrase(response.status),
+        body.len(),
+        body
+    )?;
+    w.flush()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+fn invalid_request(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// decodes "%XX" escapes of a path segment
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{percent_decode, Request, Server, ServerConfig};
+    use crate::store::tests::test_dir;
+    use crate::{Error, FieldType, Index, IndexWriter, IndexWriterConfig, Schema};
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+    use std::{fs, thread};
+
+    fn server(config: ServerConfig) -> Server {
+        let mut schema = Schema::new();
+        schema
+            .add_field("id", FieldType::Keyword)
+            .add_field("category", FieldType::Keyword)
+            .add_field("price", FieldType::Numeric);
+        let index = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        })
+        .build();
+        Server::new(index, config)
+    }
+
+    // publishes every update, so that searches see it right away
+    fn config() -> ServerConfig {
+        ServerConfig {
+            commit_interval: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    fn ids(body: &Value) -> Vec<u64> {
+        body["hits"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["id"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn server_documents_test() {
+        let server = server(config());
+
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"[{"id": "a", "body": "dog cat"}, {"id": "b/1", "body": "dog fox"}]"#,
+        ));
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, json!({"ids": [0, 1]}));
+
+        let response = server.handle(&Request::new("POST", "/search", r#"{"query": "dog"}"#));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["total_hits"], 2);
+        assert_eq!(ids(&response.body), vec![0, 1]);
+
+        // replace by key
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"{"id": "a", "body": "raccoon"}"#,
+        ));
+        assert_eq!(response.body, json!({"ids": [2]}));
+        let response = server.handle(&Request::new("POST", "/search", r#"{"query": "dog"}"#));
+        assert_eq!(ids(&response.body), vec![1]);
+
+        let response = server.handle(&Request::new(
+            "POST",
+            "/explain",
+            r#"{"query": "dog", "id": 1}"#,
+        ));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["description"], "sum of:");
+        assert_eq!(
+            response.body["details"][0]["description"],
+            "weight(dog), product of:"
+        );
+        let response = server.handle(&Request::new(
+            "POST",
+            "/explain",
+            r#"{"query": "dog", "id": 0}"#,
+        ));
+        assert_eq!(response.status, 404);
+
+        let response = server.handle(&Request::new("DELETE", "/documents/b%2F1", ""));
+        assert_eq!(response.body, json!({"deleted": 1}));
+        let response = server.handle(&Request::new("DELETE", "/documents/b%2F1", ""));
+        assert_eq!(response.status, 404);
+
+        let response = server.handle(&Request::new("GET", "/stats", ""));
+        assert_eq!(response.body["doc_count"], 1);
+        // raccoon and the key
+        assert_eq!(response.body["term_count"], 2);
+    }
+
+    #[test]
+    fn server_paging_test() {
+        let server = server(config());
+        // more dogs score higher. the birds keep the idf of dog positive.
+        let docs = (0..10)
+            .map(|i| match i {
+                0..=4 => json!({"id": i.to_string(), "body": "dog ".repeat(i + 1) + "cat"}),
+                _ => json!({"id": i.to_string(), "body": "bird"}),
+            })
+            .collect::<Vec<_>>();
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            &Value::from(docs).to_string(),
+        ));
+        assert_eq!(response.status, 201);
+
+        let search =
+            |body: Value| server.handle(&Request::new("POST", "/search", &body.to_string()));
+        let response = search(json!({"query": "dog", "top_k": 2, "offset": 1}));
+        assert_eq!(response.body["total_hits"], 5);
+        assert_eq!(ids(&response.body), vec![3, 2]);
+
+        // pages after the last hit of the previous page
+        let mut pages = Vec::new();
+        let mut request = json!({"query": "dog", "top_k": 2});
+        loop {
+            let response = search(request.clone());
+            let page = ids(&response.body);
+            let last = match response.body["hits"].as_array().unwrap().last() {
+                None => break,
+                Some(last) => last.clone(),
+            };
+            request["search_after"] = json!([last["score"], last["id"]]);
+            pages.push(page);
+        }
+        assert_eq!(pages, vec![vec![4, 3], vec![2, 1], vec![0]]);
+
+        let response =
+            search(json!({"query": "dog", "top_k": 2, "offset": 1, "sort": "score:asc"}));
+        assert_eq!(response.body["total_hits"], 5);
+        assert_eq!(ids(&response.body), vec![1, 2]);
+    }
+
+    #[test]
+    fn server_facets_test() {
+        let server = server(config());
+        let docs = json!([
+            {"id": "1", "body": "pen", "category": "stationery", "price": "120"},
+            {"id": "2", "body": "red pen", "category": "stationery", "price": "80"},
+            {"id": "3", "body": "pen case", "category": "bags", "price": "1500"},
+            {"id": "4", "body": "book", "category": "books", "price": "800"},
+        ]);
+        let response = server.handle(&Request::new("POST", "/documents", &docs.to_string()));
+        assert_eq!(response.status, 201);
+
+        let request = json!({
+            "query": "pen",
+            "top_k": 1,
+            "sort": "price",
+            "facets": ["category", "price:[* TO 100},[100 TO *]"],
+        });
+        let response = server.handle(&Request::new("POST", "/search", &request.to_string()));
+        assert_eq!(response.body["total_hits"], 3);
+        assert_eq!(ids(&response.body), vec![1]);
+        assert_eq!(
+            response.body["facets"],
+            json!([
+                {"field": "category", "counts": [
+                    {"value": "stationery", "count": 2},
+                    {"value": "bags", "count": 1},
+                ]},
+                {"field": "price", "counts": [
+                    {"value": "price:[* TO 100}", "count": 1},
+                    {"value": "price:[100 TO *]", "count": 2},
+                ]},
+            ])
+        );
+
+        let response = server.handle(&Request::new("POST", "/search", r#"{"query": "pen"}"#));
+        assert!(response.body.get("facets").is_none());
+    }
+
+    #[test]
+    fn server_error_test() {
+        let server = server(config());
+        let cases = [
+            (Request::new("POST", "/search", "{"), 400),
+            (Request::new("POST", "/search", r#"{"top_k": 1}"#), 400),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "top_k": -1}"#),
+                400,
+            ),
+            (Request::new("POST", "/search", r#"{"query": "a^x"}"#), 400),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "offset": "1"}"#),
+                400,
+            ),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "search_after": [1]}"#),
+                400,
+            ),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "sort": "body"}"#),
+                400,
+            ),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "facets": "category"}"#),
+                400,
+            ),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "facets": ["body"]}"#),
+                400,
+            ),
+            (
+                Request::new("POST", "/search", r#"{"query": "a", "sort": "score:up"}"#),
+                400,
+            ),
+            (
+                Request::new(
+                    "POST",
+                    "/search",
+                    r#"{"query": "a", "sort": "score", "search_after": [1, 0]}"#,
+                ),
+                400,
+            ),
+            (
+                Request::new("POST", "/documents", r#"[{"body": "a"}, 1]"#),
+                400,
+            ),
+            (Request::new("GET", "/search", ""), 405),
+            (Request::new("GET", "/unknown", ""), 404),
+            (Request::new("DELETE", "/documents/%zz", ""), 400),
+        ];
+        for (request, status) in cases {
+            let response = server.handle(&request);
+            assert_eq!(response.status, status, "{:?}", request);
+            assert_eq!(response.body["error"]["status"], status);
+            assert!(response.body["error"]["reason"].is_string());
+        }
+
+        // nothing is added by the invalid request
+        let response = server.handle(&Request::new("GET", "/stats", ""));
+        assert_eq!(response.body["doc_count"], 0);
+    }
+
+    #[test]
+    fn server_key_field_test() {
+        // keys are looked up by the postings of a keyword field
+        let server = server(ServerConfig {
+            key_field: "price".to_string(),
+            ..config()
+        });
+        let response = server.handle(&Request::new("DELETE", "/documents/1", ""));
+        assert_eq!(response.status, 400);
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"{"price": "1", "body": "a"}"#,
+        ));
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn server_persistence_test() {
+        let dir = test_dir("server_persistence_test");
+        let server = server(ServerConfig {
+            path: Some(dir.clone()),
+            ..config()
+        });
+        server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"{"id": "a", "body": "dog"}"#,
+        ));
+
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.find_documents("id", "a").unwrap(), vec![0]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn server_wal_test() {
+        let parent = test_dir("server_wal_test");
+        let (dir, wal) = (parent.join("index"), parent.join("wal"));
+        fs::create_dir_all(&parent).unwrap();
+        let config = ServerConfig {
+            path: Some(dir.clone()),
+            wal: Some(wal.clone()),
+            ..config()
+        };
+        let open = || {
+            let mut schema = Schema::new();
+            schema.add_field("id", FieldType::Keyword);
+            let index = IndexWriter::with_config(IndexWriterConfig {
+                schema,
+                ..Default::default()
+            })
+            .build();
+            Server::open(index, config.clone()).unwrap()
+        };
+
+        // the index can't be saved where a file is
+        fs::write(&dir, "").unwrap();
+        let server = open();
+        for body in [
+            r#"{"id": "a", "body": "dog"}"#,
+            r#"{"id": "a", "body": "cat"}"#,
+        ] {
+            let response = server.handle(&Request::new("POST", "/documents", body));
+            assert_eq!(response.status, 500);
+        }
+        drop(server);
+
+        // the updates which failed to be saved are applied and saved at open
+        fs::remove_file(&dir).unwrap();
+        let server = open();
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.find_documents("id", "a").unwrap(), vec![1]);
+        assert_eq!(index.stats().doc_count, 1);
+
+        let response = server.handle(&Request::new("DELETE", "/documents/a", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        drop(server);
+        assert_eq!(Index::open(&dir).unwrap().stats().doc_count, 0);
+
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn server_commit_test() {
+        let dir = test_dir("server_commit_test");
+        let server = server(ServerConfig {
+            path: Some(dir.clone()),
+            commit_interval: Duration::from_secs(3600),
+            ..Default::default()
+        });
+        let search = |server: &Server| {
+            let response = server.handle(&Request::new("POST", "/search", r#"{"query": "dog"}"#));
+            ids(&response.body)
+        };
+        for body in [r#"{"id": "a", "body": "dog"}"#, r#"{"body": "dog"}"#] {
+            let response = server.handle(&Request::new("POST", "/documents", body));
+            assert_eq!(response.status, 201);
+        }
+        // updates are published together
+        assert_eq!(search(&server), Vec::<u64>::new());
+        assert!(!dir.exists());
+        server.commit().unwrap();
+        assert_eq!(search(&server), vec![0, 1]);
+        assert_eq!(Index::open(&dir).unwrap().stats().doc_count, 2);
+
+        // and so are those left when the server is dropped
+        let response = server.handle(&Request::new("DELETE", "/documents/a", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(search(&server), vec![0, 1]);
+        drop(server);
+        assert_eq!(Index::open(&dir).unwrap().stats().doc_count, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn server_wal_replay_test() {
+        let parent = test_dir("server_wal_replay_test");
+        let (dir, wal) = (parent.join("index"), parent.join("wal"));
+        fs::create_dir_all(&parent).unwrap();
+        server(config()).reader().save(&dir).unwrap();
+        let config = ServerConfig {
+            path: Some(dir.clone()),
+            wal: Some(wal.clone()),
+            commit_interval: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let open = || Server::open(Index::open(&dir).unwrap(), config.clone()).unwrap();
+
+        let server = open();
+        for body in [
+            r#"{"id": "a", "body": "dog"}"#,
+            r#"{"id": "b", "body": "cat"}"#,
+            r#"{"id": "a", "body": "fox"}"#,
+        ] {
+            let response = server.handle(&Request::new("POST", "/documents", body));
+            assert_eq!(response.status, 201);
+        }
+        let response = server.handle(&Request::new("DELETE", "/documents/b", ""));
+        assert_eq!(response.status, 200);
+        // a crash after the index is saved, before the log is cleared
+        let logged = fs::read(&wal).unwrap();
+        server.commit().unwrap();
+        drop(server);
+        fs::write(&wal, &logged).unwrap();
+
+        // the updates in the saved index are not applied again
+        let server = open();
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.stats().doc_count, 1);
+        assert_eq!(index.find_documents("id", "a").unwrap(), vec![2]);
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+
+        // those after it are, replacing documents of the same key
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"[{"id": "a", "body": "bird"}, {"id": "c", "body": "bird"}]"#,
+        ));
+        assert_eq!(response.body, json!({"ids": [3, 4]}));
+        // a crash before the updates are published
+        std::mem::forget(server);
+        let server = open();
+        let index = Index::open(&dir).unwrap();
+        assert_eq!(index.stats().doc_count, 2);
+        assert_eq!(index.find_documents("id", "a").unwrap(), vec![3]);
+        assert_eq!(server.reader().stats(), index.stats());
+
+        drop(server);
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn server_wal_rejected_test() {
+        let parent = test_dir("server_wal_rejected_test");
+        let (dir, wal) = (parent.join("index"), parent.join("wal"));
+        fs::create_dir_all(&parent).unwrap();
+        let index = server(config()).reader().to_memory().unwrap();
+        let config = ServerConfig {
+            path: Some(dir.clone()),
+            wal: Some(wal.clone()),
+            commit_interval: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let server = Server::open(index.to_memory().unwrap(), config.clone()).unwrap();
+
+        // a document which the index rejects is not logged, so the server opens again
+        let response = server.handle(&Request::new(
+            "POST",
+            "/documents",
+            r#"{"id": "a", "body": "dog", "price": "x"}"#,
+        ));
+        assert_eq!(response.status, 400);
+        assert_eq!(fs::metadata(&wal).unwrap().len(), 0);
+        drop(server);
+        let server = Server::open(index.to_memory().unwrap(), config.clone()).unwrap();
+        assert_eq!(server.reader().stats().doc_count, 0);
+        drop(server);
+
+        // a log is cleared only once updates are saved to the path
+        let err = Server::open(
+            index,
+            ServerConfig {
+                path: None,
+                ..config
+            },
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, Error::Io(_)));
+
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn server_reader_test() {
+        let dir = test_dir("server_reader_test");
+        let server = server(ServerConfig {
+            path: Some(dir.clone()),
+            ..config()
+        });
+        let before = server.reader();
+        let response = server.handle(&Request::new("POST", "/documents", r#"{"body": "dog"}"#));
+        assert_eq!(response.status, 201);
+
+        // searches in progress keep the index they started with
+        assert_eq!(before.stats().doc_count, 0);
+        assert_eq!(*server.reader(), Index::open(&dir).unwrap());
+        assert_eq!(server.reader().stats().doc_count, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn server_timeout_test() {
+        let server: &'static Server = Box::leak(Box::new(server(ServerConfig {
+            max_connections: 1,
+            timeout: Duration::from_millis(200),
+            ..config()
+        })));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || server.serve(listener));
+
+        // an idle client holds the only connection until it times out
+        let start = Instant::now();
+        let mut idle = TcpStream::connect(addr).unwrap();
+        write!(idle, "GET /stats HTTP/1.1\r\n").unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /stats HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // the idle connection is closed without a response
+        let mut response = String::new();
+        idle.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn server_connection_test() {
+        let server = server(config());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..2 {
+                    let (stream, _) = listener.accept().unwrap();
+                    server.handle_connection(stream).unwrap();
+                }
+            });
+
+            let body = r#"{"body": "I am Taisuke"}"#;
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST /documents HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+            assert!(response.ends_with("\r\n\r\n{\"ids\":[0]}"));
+
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET /stats HTTP/1.1\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.contains("\"doc_count\":1"));
+        });
+    }
+
+    #[test]
+    fn percent_decode_test() {
+        assert_eq!(percent_decode("abc"), Some("abc".to_string()));
+        assert_eq!(percent_decode("a%2Fb%20c"), Some("a/b c".to_string()));
+        assert_eq!(percent_decode("%E3%81%82"), Some("あ".to_string()));
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%ff"), None);
+    }
+}
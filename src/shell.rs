@@ -0,0 +1,292 @@
+use crate::explain::display_term;
+use crate::query::parse_query;
+use crate::schema::field_term;
+use crate::store::index_error;
+use crate::{explain, search, Count, Highlighter, HighlighterConfig, Index, Result, Term};
+
+use std::fmt::Write;
+use std::time::Instant;
+
+const HELP: &str = "<query>          search documents containing all terms
+:explain <query>  show analyzed terms with document frequency and idf
+:explain <id> <query>
+                  show how the score of a document is computed
+:terms <prefix>   list terms starting with prefix, such as `ca` or `title:ca`
+:doc <id>         show the stored fields of a document
+:top-k <n>        change the number of shown hits
+:stats            show index statistics
+:help             show this help
+:quit             exit the shell";
+
+// max number of terms listed by `:terms`
+const MAX_TERMS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct ShellConfig {
+    pub top_k: usize,
+
+    // highlight matches with terminal colors
+    pub color: bool,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 10,
+            color: false,
+        }
+    }
+}
+
+// evaluates queries and `:` commands typed into `imser shell`
+pub struct Shell {
+    index: Index,
+    config: ShellConfig,
+}
+
+impl Shell {
+    pub fn new(index: Index, config: ShellConfig) -> Self {
+        Self { index, config }
+    }
+
+    // returns the output of the line, or None if the shell should exit
+    pub fn execute(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (command, arg) = match line.strip_prefix(':') {
+            None => return Some(self.query(line).unwrap_or_else(|e| e.to_string())),
+            Some(command) => match command.split_once(char::is_whitespace) {
+                Some((command, arg)) => (command, arg.trim()),
+                None => (command, ""),
+            },
+        };
+        let output = match command {
+            "explain" => self.explain(arg),
+            "terms" => Ok(self.terms(arg)),
+            "doc" => Ok(self.doc(arg)),
+            "top-k" => Ok(self.set_top_k(arg)),
+            "stats" => Ok(self.stats()),
+            "help" | "h" => Ok(HELP.to_string()),
+            "quit" | "q" | "exit" => return None,
+            _ => Ok(format!("unknown command: :{} (try :help)", command)),
+        };
+        Some(output.unwrap_or_else(|e| e.to_string()))
+    }
+
+    fn query(&self, query: &str) -> Result<String> {
+        if query.is_empty() {
+            return Ok(String::new());
+        }
+        let start = Instant::now();
+        let result = search(&self.index, query, self.config.top_k)?;
+        let elapsed = start.elapsed();
+
+        let (pre_tag, post_tag) = if self.config.color {
+            ("\x1b[1;31m", "\x1b[0m")
+        } else {
+            ("", "")
+        };
+        let highlighter = Highlighter::with_config(
+            query,
+            HighlighterConfig {
+                tokenize_type: self.index.tokenize_type(),
+                pre_tag: pre_tag.to_string(),
+                post_tag: post_tag.to_string(),
+                ..Default::default()
+            },
+        )?;
+        let mut output = String::new();
+        for doc in result.docs.iter() {
+            let snippet = match self.index.doc(doc.doc_id)? {
+                Some(stored) => highlighter.snippet(&stored.body)?,
+                None => continue,
+            };
+            let _ = writeln!(output, "[{}] {:.4}\t{}", doc.doc_id, doc.score, snippet);
+        }
+        let _ = write!(
+            output,
+            "{} hits ({:.3} ms)",
+            result.total_hits,
+            elapsed.as_secs_f64() * 1000.0
+        );
+        Ok(output)
+    }
+
+    fn explain(&self, arg: &str) -> Result<String> {
+        // `:explain <id> <query>` explains the score of the document
+        if let Some((id, query)) = arg.split_once(char::is_whitespace) {
+            if let Ok(id) = id.parse::<usize>() {
+                return Ok(match explain(&self.index, query, id)? {
+                    Some(explanation) => explanation.to_string().trim_end().to_string(),
+                    None => format!("document {} not found", id),
+                });
+            }
+        }
+
+        let query = parse_query(self.index.tokenize_type(), self.index.schema(), arg)?;
+        if query.terms.is_empty() && query.phrases.is_empty() && query.ranges.is_empty() {
+            return Ok("no terms in the query".to_string());
+        }
+        let mut output = format!("{} documents", self.index.doc_count);
+        // the terms of a phrase are listed one by one
+        for clause in query.terms.iter().chain(query.phrases.iter().flatten()) {
+            let _ = write!(
+                output,
+                "\n{}\tdf={}\tidf={:.4}\tboost={}",
+                display_term(&clause.term),
+                self.doc_freq(&clause.term)?,
+                self.index.idf(&clause.term).map_err(index_error)?,
+                clause.boost
+            );
+        }
+        for range in query.ranges.iter() {
+            let count = self.index.searcher().search(range, Count::new())?;
+            let _ = write!(output, "\n{}\tfilter\tdocs={}", range, count);
+        }
+        Ok(output)
+    }
+
+    fn terms(&self, prefix: &str) -> String {
+        // `field:prefix` looks up the terms of the field
+        let prefix = match prefix.split_once(':') {
+            Some((field, text)) if self.index.schema().field_type(field).is_some() => {
+                field_term(field, text)
+            }
+            _ => prefix.to_string(),
+        };
+        let terms = match self.index.terms_with_prefix(&prefix) {
+            Ok(terms) => terms,
+            Err(e) => return index_error(e).to_string(),
+        };
+        if terms.is_empty() {
+            return "no terms found".to_string();
+        }
+
+        let mut output = String::new();
+        for term in terms.iter().take(MAX_TERMS) {
+            match self.doc_freq(term) {
+                Ok(doc_freq) => {
+                    let _ = writeln!(output, "{}\t{}", display_term(term), doc_freq);
+                }
+                Err(e) => return e.to_string(),
+            }
+        }
+        if terms.len() > MAX_TERMS {
+            let _ = writeln!(output, "... {} more", terms.len() - MAX_TERMS);
+        }
+        output.pop();
+        output
+    }
+
+    fn doc(&self, id: &str) -> String {
+        let id = match id.parse::<usize>() {
+            Ok(id) => id,
+            Err(_) => return format!("invalid document id: {}", id),
+        };
+        let doc = match self.index.doc(id) {
+            Ok(Some(doc)) => doc,
+            Ok(None) => return format!("document {} not found", id),
+            Err(e) => return e.to_string(),
+        };
+        let mut output = String::new();
+        for field in self.index.schema().fields() {
+            if let Some(value) = doc.field(&field.name) {
+                let _ = writeln!(output, "{}: {}", field.name, value);
+            }
+        }
+        output.pop();
+        output
+    }
+
+    fn set_top_k(&mut self, top_k: &str) -> String {
+        match top_k.parse::<usize>() {
+            Ok(top_k) => {
+                self.config.top_k = top_k;
+                format!("top-k = {}", top_k)
+            }
+            Err(_) => format!("invalid top-k: {}", top_k),
+        }
+    }
+
+    fn stats(&self) -> String {
+        let stats = self.index.stats();
+        format!(
+            "documents\t{}\nterms\t{}\npostings\t{}\ntokens\t{}",
+            stats.doc_count, stats.term_count, stats.posting_count, stats.token_count
+        )
+    }
+
+    fn doc_freq(&self, term: &Term) -> Result<usize> {
+        self.index.doc_freq(term).map_err(index_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shell::{Shell, ShellConfig};
+    use crate::{doc, FieldType, IndexWriter, IndexWriterConfig, Schema};
+
+    fn shell() -> Shell {
+        let mut schema = Schema::new();
+        schema.add_field("title", FieldType::Text);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            schema,
+            ..Default::default()
+        });
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        let mut doc = doc!("dog cat cat fox");
+        doc.set_field("title", "cat and fox");
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        index_writer.write(doc!("camel")).unwrap();
+        Shell::new(index_writer.build(), ShellConfig::default())
+    }
+
+    #[test]
+    fn shell_query_test() {
+        let mut shell = shell();
+        let output = shell.execute("fox").unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "[2] 0.1383\tdog raccoon fox");
+        assert_eq!(lines[1], "[1] 0.1038\tdog cat cat fox");
+        assert!(lines[2].starts_with("2 hits ("));
+        assert!(lines[2].ends_with(" ms)"));
+
+        assert_eq!(shell.execute(":top-k 1").unwrap(), "top-k = 1");
+        assert_eq!(shell.execute("fox").unwrap().lines().count(), 2);
+        assert!(shell.execute("elephant").unwrap().starts_with("0 hits"));
+        assert_eq!(shell.execute("").unwrap(), "");
+    }
+
+    #[test]
+    fn shell_command_test() {
+        let mut shell = shell();
+        assert_eq!(shell.execute(":terms ca").unwrap(), "camel\t1\ncat\t1");
+        assert_eq!(
+            shell.execute(":terms title:").unwrap(),
+            "title:and\t1\ntitle:cat\t1\ntitle:fox\t1"
+        );
+        assert_eq!(shell.execute(":terms x").unwrap(), "no terms found");
+
+        assert_eq!(
+            shell.execute(":doc 1").unwrap(),
+            "body: dog cat cat fox\ntitle: cat and fox"
+        );
+        assert_eq!(shell.execute(":doc 9").unwrap(), "document 9 not found");
+        assert_eq!(shell.execute(":doc x").unwrap(), "invalid document id: x");
+
+        assert_eq!(
+            shell.execute(":explain fox elephant").unwrap(),
+            "4 documents\nfox\tdf=2\tidf=0.4150\tboost=1\nelephant\tdf=0\tidf=2.0000\tboost=1"
+        );
+        assert_eq!(
+            shell.execute(":explain title:fox^2").unwrap(),
+            "4 documents\ntitle:fox\tdf=1\tidf=1.0000\tboost=2"
+        );
+        assert!(shell
+            .execute(":nope")
+            .unwrap()
+            .starts_with("unknown command"));
+        assert_eq!(shell.execute(":quit"), None);
+    }
+}
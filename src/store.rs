@@ -0,0 +1,954 @@
+use crate::codec::*;
+use crate::doc_store::StoreWriter;
+use crate::mapped::*;
+use crate::{Document, Error, FieldType, Index, PostingData, Result, Schema, TokenizeType};
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"IMSR";
+const VERSION: u64 = 1;
+
+const META_FILE: &str = "meta";
+const META_TMP_FILE: &str = "meta.tmp";
+
+// times `open` reads the meta again when a commit removed the files of the one it read
+const OPEN_ATTEMPTS: usize = 3;
+
+// files of a commit, which have the generation of the commit in their names
+const DATA_FILES: [&str; 4] = [TERMS_FILE, POSTINGS_FILE, NORMS_FILE, STORED_FILE];
+
+// files of a field of a commit, which are named by the index of the field in
+// the schema, such as `points2`
+const FIELD_FILES: [&str; 2] = [POINTS_FILE, COLUMN_FILE];
+
+impl Index {
+    // commits the index into `dir`, which is created if it doesn't exist.
+    //
+    // the files of a commit are named by its generation, and synced to disk
+    // before the meta file is atomically replaced by one of the generation.
+    // the files of the other commits are removed then. a crash leaves the
+    // previous commit in `dir`, and the files of the incomplete one are
+    // removed at the next commit. an index opened from `dir` keeps reading the
+    // files of its commit, so it can be saved into `dir`.
+    //
+    // `dir` is assumed to have a single writer, while readers may open it at
+    // any time.
+    //
+    // dir/meta: magic, version, generation, tokenizer, doc_count, next_doc_id,
+    //           field_count, (name, type, boost)*,
+    //           term_count, posting_count, token_count, norm_field_count, norm_field*
+    //
+    // and the files of `MappedIndex` followed by `.generation`, such as
+    // `terms.3` and `column1.3`.
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        Ok(self.write_files(dir.as_ref())?)
+    }
+
+    // opens the last commit written by `save`, and removes the files of the
+    // commits before it. files which a save in progress is writing are left
+    // to the writer. terms and documents are read from the files mapped into
+    // memory when they are used.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut attempts = 0;
+        let (index, generation) = loop {
+            match Index::read_files(dir) {
+                // a commit may have removed the files after its meta was read
+                Err(e) if e.kind() == io::ErrorKind::NotFound && attempts < OPEN_ATTEMPTS => {
+                    attempts += 1
+                }
+                result => break result.map_err(index_error)?,
+            }
+        };
+        // removing old commits is best effort, and they are removed at the next commit
+        let _ = remove_orphans(dir, generation, false);
+        Ok(index)
+    }
+
+    fn write_files(&self, dir: &Path) -> io::Result<()> {
+        let norm_fields = self.norm_fields();
+        let mut commit = CommitWriter::create(dir, norm_fields.clone())?;
+        for term in self.terms_with_prefix("")? {
+            for posting in self.read_postings(&term)?.unwrap_or_default().iter() {
+                commit.add_posting(posting)?;
+            }
+            commit.finish_term(&term)?;
+        }
+        for id in 0..self.next_doc_id {
+            let lengths = norm_fields
+                .iter()
+                .map(|field| self.field_length(id, field))
+                .collect::<io::Result<Vec<_>>>()?;
+            commit.add_norms(id, &lengths)?;
+        }
+        self.for_each_doc(|id, doc| commit.add_doc(id, doc))?;
+
+        for (i, field) in self.schema.fields().enumerate() {
+            let numeric = matches!(field.field_type, FieldType::Numeric | FieldType::Date);
+            if numeric {
+                let mut points_file = commit.field_file(POINTS_FILE, i)?;
+                self.write_points(&field.name, &mut points_file)?;
+                points_file.finish()?;
+            }
+            if numeric || field.field_type == FieldType::Keyword {
+                let mut column_file = commit.field_file(COLUMN_FILE, i)?;
+                self.write_column(field, &mut column_file)?;
+                column_file.finish()?;
+            }
+        }
+        commit.finish(
+            self.tokenize_type,
+            self.doc_count,
+            self.next_doc_id,
+            &self.schema,
+        )
+    }
+
+    // the index and the generation of its commit
+    fn read_files(dir: &Path) -> io::Result<(Self, u64)> {
+        let mut r = BufReader::new(File::open(dir.join(META_FILE))?);
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not an imser index"));
+        }
+        if read_vint(&mut r)? != VERSION {
+            return Err(invalid_data("unsupported index version"));
+        }
+        let generation = read_vint(&mut r)?;
+        let tokenize_type = read_string(&mut r)?
+            .parse::<TokenizeType>()
+            .map_err(|e| invalid_data(&e))?;
+        let doc_count = read_usize(&mut r)?;
+        let next_doc_id = read_usize(&mut r)?;
+        if doc_count > next_doc_id {
+            return Err(invalid_data("doc count is larger than next doc id"));
+        }
+        let mut schema = Schema::new();
+        for _ in 0..read_usize(&mut r)? {
+            let name = read_string(&mut r)?;
+            let field_type = read_string(&mut r)?
+                .parse::<FieldType>()
+                .map_err(|e| invalid_data(&e))?;
+            let boost = f32::from_bits(
+                u32::try_from(read_vint(&mut r)?)
+                    .map_err(|_| invalid_data("boost is out of range"))?,
+            );
+            schema.add_field(&name, field_type).set_boost(&name, boost);
+        }
+        let mut index = Index::new(doc_count, tokenize_type, schema);
+        index.next_doc_id = next_doc_id;
+
+        let counts = Counts {
+            term_count: read_usize(&mut r)?,
+            posting_count: read_usize(&mut r)?,
+            token_count: read_usize(&mut r)?,
+        };
+        let mut norm_fields = Vec::new();
+        for _ in 0..read_usize(&mut r)? {
+            norm_fields.push(read_string(&mut r)?);
+        }
+        index.mapped = Some(MappedIndex::open(
+            dir,
+            generation,
+            counts,
+            next_doc_id,
+            norm_fields,
+            &index.schema,
+        )?);
+        Ok((index, generation))
+    }
+}
+
+// the files of a commit being written into a directory, which `Index::save`
+// writes from an index and `IndexWriter::save` from its segments. postings of
+// each term, norms, documents and the files of the fields are written in this
+// order, then `finish` completes the commit.
+pub(crate) struct CommitWriter<'a> {
+    dir: &'a Path,
+    generation: u64,
+
+    terms: IndexFile,
+    postings: IndexFile,
+    term_offsets: Vec<u64>,
+    posting_count: usize,
+
+    // (postings offset, doc_freq, last doc id) of the term being written
+    term: (u64, usize, usize),
+
+    norm_fields: Vec<String>,
+    norms: IndexFile,
+    // doc id of the next norms
+    next_norms: usize,
+    token_count: usize,
+
+    stored: StoreWriter<IndexFile>,
+}
+
+impl<'a> CommitWriter<'a> {
+    // starts a commit of the next generation in `dir`, which is created if it
+    // doesn't exist
+    pub(crate) fn create(dir: &'a Path, norm_fields: Vec<String>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let generation = next_generation(dir)?;
+        let file = |name: &str| IndexFile::create(&data_file(dir, name, generation));
+        Ok(Self {
+            dir,
+            generation,
+            terms: file(TERMS_FILE)?,
+            postings: file(POSTINGS_FILE)?,
+            term_offsets: Vec::new(),
+            posting_count: 0,
+            term: (0, 0, 0),
+            norm_fields,
+            norms: file(NORMS_FILE)?,
+            next_norms: 0,
+            token_count: 0,
+            stored: StoreWriter::new(file(STORED_FILE)?),
+        })
+    }
+
+    // adds a posting of the next term. postings are added in ascending order of doc ids.
+    pub(crate) fn add_posting(&mut self, posting: &PostingData) -> io::Result<()> {
+        let (_, doc_freq, prev_doc) = &mut self.term;
+        write_usize(&mut self.postings, posting.doc_id - *prev_doc)?;
+        *prev_doc = posting.doc_id;
+        *doc_freq += 1;
+
+        write_usize(&mut self.postings, posting.positions.len())?;
+        let mut prev_position = 0;
+        for position in posting.positions.iter() {
+            write_usize(&mut self.postings, position - prev_position)?;
+            prev_position = *position;
+        }
+        Ok(())
+    }
+
+    // ends the postings added since the last term, which are of `term`. terms
+    // are ended in term order, and a term without postings is left out.
+    pub(crate) fn finish_term(&mut self, term: &str) -> io::Result<()> {
+        let (offset, doc_freq, _) = std::mem::replace(&mut self.term, (self.postings.len, 0, 0));
+        if doc_freq == 0 {
+            return Ok(());
+        }
+        self.term_offsets.push(self.terms.len);
+        write_str(&mut self.terms, term)?;
+        write_usize(&mut self.terms, doc_freq)?;
+        write_vint(&mut self.terms, offset)?;
+        self.posting_count += doc_freq;
+        Ok(())
+    }
+
+    // lengths of the norm fields of a document. documents are added in
+    // ascending order of ids, and those not added have no terms.
+    pub(crate) fn add_norms(&mut self, doc_id: usize, lengths: &[usize]) -> io::Result<()> {
+        while self.next_norms < doc_id {
+            for _ in self.norm_fields.iter() {
+                self.norms.write_all(&0u32.to_le_bytes())?;
+            }
+            self.next_norms += 1;
+        }
+        for length in lengths {
+            self.token_count += length;
+            let length = u32::try_from(*length).map_err(|_| invalid_data("field is too long"))?;
+            self.norms.write_all(&length.to_le_bytes())?;
+        }
+        self.next_norms += 1;
+        Ok(())
+    }
+
+    // documents are added in ascending order of ids
+    pub(crate) fn add_doc(&mut self, id: usize, doc: &Document) -> io::Result<()> {
+        self.stored.add(id, doc)
+    }
+
+    // a file of the `i`th field of the schema, which is finished by the caller
+    pub(crate) fn field_file(&self, name: &str, i: usize) -> io::Result<IndexFile> {
+        let name = field_file(name, i);
+        IndexFile::create(&data_file(self.dir, &name, self.generation))
+    }
+
+    // writes the meta file of the commit, which replaces the last one
+    pub(crate) fn finish(
+        mut self,
+        tokenize_type: TokenizeType,
+        doc_count: usize,
+        next_doc_id: usize,
+        schema: &Schema,
+    ) -> io::Result<()> {
+        if self.next_norms < next_doc_id {
+            self.add_norms(next_doc_id - 1, &vec![0; self.norm_fields.len()])?;
+        }
+        for offset in self.term_offsets.iter() {
+            self.terms.write_all(&offset.to_le_bytes())?;
+        }
+        self.terms.finish()?;
+        self.postings.finish()?;
+        self.norms.finish()?;
+        self.stored.finish()?.finish()?;
+
+        let dir = self.dir;
+        let mut w = IndexFile::create(&dir.join(META_TMP_FILE))?;
+        w.write_all(MAGIC)?;
+        write_vint(&mut w, VERSION)?;
+        write_vint(&mut w, self.generation)?;
+        write_str(&mut w, tokenize_type.as_str())?;
+        write_usize(&mut w, doc_count)?;
+        write_usize(&mut w, next_doc_id)?;
+        let fields = schema.fields().collect::<Vec<_>>();
+        write_usize(&mut w, fields.len())?;
+        for field in fields {
+            write_str(&mut w, &field.name)?;
+            write_str(&mut w, field.field_type.as_str())?;
+            write_vint(&mut w, field.boost.to_bits() as u64)?;
+        }
+        write_usize(&mut w, self.term_offsets.len())?;
+        write_usize(&mut w, self.posting_count)?;
+        write_usize(&mut w, self.token_count)?;
+        write_usize(&mut w, self.norm_fields.len())?;
+        for field in self.norm_fields.iter() {
+            write_str(&mut w, field)?;
+        }
+        w.finish()?;
+
+        // the commit is complete once the new meta file replaces the old one
+        fault_point()?;
+        fs::rename(dir.join(META_TMP_FILE), dir.join(META_FILE))?;
+        fault_point()?;
+        sync_dir(dir)?;
+        // the files of the previous commit may still be in use, and are
+        // removed at the next open or commit if they can't be removed now
+        let _ = remove_orphans(dir, self.generation, true);
+        Ok(())
+    }
+}
+
+// an error reading the files of an index
+pub(crate) fn index_error(e: io::Error) -> Error {
+    match e.kind() {
+        // truncated or broken files
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+            Error::CorruptIndex(e.to_string())
+        }
+        _ => Error::Io(e),
+    }
+}
+
+// path of a file of the commit `generation`
+pub(crate) fn data_file(dir: &Path, name: &str, generation: u64) -> PathBuf {
+    dir.join(format!("{}.{}", name, generation))
+}
+
+// name of the file `name` of the `i`th field of the schema
+pub(crate) fn field_file(name: &str, i: usize) -> String {
+    format!("{}{}", name, i)
+}
+
+// (name, generation) of a file of a commit, or None for other files
+fn parse_data_file(file_name: &str) -> Option<(&str, u64)> {
+    let (name, generation) = file_name.split_once('.')?;
+    let generation = generation.parse::<u64>().ok()?;
+    let field_file = FIELD_FILES.iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|i| !i.is_empty() && i.bytes().all(|b| b.is_ascii_digit()))
+    });
+    (DATA_FILES.contains(&name) || field_file).then_some((name, generation))
+}
+
+// a generation greater than those of all files in `dir`, so that no file
+// of a commit is written twice
+fn next_generation(dir: &Path) -> io::Result<u64> {
+    let mut generation = 0;
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        if let Some((_, g)) = file_name.to_str().and_then(parse_data_file) {
+            generation = generation.max(g);
+        }
+    }
+    Ok(generation + 1)
+}
+
+// removes the files of commits before `generation`, which is the last one.
+// with `incomplete`, which only the writer may pass, files of commits after it
+// and a meta file which didn't replace the last one are removed too, since
+// they are the files of a save which crashed. other files in `dir` are left
+// as they are.
+fn remove_orphans(dir: &Path, generation: u64, incomplete: bool) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let orphan = match file_name.to_str() {
+            Some(META_TMP_FILE) => incomplete,
+            Some(file_name) => parse_data_file(file_name)
+                .is_some_and(|(_, g)| g < generation || (incomplete && g > generation)),
+            None => false,
+        };
+        if orphan {
+            fault_point()?;
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// makes renames and removals in `dir` durable
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    // directories can't be opened as files on windows
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+#[cfg(test)]
+thread_local! {
+    // writes left until they fail in a test, which simulates a crash
+    static WRITES_LEFT: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+}
+
+// fails once the writes left in a test run out
+fn fault_point() -> io::Result<()> {
+    #[cfg(test)]
+    WRITES_LEFT.with(|left| match left.get() {
+        Some(0) => Err(io::Error::other("injected fault")),
+        Some(n) => {
+            left.set(Some(n - 1));
+            Ok(())
+        }
+        None => Ok(()),
+    })?;
+    Ok(())
+}
+
+// a file of a commit, which is synced to disk at `finish`
+pub(crate) struct IndexFile {
+    w: BufWriter<File>,
+
+    // bytes written so far
+    len: u64,
+}
+
+impl IndexFile {
+    fn create(path: &Path) -> io::Result<Self> {
+        fault_point()?;
+        Ok(Self {
+            w: BufWriter::new(File::create(path)?),
+            len: 0,
+        })
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.w.flush()?;
+        fault_point()?;
+        self.w.get_ref().sync_all()
+    }
+}
+
+impl Write for IndexFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Err(e) = fault_point() {
+            // a crash may leave a part of a write on disk
+            self.w.write_all(&buf[..buf.len() / 2])?;
+            self.w.flush()?;
+            return Err(e);
+        }
+        let n = self.w.write(buf)?;
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::doc_store::StoreWriter;
+    use crate::store::{parse_data_file, WRITES_LEFT};
+    use crate::{
+        doc, Error, Facet, FacetCollector, FieldType, Index, IndexWriter, IndexWriterConfig, Order,
+        Schema, Sort, TermQuery, TokenizeType, TopDocs, TopFieldDocs,
+    };
+    use std::fs;
+    use std::ops::Bound;
+    use std::path::{Path, PathBuf};
+
+    // returns an empty directory unique to the test
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("imser-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // path of the file `name` of the commit in `dir`
+    fn commit_file(dir: &Path, name: &str) -> PathBuf {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| {
+                path.file_name()
+                    .and_then(|file_name| file_name.to_str())
+                    .and_then(parse_data_file)
+                    .is_some_and(|(n, _)| n == name)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn save_and_open_test() {
+        let dir = test_dir("save_and_open_test");
+
+        let mut schema = Schema::new();
+        schema
+            .add_field("title", FieldType::Text)
+            .add_field("url", FieldType::Stored)
+            .add_field("date", FieldType::Date)
+            .set_boost("title", 2.5);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            tokenize_type: TokenizeType::Whitespace,
+            schema,
+            ..Default::default()
+        });
+        index_writer.write(doc!("dog dog dog monkey bird")).unwrap();
+        let mut doc = doc!("dog cat cat fox");
+        doc.set_field("title", "cat and fox");
+        doc.set_field("url", "https://example.com/");
+        doc.set_field("date", "2025-01-31");
+        index_writer.write(doc).unwrap();
+        index_writer.write(doc!("dog raccoon fox")).unwrap();
+        let mut index = index_writer.build();
+        index.delete_document(0).unwrap();
+        index.save(&dir).unwrap();
+
+        // an opened index is read from the mapped files
+        let mut opened = Index::open(&dir).unwrap();
+        assert!(opened.mapped.is_some());
+        assert_eq!(opened.stats(), index.stats());
+        assert_eq!(opened.doc(0).unwrap(), None);
+        assert_eq!(opened.doc(1).unwrap(), index.doc(1).unwrap());
+        assert_eq!(opened.doc(5).unwrap(), None);
+        assert_eq!(opened.doc_freq("dog").unwrap(), 2);
+        assert_eq!(opened.doc_freq("monkey").unwrap(), 0);
+        assert_eq!(opened.doc_freq("unknown").unwrap(), 0);
+        assert_eq!(
+            opened.terms_with_prefix("title\0").unwrap(),
+            index.terms_with_prefix("title\0").unwrap()
+        );
+        assert_eq!(opened.field_length(1, "title").unwrap(), 3);
+        let term = "fox".to_string();
+        assert_eq!(opened.freq(2, &term).unwrap(), 1);
+        assert_eq!(
+            opened.tf(2, &term, 1).unwrap(),
+            index.tf(2, &term, 1).unwrap()
+        );
+        assert_eq!(
+            opened.read_postings("cat").unwrap(),
+            index.read_postings("cat").unwrap()
+        );
+        // values of numeric and date fields are read from their points
+        assert_eq!(
+            opened
+                .numeric_range("date", Bound::Unbounded, Bound::Unbounded)
+                .unwrap(),
+            vec![1]
+        );
+
+        let searcher = opened.searcher();
+        let query = searcher.parse_query("title:fox^2 dog").unwrap();
+        assert_eq!(
+            searcher.search(&query, TopDocs::new(10)).unwrap(),
+            index.searcher().search(&query, TopDocs::new(10)).unwrap()
+        );
+
+        opened.load().unwrap();
+        assert!(opened.mapped.is_none());
+        assert_eq!(opened, index);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_opened_test() {
+        let dir = test_dir("save_opened_test");
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.write(doc!("dog fox")).unwrap();
+        let index = index_writer.build();
+        index.save(&dir).unwrap();
+
+        // the mapped files are replaced, and the opened index still reads the old ones
+        let mut opened = Index::open(&dir).unwrap();
+        opened.save(&dir).unwrap();
+        let mut other = Index::open(&dir).unwrap();
+        other.add_document(doc!("bird")).unwrap();
+        other.save(&dir).unwrap();
+        assert_eq!(opened.doc(1).unwrap().as_deref(), Some(&doc!("dog fox")));
+        assert_eq!(opened.stats().doc_count, 2);
+
+        // a mapped index is loaded before it's modified
+        assert_eq!(opened.delete_document(0).unwrap(), Some(doc!("dog cat")));
+        assert!(opened.mapped.is_none());
+        let reopened = Index::open(&dir).unwrap();
+        assert_eq!(reopened.stats().doc_count, 3);
+        assert_eq!(reopened.doc(2).unwrap().as_deref(), Some(&doc!("bird")));
+        assert!(fs::read_dir(&dir).unwrap().all(|entry| !entry
+            .unwrap()
+            .path()
+            .to_string_lossy()
+            .ends_with(".tmp")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_store_test() {
+        let dir = test_dir("compressed_store_test");
+        let mut index_writer = IndexWriter::new();
+        let body = "the quick brown fox jumps over the lazy dog ".repeat(20);
+        for i in 0..200 {
+            index_writer.write(doc!(format!("{} {}", i, body))).unwrap();
+        }
+        let mut index = index_writer.build();
+        index.delete_document(100).unwrap();
+        index.save(&dir).unwrap();
+        let stored = commit_file(&dir, "stored");
+        assert!((fs::metadata(&stored).unwrap().len() as usize) < 200 * body.len() / 10);
+
+        let opened = Index::open(&dir).unwrap();
+        for id in [0, 99, 100, 101, 199, 200] {
+            assert_eq!(opened.doc(id).unwrap(), index.doc(id).unwrap());
+        }
+
+        // a broken block is found when it's read
+        let mut data = fs::read(&stored).unwrap();
+        data[4..12].fill(0xff);
+        fs::write(&stored, &data).unwrap();
+        let mut opened = Index::open(&dir).unwrap();
+        assert!(matches!(opened.doc(0), Err(Error::CorruptIndex(_))));
+        assert!(matches!(
+            opened.delete_document(0),
+            Err(Error::CorruptIndex(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn points_test() {
+        let dir = test_dir("points_test");
+        let mut schema = Schema::new();
+        schema
+            .add_field("price", FieldType::Numeric)
+            .add_field("title", FieldType::Text)
+            .add_field("date", FieldType::Date);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            tokenize_type: TokenizeType::Whitespace,
+            schema,
+            ..Default::default()
+        });
+        for (price, date) in [
+            ("30", Some("2025-03-01")),
+            ("5", Some("2025-01-01")),
+            ("12.5", None),
+        ] {
+            let mut doc = doc!("item");
+            doc.set_field("price", price);
+            if let Some(date) = date {
+                doc.set_field("date", date);
+            }
+            index_writer.write(doc).unwrap();
+        }
+        let mut index = index_writer.build();
+        index.delete_document(0).unwrap();
+        index.save(&dir).unwrap();
+
+        // the points of each numeric and date field are saved in their own files
+        let mut price = Vec::new();
+        index.write_points("price", &mut price).unwrap();
+        assert_eq!(price.len(), 2 * 16);
+        assert_eq!(fs::read(commit_file(&dir, "points1")).unwrap(), price);
+        assert_eq!(fs::read(commit_file(&dir, "points3")).unwrap().len(), 16);
+
+        // and found without the stored documents
+        let stored = StoreWriter::new(Vec::new()).finish().unwrap();
+        fs::write(commit_file(&dir, "stored"), stored).unwrap();
+        let opened = Index::open(&dir).unwrap();
+        assert_eq!(opened.doc(1).unwrap(), None);
+        let searcher = opened.searcher();
+        let search = |query: &str| {
+            let query = searcher.parse_query(query).unwrap();
+            let result = searcher.search(&query, TopDocs::new(10)).unwrap();
+            let mut ids = result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(search("price:[* TO 20]"), vec![1, 2]);
+        assert_eq!(search("price:{5 TO 30]"), vec![2]);
+        assert_eq!(search("date:[2025-01-01 TO *]"), vec![1]);
+
+        // and saved again from the mapped files
+        let other = test_dir("points_test_other");
+        opened.save(&other).unwrap();
+        assert_eq!(fs::read(commit_file(&other, "points1")).unwrap(), price);
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other).unwrap();
+    }
+
+    #[test]
+    fn columns_test() {
+        let dir = test_dir("columns_test");
+        let mut schema = Schema::new();
+        schema
+            .add_field("price", FieldType::Numeric)
+            .add_field("tag", FieldType::Keyword)
+            .add_field("title", FieldType::Text);
+        let mut index_writer = IndexWriter::with_config(IndexWriterConfig {
+            tokenize_type: TokenizeType::Whitespace,
+            schema,
+            ..Default::default()
+        });
+        for (price, tag) in [("30", "rust"), ("5", "go"), ("12.5", "rust"), ("1", "")] {
+            let mut doc = doc!("item");
+            doc.set_field("price", price);
+            doc.set_field("tag", tag);
+            index_writer.write(doc).unwrap();
+        }
+        let index = index_writer.build();
+        index.save(&dir).unwrap();
+
+        // numeric columns have the value of each doc id, and keyword ones the
+        // offset of it followed by the distinct values
+        assert_eq!(fs::read(commit_file(&dir, "column1")).unwrap().len(), 4 * 8);
+        assert_eq!(
+            fs::read(commit_file(&dir, "column2")).unwrap().len(),
+            4 * 8 + "go".len() + "rust".len() + 2
+        );
+        assert!(parse_data_file("column3.1").is_some());
+        assert!(parse_data_file("column.1").is_none());
+        assert!(parse_data_file("columnx.1").is_none());
+
+        // and sort and facets read them without the stored documents
+        let stored = StoreWriter::new(Vec::new()).finish().unwrap();
+        fs::write(commit_file(&dir, "stored"), stored).unwrap();
+        let opened = Index::open(&dir).unwrap();
+        assert_eq!(opened.doc(0).unwrap(), None);
+        let searcher = opened.searcher();
+        let query = searcher.parse_query("item").unwrap();
+        let collector = TopFieldDocs::new(&opened, Sort::by_field("price", Order::Desc), 10);
+        let result = searcher.search(&query, collector.unwrap()).unwrap();
+        let ids = result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 2, 1, 3]);
+        let collector = TopFieldDocs::new(&opened, "tag:asc:last,price".parse().unwrap(), 10);
+        let result = searcher.search(&query, collector.unwrap()).unwrap();
+        let ids = result.docs.iter().map(|d| d.doc_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 0, 3]);
+        let facet = FacetCollector::new(&opened, Facet::terms("tag", 10)).unwrap();
+        let counts = searcher.search(&query, facet).unwrap().counts;
+        assert_eq!(
+            counts
+                .iter()
+                .map(|c| (c.value.as_str(), c.count))
+                .collect::<Vec<_>>(),
+            vec![("rust", 2), ("go", 1)]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_corrupt_test() {
+        let dir = test_dir("open_corrupt_test");
+        assert!(matches!(Index::open(&dir), Err(Error::Io(_))));
+
+        IndexWriter::new().build().save(&dir).unwrap();
+        fs::write(dir.join("meta"), b"XXXX").unwrap();
+        assert!(matches!(Index::open(&dir), Err(Error::CorruptIndex(_))));
+
+        // truncated file
+        IndexWriter::new().build().save(&dir).unwrap();
+        fs::write(dir.join("meta"), b"IMSR").unwrap();
+        assert!(matches!(Index::open(&dir), Err(Error::CorruptIndex(_))));
+
+        // files of a size not matching the meta, and broken postings found at search
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog")).unwrap();
+        index_writer.build().save(&dir).unwrap();
+        fs::write(commit_file(&dir, "norms"), b"").unwrap();
+        assert!(matches!(Index::open(&dir), Err(Error::CorruptIndex(_))));
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog")).unwrap();
+        index_writer.build().save(&dir).unwrap();
+        fs::write(commit_file(&dir, "postings"), [0x80]).unwrap();
+        let mut index = Index::open(&dir).unwrap();
+        let query = TermQuery::new("body", "dog");
+        assert!(matches!(
+            index.searcher().search(&query, TopDocs::new(1)),
+            Err(Error::CorruptIndex(_))
+        ));
+        assert!(matches!(index.load(), Err(Error::CorruptIndex(_))));
+        assert!(index.mapped.is_some());
+
+        // broken terms found by their doc freqs
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog")).unwrap();
+        index_writer.build().save(&dir).unwrap();
+        let terms = commit_file(&dir, "terms");
+        let len = fs::metadata(&terms).unwrap().len() as usize;
+        fs::write(&terms, vec![0xff; len]).unwrap();
+        let index = Index::open(&dir).unwrap();
+        assert!(index.doc_freq("dog").is_err());
+        assert!(matches!(
+            index.searcher().search(&query, TopDocs::new(1)),
+            Err(Error::CorruptIndex(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn commit_test() {
+        let dir = test_dir("commit_test");
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.build().save(&dir).unwrap();
+        let terms = commit_file(&dir, "terms");
+
+        // files of older commits are removed at open, and those of an
+        // incomplete one are left to the writer
+        fs::write(dir.join("terms.0"), b"").unwrap();
+        fs::write(dir.join("terms.9"), b"").unwrap();
+        fs::write(dir.join("meta.tmp"), b"").unwrap();
+        fs::write(dir.join("other"), b"").unwrap();
+        let mut opened = Index::open(&dir).unwrap();
+        assert!(!dir.join("terms.0").exists());
+        assert!(dir.join("terms.9").exists());
+        assert!(dir.join("meta.tmp").exists());
+        assert!(dir.join("other").exists());
+
+        // the files of the other commits are removed after a commit
+        opened.add_document(doc!("dog fox")).unwrap();
+        opened.save(&dir).unwrap();
+        assert!(!terms.exists());
+        assert!(!dir.join("terms.9").exists());
+        assert!(!dir.join("meta.tmp").exists());
+        assert!(dir.join("other").exists());
+        assert_eq!(commit_file(&dir, "terms"), dir.join("terms.10"));
+        assert_eq!(Index::open(&dir).unwrap().stats().doc_count, 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn commit_fault_test() {
+        let dir = test_dir("commit_fault_test");
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.write(doc!("dog fox")).unwrap();
+        let first = index_writer.build();
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        index_writer.write(doc!("dog fox")).unwrap();
+        let mut second = index_writer.build();
+        second.add_document(doc!("cat bird")).unwrap();
+        second.delete_document(0).unwrap();
+
+        let save = |index: &Index, writes: Option<usize>| {
+            WRITES_LEFT.with(|left| left.set(writes));
+            let result = index.save(&dir);
+            WRITES_LEFT.with(|left| left.set(None));
+            result
+        };
+
+        // counts the writes of a commit, and crashes the commit at each of them
+        first.save(&dir).unwrap();
+        WRITES_LEFT.with(|left| left.set(Some(usize::MAX)));
+        second.save(&dir).unwrap();
+        let writes = usize::MAX - WRITES_LEFT.with(|left| left.replace(None)).unwrap();
+        assert!(writes > 10);
+        save(&first, None).unwrap();
+        for n in 0..writes {
+            let result = save(&second, Some(n));
+
+            let mut opened = Index::open(&dir).unwrap();
+            opened.load().unwrap();
+            if result.is_ok() {
+                assert_eq!(opened, second, "{}", n);
+            } else {
+                assert!(opened == first || opened == second, "{}", n);
+            }
+            // only the files of the last commit are left after the next one
+            save(&first, None).unwrap();
+            let mut files = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            files.sort();
+            let generation = files[1].rsplit('.').next().unwrap().to_string();
+            let expected =
+                ["meta", "norms", "postings", "stored", "terms"].map(|name| match name {
+                    "meta" => name.to_string(),
+                    _ => format!("{}.{}", name, generation),
+                });
+            assert_eq!(files, expected, "{}", n);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_during_commit_test() {
+        let dir = test_dir("open_during_commit_test");
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog cat")).unwrap();
+        let first = index_writer.build();
+        let mut index_writer = IndexWriter::new();
+        index_writer.write(doc!("dog fox")).unwrap();
+        index_writer.write(doc!("cat bird")).unwrap();
+        let second = index_writer.build();
+
+        // stops a commit of `second` after the meta file is written, right
+        // before it replaces the last one, which is the last fault point
+        // leaving meta.tmp
+        first.save(&dir).unwrap();
+        WRITES_LEFT.with(|left| left.set(Some(usize::MAX)));
+        second.save(&dir).unwrap();
+        let writes = usize::MAX - WRITES_LEFT.with(|left| left.replace(None)).unwrap();
+        let stopped = (0..writes).rev().any(|n| {
+            first.save(&dir).unwrap();
+            WRITES_LEFT.with(|left| left.set(Some(n)));
+            let result = second.save(&dir);
+            WRITES_LEFT.with(|left| left.set(None));
+            result.is_err() && dir.join("meta.tmp").exists()
+        });
+        assert!(stopped);
+        let files = |dir: &Path| {
+            let mut files = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        let written = files(&dir);
+
+        // a reader opens the last commit and leaves the files of the save
+        let mut opened = Index::open(&dir).unwrap();
+        opened.load().unwrap();
+        assert_eq!(opened, first);
+        assert_eq!(files(&dir), written);
+
+        // which the writer completes
+        fs::rename(dir.join("meta.tmp"), dir.join("meta")).unwrap();
+        let mut opened = Index::open(&dir).unwrap();
+        opened.load().unwrap();
+        assert_eq!(opened, second);
+        assert_eq!(files(&dir).len(), 5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
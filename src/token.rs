@@ -1,5 +1,8 @@
+use crate::{Error, Result};
 use lindera::tokenizer::{Tokenizer, TokenizerConfig};
 use lindera_core::viterbi::{Mode, Penalty};
+use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq)]
 pub enum TokenKind<'a> {
@@ -41,28 +44,50 @@ impl<'a> Token<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub enum TokenizeType {
+    #[default]
     Whitespace,
     Japanese,
 }
 
-impl Default for TokenizeType {
-    fn default() -> Self {
-        TokenizeType::Whitespace
+impl TokenizeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenizeType::Whitespace => "whitespace",
+            TokenizeType::Japanese => "japanese",
+        }
     }
 }
 
-fn japanese_tokenize(sentence: &str) -> Vec<Token> {
-    let mut tokenizer = Tokenizer::with_config(TokenizerConfig {
-        dict_path: None,
+impl FromStr for TokenizeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "whitespace" => Ok(TokenizeType::Whitespace),
+            "japanese" => Ok(TokenizeType::Japanese),
+            _ => Err(format!("unknown tokenizer: {}", s)),
+        }
+    }
+}
+
+// uses the bundled IPADIC dictionary unless `dict_path` is given
+fn japanese_tokenizer(dict_path: Option<&Path>) -> Result<Tokenizer> {
+    Tokenizer::with_config(TokenizerConfig {
+        dict_path,
         user_dict_path: None,
         user_dict_bin_path: None,
         mode: Mode::Decompose(Penalty::default()),
     })
-    .unwrap();
+    .map_err(|e| Error::AnalyzerInit(e.to_string()))
+}
 
-    let tokens = tokenizer.tokenize(sentence).unwrap();
+fn japanese_tokenize(sentence: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokenizer = japanese_tokenizer(None)?;
+    let tokens = tokenizer
+        .tokenize(sentence)
+        .map_err(|e| Error::AnalyzerInit(e.to_string()))?;
     let mut base_offset = 0;
     let mut word_count = 0;
 
@@ -85,10 +110,10 @@ fn japanese_tokenize(sentence: &str) -> Vec<Token> {
         base_offset += token.text.len();
         ret.push(term);
     }
-    ret
+    Ok(ret)
 }
 
-fn whitespace_tokenize(sentence: &str) -> Vec<Token> {
+fn whitespace_tokenize(sentence: &str) -> Vec<Token<'_>> {
     let mut tokens = Vec::new();
 
     let mut length = 0;
@@ -143,23 +168,49 @@ fn whitespace_tokenize(sentence: &str) -> Vec<Token> {
     tokens
 }
 
-pub fn tokenize(tokenize_type: TokenizeType, sentence: &str) -> Vec<Token> {
+pub fn tokenize(tokenize_type: TokenizeType, sentence: &str) -> Result<Vec<Token<'_>>> {
     match tokenize_type {
         TokenizeType::Japanese => japanese_tokenize(sentence),
-        TokenizeType::Whitespace => whitespace_tokenize(sentence),
+        TokenizeType::Whitespace => Ok(whitespace_tokenize(sentence)),
+    }
+}
+
+// splits text into tokens. the same analyzer must be used for indexing and searching,
+// so that terms of queries match indexed terms.
+pub trait Analyzer {
+    fn tokenize<'a>(&self, text: &'a str) -> Result<Vec<Token<'a>>>;
+
+    // terms of the text in order, without punctuation
+    fn terms(&self, text: &str) -> Result<Vec<String>> {
+        Ok(self
+            .tokenize(text)?
+            .into_iter()
+            .filter_map(|t| match t.kind {
+                TokenKind::Term(term) => Some(term.to_string()),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+impl Analyzer for TokenizeType {
+    fn tokenize<'a>(&self, text: &'a str) -> Result<Vec<Token<'a>>> {
+        tokenize(*self, text)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{tokenize, Token, TokenizeType};
+    use crate::token::japanese_tokenizer;
+    use crate::{tokenize, Analyzer, Error, Token, TokenizeType};
+    use std::path::Path;
 
     #[test]
     fn japanese_tokenize_test() {
-        assert_eq!(tokenize(TokenizeType::Japanese, ""), vec![]);
+        assert_eq!(tokenize(TokenizeType::Japanese, "").unwrap(), vec![]);
 
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "関西国際空港限定トートバッグ"),
+            tokenize(TokenizeType::Japanese, "関西国際空港限定トートバッグ").unwrap(),
             vec![
                 Token::new_term("関西", 0, 0),
                 Token::new_term("国際", 6, 1),
@@ -170,7 +221,7 @@ mod tests {
         );
 
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "すもももももももものうち"),
+            tokenize(TokenizeType::Japanese, "すもももももももものうち").unwrap(),
             vec![
                 Token::new_term("すもも", 0, 0),
                 Token::new_term("も", 9, 1),
@@ -184,60 +235,82 @@ mod tests {
 
         // 動詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "好き"),
+            tokenize(TokenizeType::Japanese, "好き").unwrap(),
             vec![Token::new_term("好き", 0, 0)]
         );
         // 形容詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "赤い"),
+            tokenize(TokenizeType::Japanese, "赤い").unwrap(),
             vec![Token::new_term("赤い", 0, 0)]
         );
         // 形容動詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "静かだ"),
+            tokenize(TokenizeType::Japanese, "静かだ").unwrap(),
             vec![Token::new_term("静か", 0, 0), Token::new_term("だ", 6, 1)]
         );
         // 助詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "見て"),
+            tokenize(TokenizeType::Japanese, "見て").unwrap(),
             vec![Token::new_term("見", 0, 0), Token::new_term("て", 3, 1)]
         );
         // 助動詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "見えない"),
+            tokenize(TokenizeType::Japanese, "見えない").unwrap(),
             vec![Token::new_term("見え", 0, 0), Token::new_term("ない", 6, 1)]
         );
         // 副詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "ゆっくり"),
+            tokenize(TokenizeType::Japanese, "ゆっくり").unwrap(),
             vec![Token::new_term("ゆっくり", 0, 0)]
         );
         // 連体詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "大きな"),
+            tokenize(TokenizeType::Japanese, "大きな").unwrap(),
             vec![Token::new_term("大きな", 0, 0)]
         );
         // 接続詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "そして"),
+            tokenize(TokenizeType::Japanese, "そして").unwrap(),
             vec![Token::new_term("そして", 0, 0)]
         );
         // 感動詞
         assert_eq!(
-            tokenize(TokenizeType::Japanese, "あら"),
+            tokenize(TokenizeType::Japanese, "あら").unwrap(),
             vec![Token::new_term("あら", 0, 0)]
         );
     }
 
+    #[test]
+    fn analyzer_init_error_test() {
+        let tokenizer = japanese_tokenizer(Some(Path::new("/nonexistent/dictionary")));
+        assert!(matches!(tokenizer, Err(Error::AnalyzerInit(_))));
+    }
+
+    #[test]
+    fn tokenize_type_test() {
+        for tokenize_type in [TokenizeType::Whitespace, TokenizeType::Japanese] {
+            assert_eq!(tokenize_type.as_str().parse(), Ok(tokenize_type));
+        }
+        assert!("mecab".parse::<TokenizeType>().is_err());
+
+        assert_eq!(
+            TokenizeType::Whitespace.terms("I am, Taisuke").unwrap(),
+            vec!["I", "am", "Taisuke"]
+        );
+    }
+
     #[test]
     fn whitespace_tokenize_test() {
         let sentence = "".to_string();
-        assert_eq!(tokenize(TokenizeType::Whitespace, &sentence), vec![]);
+        assert_eq!(
+            tokenize(TokenizeType::Whitespace, &sentence).unwrap(),
+            vec![]
+        );
 
         let sentence = "I am  Taisuke".to_string();
 
         assert_eq!(
-            tokenize(TokenizeType::Whitespace, &sentence),
+            tokenize(TokenizeType::Whitespace, &sentence).unwrap(),
             vec![
                 Token::new_term("I", 0, 0),
                 Token::new_term("am", 2, 1),
@@ -247,7 +320,7 @@ mod tests {
 
         let sentence = "I am Taisuke.".to_string();
         assert_eq!(
-            tokenize(TokenizeType::Whitespace, &sentence),
+            tokenize(TokenizeType::Whitespace, &sentence).unwrap(),
             vec![
                 Token::new_term("I", 0, 0),
                 Token::new_term("am", 2, 1),
@@ -258,7 +331,7 @@ mod tests {
 
         let sentence = "What is that?".to_string();
         assert_eq!(
-            tokenize(TokenizeType::Whitespace, &sentence),
+            tokenize(TokenizeType::Whitespace, &sentence).unwrap(),
             vec![
                 Token::new_term("What", 0, 0),
                 Token::new_term("is", 5, 1),
@@ -269,7 +342,7 @@ mod tests {
 
         let sentence = "What's that?".to_string();
         assert_eq!(
-            tokenize(TokenizeType::Whitespace, &sentence),
+            tokenize(TokenizeType::Whitespace, &sentence).unwrap(),
             vec![
                 Token::new_term("What", 0, 0),
                 Token::new_punct("'", 4, 1),
@@ -281,7 +354,7 @@ mod tests {
 
         let sentence = "すもも も もも も もも の うち";
         assert_eq!(
-            tokenize(TokenizeType::Whitespace, sentence),
+            tokenize(TokenizeType::Whitespace, sentence).unwrap(),
             vec![
                 Token::new_term("すもも", 0, 0),
                 Token::new_term("も", 10, 1),
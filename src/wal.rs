@@ -0,0 +1,341 @@
+use crate::codec::*;
+use crate::Document;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const WRITE_OP: u8 = 0;
+const DELETE_OP: u8 = 1;
+
+// when operations logged to a write-ahead log are synced to disk. every
+// operation is written to the file before it's applied, so a crash of the
+// process loses none of them, and the policy is about a crash of the system.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SyncPolicy {
+    // every operation
+    #[default]
+    Always,
+
+    // operations logged in the last interval, which a background thread syncs
+    // at the end of it. a zero interval is the same as Always.
+    Interval(Duration),
+
+    // when the system writes the file back
+    Never,
+}
+
+// an operation of an index writer or a server, with the id of the document
+// written or deleted
+#[derive(Debug, PartialEq)]
+pub(crate) enum Op {
+    Write(usize, Document),
+    Delete(usize),
+}
+
+// a log of the operations written to an index writer since it was created.
+//
+// wal:    record*
+// record: payload length u32 LE, checksum of payload u32 LE, payload
+// payload: WRITE_OP, doc_id, body, field_count, (name, value)*
+//        | DELETE_OP, doc_id
+//
+// a crash may leave a torn record at the end, which is dropped at open.
+// operations not synced yet are synced when the log is dropped, unless the
+// policy is Never.
+#[derive(Debug)]
+pub(crate) struct Wal {
+    file: Arc<File>,
+    sync: SyncPolicy,
+
+    // whether operations were logged since the last sync
+    dirty: Arc<AtomicBool>,
+
+    syncer: Option<Syncer>,
+}
+
+// a thread syncing the operations of a log at every interval
+#[derive(Debug)]
+struct Syncer {
+    // stops the thread when dropped
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+
+    // the first failure to sync, which the next operation returns
+    error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl Syncer {
+    fn spawn(file: Arc<File>, dirty: Arc<AtomicBool>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let error = Arc::new(Mutex::new(None));
+        let thread_error = error.clone();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if dirty.swap(false, Ordering::AcqRel) {
+                    if let Err(e) = file.sync_data() {
+                        let mut error = thread_error.lock().unwrap_or_else(|e| e.into_inner());
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+        });
+        Self {
+            stop,
+            handle,
+            error,
+        }
+    }
+
+    fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+impl Wal {
+    // opens the log at `path`, creating it if it doesn't exist, and returns
+    // the operations logged in it. the log is truncated at the first broken record.
+    pub(crate) fn open(path: &Path, sync: SyncPolicy) -> io::Result<(Self, Vec<Op>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut ops = Vec::new();
+        let mut offset = 0;
+        while let Some((op, len)) = read_record(&data[offset..]) {
+            ops.push(op);
+            offset += len;
+        }
+        if offset < data.len() {
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+        let file = Arc::new(file);
+        let dirty = Arc::new(AtomicBool::new(false));
+        let syncer = match sync {
+            SyncPolicy::Interval(interval) if !interval.is_zero() => {
+                Some(Syncer::spawn(file.clone(), dirty.clone(), interval))
+            }
+            _ => None,
+        };
+        let wal = Self {
+            file,
+            sync,
+            dirty,
+            syncer,
+        };
+        Ok((wal, ops))
+    }
+
+    // `id` is the id the document is given
+    pub(crate) fn log_write(&mut self, id: usize, doc: &Document) -> io::Result<()> {
+        let mut payload = vec![WRITE_OP];
+        write_usize(&mut payload, id)?;
+        write_str(&mut payload, &doc.body)?;
+        write_usize(&mut payload, doc.fields.len())?;
+        for (name, value) in doc.fields.iter() {
+            write_str(&mut payload, name)?;
+            write_str(&mut payload, value)?;
+        }
+        self.append(&payload)
+    }
+
+    pub(crate) fn log_delete(&mut self, id: usize) -> io::Result<()> {
+        let mut payload = vec![DELETE_OP];
+        write_usize(&mut payload, id)?;
+        self.append(&payload)
+    }
+
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        if let Some(e) = self.syncer.as_ref().and_then(Syncer::take_error) {
+            return Err(e);
+        }
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "operation is too large"))?;
+        let mut record = Vec::with_capacity(payload.len() + 8);
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&checksum(payload).to_le_bytes());
+        record.extend_from_slice(payload);
+        // a single write, which the system keeps even if the process crashes
+        (&*self.file).write_all(&record)?;
+
+        match self.sync {
+            SyncPolicy::Always | SyncPolicy::Interval(Duration::ZERO) => self.file.sync_data(),
+            _ => {
+                self.dirty.store(true, Ordering::Release);
+                Ok(())
+            }
+        }
+    }
+
+    // syncs the operations logged since the last sync
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if self.dirty.swap(false, Ordering::AcqRel) {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    // removes every operation once they are in a committed index
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.dirty.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for Wal {
+    fn drop(&mut self) {
+        if let Some(syncer) = self.syncer.take() {
+            drop(syncer.stop);
+            let _ = syncer.handle.join();
+        }
+        if self.sync != SyncPolicy::Never {
+            let _ = self.sync();
+        }
+    }
+}
+
+// an operation and the length of its record, or None if `data` doesn't start
+// with a complete record
+fn read_record(data: &[u8]) -> Option<(Op, usize)> {
+    let u32_at = |start: usize| -> Option<u32> {
+        let bytes = data.get(start..start + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let len = u32_at(0)? as usize;
+    let payload = data.get(8..8 + len)?;
+    if checksum(payload) != u32_at(4)? {
+        return None;
+    }
+    let op = read_op(payload).ok()?;
+    Some((op, 8 + len))
+}
+
+fn read_op(mut r: &[u8]) -> io::Result<Op> {
+    let mut kind = [0u8];
+    r.read_exact(&mut kind)?;
+    let op = match kind[0] {
+        WRITE_OP => {
+            let id = read_usize(&mut r)?;
+            let mut doc = Document::new(read_string(&mut r)?);
+            for _ in 0..read_usize(&mut r)? {
+                let name = read_string(&mut r)?;
+                let value = read_string(&mut r)?;
+                doc.set_field(&name, value);
+            }
+            Op::Write(id, doc)
+        }
+        DELETE_OP => Op::Delete(read_usize(&mut r)?),
+        _ => return Err(invalid_data("unknown operation")),
+    };
+    if !r.is_empty() {
+        return Err(invalid_data("trailing bytes in an operation"));
+    }
+    Ok(op)
+}
+
+// FNV-1a, which finds a record partly written or overwritten
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc;
+    use crate::store::tests::test_dir;
+    use crate::wal::{Op, SyncPolicy, Wal};
+    use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn wal_test() {
+        let dir = test_dir("wal_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wal");
+        let mut doc = doc!("dog cat");
+        doc.set_field("id", "1".to_string());
+
+        let (mut wal, ops) = Wal::open(&path, SyncPolicy::Always).unwrap();
+        assert!(ops.is_empty());
+        wal.log_write(0, &doc).unwrap();
+        wal.log_delete(0).unwrap();
+        drop(wal);
+        let (mut wal, ops) = Wal::open(&path, SyncPolicy::Interval(Duration::ZERO)).unwrap();
+        assert_eq!(ops, vec![Op::Write(0, doc.clone()), Op::Delete(0)]);
+        wal.log_write(1, &doc!("fox")).unwrap();
+        drop(wal);
+
+        // a torn record is dropped, and the next one is written after the others
+        let len = fs::metadata(&path).unwrap().len();
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 1).unwrap();
+        drop(file);
+        let (mut wal, ops) = Wal::open(&path, SyncPolicy::Never).unwrap();
+        assert_eq!(ops, vec![Op::Write(0, doc.clone()), Op::Delete(0)]);
+        wal.log_delete(1).unwrap();
+        drop(wal);
+        let (_, ops) = Wal::open(&path, SyncPolicy::Never).unwrap();
+        assert_eq!(
+            ops,
+            vec![Op::Write(0, doc.clone()), Op::Delete(0), Op::Delete(1)]
+        );
+
+        // so is a broken one, with the records after it
+        let mut data = fs::read(&path).unwrap();
+        data[10] ^= 0xff;
+        fs::write(&path, data).unwrap();
+        let (mut wal, ops) = Wal::open(&path, SyncPolicy::Never).unwrap();
+        assert!(ops.is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        wal.log_write(2, &doc).unwrap();
+        wal.clear().unwrap();
+        let (_, ops) = Wal::open(&path, SyncPolicy::Never).unwrap();
+        assert!(ops.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wal_interval_test() {
+        let dir = test_dir("wal_interval");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wal");
+        let sync = SyncPolicy::Interval(Duration::from_millis(10));
+
+        let (mut wal, _) = Wal::open(&path, sync).unwrap();
+        wal.log_write(0, &doc!("dog")).unwrap();
+        // the last operation is synced without another one
+        let start = Instant::now();
+        while wal.dirty.load(Ordering::Acquire) {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // and so is one logged right before the log is dropped
+        wal.log_delete(0).unwrap();
+        assert!(wal.dirty.load(Ordering::Acquire));
+        drop(wal);
+        let (wal, ops) = Wal::open(&path, sync).unwrap();
+        assert_eq!(ops, vec![Op::Write(0, doc!("dog")), Op::Delete(0)]);
+        assert!(!wal.dirty.load(Ordering::Acquire));
+
+        drop(wal);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
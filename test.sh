@@ -20,24 +20,134 @@ echo "+cargo fmt -- --check ==========="
 cargo fmt -- --check
 echo "================================="
 
+workdir=$(mktemp -d)
+trap 'rm -rf "$workdir"' EXIT
+
 assert() {
     expected=$1
-    term=$2
+    expected_status=$2
     shift; shift;
-    sentences=$@
 
-    actual=$(./target/debug/imser "$term" "$sentences" 2>&1)
+    actual=$(./target/debug/imser "$@" 2>&1)
+    status=$?
 
-    if [ "$expected" = "$actual" ]; then
-        echo "search \"$term\" from \"$sentences\" => $actual"
+    if [ "$expected" = "$actual" ] && [ "$expected_status" = "$status" ]; then
+        echo "imser $* => $actual"
     else
-        echo "search \"$term\" from \"$sentences\" => $expected, but got \"$actual\""
+        echo "imser $* => $expected ($expected_status), but got \"$actual\" ($status)"
         exit 1
     fi
 }
 
-assert "I am Taisuke" "Taisuke" "I am Taisuke"
-assert "that that is is that that is not is not is that it it is" "that" "that that is is that that is not is not is that it it is"
-assert "term not found: foo" "foo" "This is a pen"
+mkdir -p "$workdir/docs/sub"
+echo "I am Taisuke" > "$workdir/docs/a.txt"
+echo "that that is is that that is not is not is that it it is" > "$workdir/docs/sub/b.txt"
+echo "This is a pen" >> "$workdir/docs/sub/b.txt"
+
+assert "indexed 3 documents into $workdir/idx" 0 index "$workdir/docs" --out "$workdir/idx" --input lines
+assert "I am Taisuke" 0 search "$workdir/idx" "Taisuke"
+assert "that that is is that that is not is not is that it it is" 0 search "$workdir/idx" "that"
+assert "term not found: foo" 1 search "$workdir/idx" "foo"
+assert "This is a pen" 0 search "$workdir/idx" "pen" --format full
+assert "that that is is that that is not is not is that it it is" 0 search "$workdir/idx" "is" --top-k 1
+assert "This is a pen" 0 search "$workdir/idx" "is" --top-k 1 --offset 1
+assert "This is a pen" 0 search "$workdir/idx" "is" --top-k 1 --search-after 0,1
+assert "" 0 search "$workdir/idx" "is" --search-after 0,2
+assert "invalid --search-after: 1" 2 search "$workdir/idx" "is" --search-after 1
+assert '{"hits":[{"fields":{"body":"I am Taisuke"},"highlights":{"body":["I am <em>Taisuke</em>"]},"id":0,"score":0.1949875}],"total_hits":1}' 0 search "$workdir/idx" "Taisuke" --format json
+assert '{"hits":[],"total_hits":0}' 1 search "$workdir/idx" "foo" --format json
+assert '{"fields":{"body":"that that is is that that is not is not is that it it is"},"highlights":{"body":["that that <em>is</em> <em>is</em> that that <em>is</em> not <em>is</em> not <em>is</em> that it it <em>is</em>"]},"id":1,"score":0.0,"total_hits":2}' 0 search "$workdir/idx" "is" --format jsonl --top-k 1
+assert "id	score	body	title	path	highlight
+0	0.1949875	I am Taisuke			I am <em>Taisuke</em>
+1 hits" 0 search "$workdir/idx" "Taisuke" --format tsv
+assert "tokenizer	whitespace
+documents	3
+terms	10
+postings	11
+tokens	22" 0 stats "$workdir/idx"
+
+# a rerun of an interrupted run indexes the input once
+assert "$workdir/docs/a.txt/idx: Not a directory (os error 20)" 2 index "$workdir/docs" --out "$workdir/docs/a.txt/idx" --input lines
+assert "indexed 3 documents into $workdir/rerun_idx" 0 index "$workdir/docs" --out "$workdir/rerun_idx" --input lines
+assert "tokenizer	whitespace
+documents	3
+terms	10
+postings	11
+tokens	22" 0 stats "$workdir/rerun_idx"
+assert "unknown option: --wal" 2 index "$workdir/docs" --out "$workdir/rerun_idx" --wal "$workdir/wal"
+
+mkdir -p "$workdir/jsonl"
+cat > "$workdir/jsonl/docs.jsonl" <<EOF
+{"title": "greeting", "text": "I am Taisuke"}
+{"title": "broken"
+{"title": "pen", "text": "This is a pen"}
+EOF
+assert "$workdir/jsonl/docs.jsonl:2: skipped: invalid JSON: EOF while parsing an object at line 1 column 18
+indexed 2 documents into $workdir/jsonl_idx" 0 index "$workdir/jsonl" --out "$workdir/jsonl_idx" --field title:stored --map body=text
+assert "This is a pen" 0 search "$workdir/jsonl_idx" "pen"
+assert "$workdir/jsonl/docs.jsonl:2: invalid JSON: EOF while parsing an object at line 1 column 18" 2 index "$workdir/jsonl" --out "$workdir/jsonl_idx" --map body=text --on-error abort
+
+mkdir -p "$workdir/csv"
+cat > "$workdir/csv/docs.csv" <<EOF
+title,text
+greeting,I am Taisuke
+multiline,"that that is is that that is not
+is not is that it it is"
+EOF
+printf 'title\ttext\npen\tThis is a pen\n' > "$workdir/csv/docs.tsv"
+assert "indexed 3 documents into $workdir/csv_idx" 0 index "$workdir/csv" --out "$workdir/csv_idx" --field title:text --map body=text
+assert "I am Taisuke" 0 search "$workdir/csv_idx" "Taisuke"
+assert "I am Taisuke" 0 search "$workdir/csv_idx" "title:greeting^2 Taisuke"
+assert "This is a pen" 0 search "$workdir/csv_idx" "pen" --format full
+assert "indexed 3 documents into $workdir/csv_threads_idx" 0 index "$workdir/csv" --out "$workdir/csv_threads_idx" --field title:text --map body=text --threads 4
+assert "I am Taisuke" 0 search "$workdir/csv_threads_idx" "title:greeting^2 Taisuke"
+assert "invalid --threads: invalid digit found in string" 2 index "$workdir/csv" --out "$workdir/csv_threads_idx" --threads x
+assert "indexed 3 documents into $workdir/csv_budget_idx" 0 index "$workdir/csv" --out "$workdir/csv_budget_idx" --field title:text --map body=text --threads 2 --ram-budget 1
+assert "I am Taisuke" 0 search "$workdir/csv_budget_idx" "title:greeting^2 Taisuke"
+assert "invalid --ram-budget: invalid digit found in string" 2 index "$workdir/csv" --out "$workdir/csv_budget_idx" --ram-budget x
+
+mkdir -p "$workdir/range"
+cat > "$workdir/range/docs.csv" <<EOF
+text,price,date,category
+red pen,120,2025-01-31,stationery
+blue pen,80,2025-03-01,stationery
+black pen,,2024-12-24,gifts
+EOF
+assert "indexed 3 documents into $workdir/range_idx" 0 index "$workdir/range" --out "$workdir/range_idx" --field price:numeric --field date:date --field category:keyword --map body=text
+assert "blue pen" 0 search "$workdir/range_idx" "pen price:[* TO 100]"
+assert "red pen" 0 search "$workdir/range_idx" "pen price:{80 TO *] date:[2025-01-01 TO 2025-12-31]"
+assert "black pen" 0 search "$workdir/range_idx" "date:2024-12-24"
+assert "red pen
+blue pen
+black pen" 0 search "$workdir/range_idx" "pen" --sort price:desc
+assert "black pen
+blue pen" 0 search "$workdir/range_idx" "pen" --sort price:asc:first --top-k 2
+assert "red pen
+black pen" 0 search "$workdir/range_idx" "pen" --sort date:desc,score --offset 1
+assert "invalid query: field is not sortable: text" 2 search "$workdir/range_idx" "pen" --sort text
+assert "black pen" 0 search "$workdir/range_idx" "pen category:gifts"
+assert "red pen
+
+category	stationery	1
+price	price:[* TO 100}	0
+price	price:[100 TO *]	1" 0 search "$workdir/range_idx" "red" --facet category --facet "price:[* TO 100},[100 TO *]"
+assert '{"facets":[{"counts":[{"count":2,"value":"stationery"},{"count":1,"value":"gifts"}],"field":"category"}]}' 0 search "$workdir/range_idx" "pen" --facet category --format jsonl --top-k 0
+assert "invalid query: invalid facet: text" 2 search "$workdir/range_idx" "pen" --facet text
+
+mkdir -p "$workdir/md/drafts"
+printf '# Getting started\n\nrun imser index\n' > "$workdir/md/guide.md"
+printf 'Notes\nrun imser search\n' > "$workdir/md/notes.txt"
+printf 'run imser shell\n' > "$workdir/md/drafts/wip.md"
+printf 'run\0imser\n' > "$workdir/md/binary.txt"
+assert "$workdir/md/binary.txt: skipped: binary file
+indexed 2 documents into $workdir/md_idx" 0 index "$workdir/md" --out "$workdir/md_idx" --exclude drafts
+assert "Notes
+run imser search" 0 search "$workdir/md_idx" "search" --format full
+
+assert "0 = no match, missing terms: Taisuke
+  0 = no match of Taisuke" 1 explain "$workdir/idx" 1 "Taisuke"
+assert '{"description":"sum of:","details":[{"description":"weight(pen), product of:","details":[{"description":"boost, product of:","details":[{"description":"query boost","details":[],"value":1.0},{"description":"field boost","details":[],"value":1.0}],"value":1.0},{"description":"tf, computed as freq / field length from:","details":[{"description":"freq, occurrences of the term in the field","details":[],"value":1.0},{"description":"field length, the doc length norm","details":[],"value":4.0}],"value":0.25},{"description":"idf, computed as log2(doc count / (doc freq + 1)) from:","details":[{"description":"doc count, number of documents","details":[],"value":3.0},{"description":"doc freq, number of documents with the term","details":[],"value":1.0}],"value":0.5849625}],"value":0.14624062}],"value":0.14624062}' 0 explain "$workdir/idx" 2 "pen" --format json
+assert "invalid --port: invalid digit found in string" 2 serve "$workdir/md_idx" --port http
+assert "invalid --commit-interval: invalid digit found in string" 2 serve "$workdir/md_idx" --commit-interval 1s
 
 echo OK